@@ -0,0 +1,71 @@
+/*Benchmark of the external product TGSW x LWE (the linear algebra of every obfuscated gate), on random ciphertexts
+of the shapes of the parameter sets of main.rs, against the column-wise product of the original implementation
+Run with: cargo bench --bench ext_product
+*/
+#![allow(non_snake_case)]
+use ohlg::tgsw::*;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::commons::math::random::{DefaultRandomGenerator, RandomGenerator, Seed};
+use tfhe::core_crypto::prelude::{CiphertextModulus, LweCiphertext};
+
+#[path = "../tests/common/column_product.rs"]
+mod column_product;
+use column_product::column_product;
+
+fn average_time(trials: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..trials {
+        f();
+    }
+    start.elapsed() / trials
+}
+
+fn main() {
+    let trials = 20;
+    for (name, tgsw_params) in [
+        ("TFHE_RS", TgswParams::new(DEFAULT_PARAMETERS, 64, 2)),
+        ("TFHE_IO", TgswParams::new(TFHE_LIB_PARAMETERS, 16, 3)),
+        (
+            "TFHE_RS_KS_PBS",
+            TgswParams::new(DEFAULT_PARAMETERS_KS_PBS, 64, 2),
+        ),
+    ] {
+        let B = tgsw_params.decomp_base;
+        let l = tgsw_params.decomp_level;
+        let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+        let mut generator = RandomGenerator::<DefaultRandomGenerator>::new(Seed(0));
+        let mut random_vec =
+            |len: usize| -> Vec<u32> { (0..len).map(|_| generator.random_uniform()).collect() };
+        let tgsw_ct = TgswCiphertext::from_container(
+            random_vec(l * lwe_size.0 * lwe_size.0),
+            lwe_size,
+            CiphertextModulus::new_native(),
+        );
+        let ct_lwe =
+            LweCiphertext::from_container(random_vec(lwe_size.0), CiphertextModulus::new_native());
+
+        let blocked = tgsw_ct.ext_product_lwe(&ct_lwe, B, l, DecompositionKind::Unsigned);
+        assert_eq!(
+            blocked.as_ref(),
+            column_product(&tgsw_ct, ct_lwe.as_ref(), B, l).as_slice()
+        );
+
+        let kernel_time = average_time(trials, || {
+            black_box(tgsw_ct.ext_product_lwe(
+                black_box(&ct_lwe),
+                B,
+                l,
+                DecompositionKind::Unsigned,
+            ));
+        });
+        let column_time = average_time(trials, || {
+            black_box(column_product(&tgsw_ct, black_box(ct_lwe.as_ref()), B, l));
+        });
+        println!(
+            "{name} (n+1 = {}, B = {B}, l = {l}): ext_product {:?}, column-wise product {:?}",
+            lwe_size.0, kernel_time, column_time
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//Versions of the serialized OHLG types, see tfhe::safe_serialization
+//To change a type, add a new variant here (e.g. V1) with an Upgrade from the previous one,
+//and add the fixtures of the new version to tests/backward_compatibility
+use tfhe::core_crypto::prelude::{CiphertextModulus, Container, LweSize, UnsignedInteger};
+use tfhe_versionable::{Upgrade, Version, VersionsDispatch};
+
+use crate::gate_params::GateParams;
+use crate::netlist::{Gate, GateOp, Netlist, Port};
+use crate::tgsw::{
+    DecompositionKind, SeededTgswCiphertext, SerializableTgswCiphertext, TgswError, TgswParams,
+};
+
+#[derive(VersionsDispatch)]
+pub enum DecompositionKindVersions {
+    V0(DecompositionKind),
+}
+
+#[derive(VersionsDispatch)]
+pub enum TgswParamsVersions {
+    V0(TgswParams),
+}
+
+//V0 stored one Vec per TGSW row, which must all have the same non-zero length n+1
+#[derive(Version)]
+pub struct TgswCiphertextV0<C: Container>
+where
+    C::Element: UnsignedInteger,
+{
+    ct_matrix: Vec<C>,
+    ciphertext_modulus: CiphertextModulus<C::Element>,
+}
+
+impl<Scalar: UnsignedInteger> Upgrade<SerializableTgswCiphertext<Vec<Scalar>>>
+    for TgswCiphertextV0<Vec<Scalar>>
+{
+    type Error = TgswError;
+
+    fn upgrade(self) -> Result<SerializableTgswCiphertext<Vec<Scalar>>, Self::Error> {
+        let lwe_size = LweSize(self.ct_matrix.first().map_or(0, Vec::len));
+        if let Some((row, ct_row)) = self
+            .ct_matrix
+            .iter()
+            .enumerate()
+            .find(|(_, ct_row)| ct_row.len() != lwe_size.0)
+        {
+            return Err(TgswError::RaggedRows {
+                row,
+                row_len: ct_row.len(),
+                lwe_size,
+            });
+        }
+        //The length is checked when converting to a TgswCiphertext (e.g. no rows, or rows of length 0)
+        Ok(SerializableTgswCiphertext {
+            data: self.ct_matrix.concat(),
+            lwe_size,
+            ciphertext_modulus: self.ciphertext_modulus,
+        })
+    }
+}
+
+#[derive(VersionsDispatch)]
+pub enum TgswCiphertextVersions<C: Container>
+where
+    C::Element: UnsignedInteger,
+{
+    V0(TgswCiphertextV0<C>),
+    V1(SerializableTgswCiphertext<C>),
+}
+
+#[derive(VersionsDispatch)]
+pub enum SeededTgswCiphertextVersions<Scalar: UnsignedInteger> {
+    V0(SeededTgswCiphertext<Scalar>),
+}
+
+#[derive(VersionsDispatch)]
+pub enum GateParamsVersions {
+    V0(GateParams),
+}
+
+#[derive(VersionsDispatch)]
+pub enum GateOpVersions {
+    V0(GateOp),
+}
+
+#[derive(VersionsDispatch)]
+pub enum GateVersions {
+    V0(Gate),
+}
+
+#[derive(VersionsDispatch)]
+pub enum PortVersions {
+    V0(Port),
+}
+
+#[derive(VersionsDispatch)]
+pub enum NetlistVersions {
+    V0(Netlist),
+}
@@ -1,37 +1,49 @@
 #![allow(non_snake_case)]
+use ohlg::gate_compiler::GateKind;
+use ohlg::gate_params::new_encrypt_gate_params;
+use ohlg::netlist::{GateOp, Netlist};
 use ohlg::tgsw::*;
 use std::io::Write;
 use std::time::Instant;
 use tfhe::boolean::prelude::*;
+use tfhe::safe_serialization::safe_serialize;
+
+//Size limit of the serialized gate parameters (159 gates take ~1.6 MB with the TFHE-rs parameters)
+const SERIALIZED_SIZE_LIMIT: u64 = 1 << 30;
 
 fn main() {
     /*
     For TFHE-io parameters, the Decomposition base B = 16 and level l=3 are used. Obfuscated gate error probability is 10^-18
     For TFHE-rs default Boolean parameters, the Decomposition base B = 64 and level l=2 are used. Obfuscated gate error probability is 10^-25
+    For TFHE-rs KS->PBS Boolean parameters (encrypted under the big key), B = 64 and l = 2 are used as well
      */
     let param_choice = "TFHE_RS";
-    let tgsw_params: TgswParams;
-
-    match param_choice {
+    let tgsw_params: TgswParams = match param_choice {
         "TFHE_IO" => {
             //tgsw_decomposition_base = 16;
             //tgsw_decomposition_level = 3;
             //selected_parameters = TFHE_LIB_PARAMETERS;
-            tgsw_params = TgswParams::new(TFHE_LIB_PARAMETERS, 16, 3);
+            TgswParams::new(TFHE_LIB_PARAMETERS, 16, 3)
         }
         "TFHE_RS" => {
             //tgsw_decomposition_base = 64;
             //tgsw_decomposition_level = 2;
             //selected_parameters = DEFAULT_PARAMETERS;
-            tgsw_params = TgswParams::new(DEFAULT_PARAMETERS, 64, 2);
+            TgswParams::new(DEFAULT_PARAMETERS, 64, 2)
+        }
+        "TFHE_RS_KS_PBS" => {
+            //The ciphertexts are encrypted under the big key (glwe_dimension*polynomial_size = 1536)
+            TgswParams::new(DEFAULT_PARAMETERS_KS_PBS, 64, 2)
         }
         _ => {
             //tgsw_decomposition_base = 64;
             //tgsw_decomposition_level = 2;
             //selected_parameters = DEFAULT_PARAMETERS;
-            tgsw_params = TgswParams::new(DEFAULT_PARAMETERS, 64, 2);
+            TgswParams::new(DEFAULT_PARAMETERS, 64, 2)
         }
-    }
+    };
+    //Check the decomposition against the modulus and the parameter set before encrypting anything
+    tgsw_params.validate().unwrap();
 
     let chars_num = 10;
     let bits_per_char = 8;
@@ -61,48 +73,50 @@ fn main() {
     let client_key = ClientKey::new(&tgsw_params.tfhe_params);
     let server_key = ServerKey::new(&client_key);
 
-    //-----------------Gate Parameters Encryption-----------------
-    //Create an empty vector to store num_total_gates number of TGSW ciphertexts
-    let mut C_gate_params: Vec<TgswCiphertext> = Vec::with_capacity(num_total_gates as usize);
-
-    //Create an empty vector to store num_total_gates number of Ciphertexts
-    let mut cd_gate_params: Vec<Ciphertext> = Vec::with_capacity(num_total_gates as usize);
+    //-----------------Data flow description and Gate Parameters Encryption-----------------
+    //The netlist of the matching circuit: the search character (bits_per_char wires) is compared to each character
+    //of the corpus (chars_num * bits_per_char wires), the server only sees the wiring, not the gate functions
+    let mut netlist = Netlist::new();
+    let search_wires = netlist.add_input_port("search", bits_per_char as usize);
+    let corpus_wires = netlist.add_input_port("corpus", (chars_num * bits_per_char) as usize);
+
+    //Create an empty vector to store the (A, d) plaintext pairs of the num_total_gates gates, gate i of the netlist uses the pair i
+    let mut gate_plaintexts: Vec<(u32, u32)> = Vec::with_capacity(num_total_gates as usize);
+    let mut add_gate = |netlist: &mut Netlist, kind: GateKind, inputs: Vec<usize>| {
+        gate_plaintexts.push(kind.plaintexts());
+        netlist.add_gate(GateOp::TwoOp, inputs, gate_plaintexts.len() - 1)
+    };
+
+    let mut final_result = None;
+    for corpus_char in corpus_wires.chunks(bits_per_char as usize) {
+        let mut char_result = None;
+        for (&search_bit, &corpus_bit) in search_wires.iter().zip(corpus_char) {
+            //XNOR: A = 2, d = 6/8 = -2/8
+            let bit = add_gate(&mut netlist, GateKind::Xnor, vec![search_bit, corpus_bit]);
+
+            //For the first bit, just keep the XNOR result, then aggregate the bits with AND: A = 1, d = 7/8 = -1/8
+            char_result = Some(match char_result {
+                None => bit,
+                Some(char_result) => add_gate(&mut netlist, GateKind::And, vec![char_result, bit]),
+            });
+        }
+        //For the first character, just keep its result, then aggregate the characters with OR: A = 1, d = 1/8
+        let char_result = char_result.unwrap();
+        final_result = Some(match final_result {
+            None => char_result,
+            Some(final_result) => {
+                add_gate(&mut netlist, GateKind::Or, vec![final_result, char_result])
+            }
+        });
+    }
+    netlist.add_output_port("match", vec![final_result.unwrap()]);
+    assert_eq!(netlist.num_params().unwrap(), num_total_gates as usize);
 
     //Start measuring time
     let start = Instant::now();
-    for c in 0..chars_num {
-        for b in 0..bits_per_char {
-            let A: u32 = 2; //Multiplicative parameter for XNOR = 2
-            let d: u32 = (1 << (32 - 3)) * 6; //Additive parameter for XNOR = 6/8 = -2/8
-
-            let C_gate = TgswCiphertext::new_encrypt(A, &tgsw_params, &client_key);
-            let cd_gate = client_key.encrypt_abs(d);
-
-            C_gate_params.push(C_gate);
-            cd_gate_params.push(cd_gate);
-
-            if b != 0 {
-                let A: u32 = 1; //Multiplicative parameter for AND = 1
-                let d: u32 = (1 << (32 - 3)) * 7; //Additive parameter for AND = 1
-
-                let C_gate = TgswCiphertext::new_encrypt(A, &tgsw_params, &client_key);
-                let cd_gate = client_key.encrypt_abs(d);
-
-                C_gate_params.push(C_gate);
-                cd_gate_params.push(cd_gate);
-            }
-        }
-        if c != 0 {
-            let A: u32 = 1; //Multiplicative parameter for OR = 1
-            let d: u32 = (1 << (32 - 3)) * 1; //Additive parameter for OR = 1
-
-            let C_gate = TgswCiphertext::new_encrypt(A, &tgsw_params, &client_key);
-            let cd_gate = client_key.encrypt_abs(d);
-
-            C_gate_params.push(C_gate);
-            cd_gate_params.push(cd_gate);
-        }
-    }
+    //Encrypt all the gate parameters in parallel
+    //The TGSW ciphertexts are in the seeded (compressed) form, so only the TGSW bodies and a seed are sent to the server
+    let gate_params = new_encrypt_gate_params(&gate_plaintexts, &tgsw_params, &client_key);
     //End measuring gates parameters encryption time
     let duration = start.elapsed();
     print!("Gates Parameters Encryption Time: {:?}\n\r", duration);
@@ -137,7 +151,10 @@ fn main() {
     //The search corpus is a string of characters, bitwise encrypted using the client key. It is expected to exist on the server side, but it is done here for the experiment.
     let plain_search_corpus = "ABCDEFGHIJ";
     //Print the search corpus
-    println!("Server will be searching in the (encrypted) Search corpus: {}", plain_search_corpus);
+    println!(
+        "Server will be searching in the (encrypted) Search corpus: {}",
+        plain_search_corpus
+    );
     let search_corpus_ciphertexts = encrypt_string(&client_key, plain_search_corpus);
     //-------------------------------------------------------------------------
 
@@ -145,43 +162,44 @@ fn main() {
     let mut file = std::fs::File::create("server_key_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
     bincode::serialize_into(&mut serialized_data, &server_key).unwrap();
-    file.write(&serialized_data).unwrap();
+    file.write_all(&serialized_data).unwrap();
 
     //--------Export client key to a file
     let mut file = std::fs::File::create("client_key_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
     bincode::serialize_into(&mut serialized_data, &client_key).unwrap();
-    file.write(&serialized_data).unwrap();
+    file.write_all(&serialized_data).unwrap();
 
     //--------Export search ciphertexts to a file
     let mut file = std::fs::File::create("search_ciphertexts_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
     bincode::serialize_into(&mut serialized_data, &search_ciphertexts).unwrap();
-    file.write(&serialized_data).unwrap();
+    file.write_all(&serialized_data).unwrap();
 
     //--------Export search corpus ciphertexts to a file
     let mut file = std::fs::File::create("search_corpus_ciphertexts_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
     bincode::serialize_into(&mut serialized_data, &search_corpus_ciphertexts).unwrap();
-    file.write(&serialized_data).unwrap();
+    file.write_all(&serialized_data).unwrap();
 
-    //--------Export C_gate_params to a file
-    let mut file = std::fs::File::create("C_gate_params_ODM.txt").unwrap();
+    //--------Export the gate parameters (C_gate_params and cd_gate_params) to a file
+    //safe_serialize adds a versioned header, so the file keeps loading with later versions of OHLG
+    let mut file = std::fs::File::create("gate_params_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
-    bincode::serialize_into(&mut serialized_data, &C_gate_params).unwrap();
-    file.write(&serialized_data).unwrap();
+    safe_serialize(&gate_params, &mut serialized_data, SERIALIZED_SIZE_LIMIT).unwrap();
+    file.write_all(&serialized_data).unwrap();
 
-    //--------Export cd_gate_params to a file
-    let mut file = std::fs::File::create("cd_gate_params_ODM.txt").unwrap();
+    //--------Export the netlist (the data flow description) to a file
+    let mut file = std::fs::File::create("netlist_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
-    bincode::serialize_into(&mut serialized_data, &cd_gate_params).unwrap();
-    file.write(&serialized_data).unwrap();
+    safe_serialize(&netlist, &mut serialized_data, SERIALIZED_SIZE_LIMIT).unwrap();
+    file.write_all(&serialized_data).unwrap();
 
     //--------Export tgsw_params to a file
     let mut file = std::fs::File::create("tgsw_params_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
-    bincode::serialize_into(&mut serialized_data, &tgsw_params).unwrap();
-    file.write(&serialized_data).unwrap();
+    safe_serialize(&tgsw_params, &mut serialized_data, SERIALIZED_SIZE_LIMIT).unwrap();
+    file.write_all(&serialized_data).unwrap();
 }
 
 //Encrypt a u8 value into 8 ciphertexts
@@ -1,9 +1,15 @@
 #![allow(non_snake_case)]
-use ohlg::oblivious_gates::ob_gate_2op;
+use ohlg::gate_params::{GateParams, GateParamsConformanceParams};
+use ohlg::netlist::Netlist;
+use ohlg::oblivious_evaluator::ObliviousEvaluator;
 use ohlg::tgsw::*;
 use std::io::Write;
 use std::time::Instant;
 use tfhe::boolean::prelude::*;
+use tfhe::safe_serialization::{safe_deserialize, safe_deserialize_conformant};
+
+//Size limit of the deserialized gate parameters (159 gates take ~1.6 MB with the TFHE-rs parameters)
+const DESERIALIZED_SIZE_LIMIT: u64 = 1 << 30;
 
 fn main() {
     //Read the search ciphertexts (the encrypted search character) from file
@@ -22,22 +28,37 @@ fn main() {
     let server_key: ServerKey = bincode::deserialize_from(&mut file).unwrap();
     print!("Successfully read the server key from the file\n\r");
 
-    //Read the C_gate_params (vector of encrypted gate multiplicative parameters) from the file
-    //This step takes some time.
-    let mut file = std::fs::File::open("C_gate_params_ODM.txt").unwrap(); //let mut C_gate_params: Vec<TgswCiphertext> = Vec::with_capacity(159); //This didn't make a difference in time.
-    let C_gate_params: Vec<TgswCiphertext> = bincode::deserialize_from(&mut file).unwrap();
-    print!("Successfully read the C_gate_params from the file\n\r");
-
-    //Read the cd_gate_params (vector of encrypted gate additive parameters) from the file
-    let mut file = std::fs::File::open("cd_gate_params_ODM.txt").unwrap();
-    let cd_gate_params: Vec<Ciphertext> = bincode::deserialize_from(&mut file).unwrap();
-    print!("Successfully read the cd_gate_params from the file\n\r");
-
     //Read the tgsw_params (Decomposition base, decomposition level, and the used TFHE parameter set) from the file
-    let mut file = std::fs::File::open("tgsw_params_ODM.txt").unwrap();
-    let tgsw_params: TgswParams = bincode::deserialize_from(&mut file).unwrap();
+    let file = std::fs::File::open("tgsw_params_ODM.txt").unwrap();
+    let tgsw_params: TgswParams = safe_deserialize(file, DESERIALIZED_SIZE_LIMIT).unwrap();
+    tgsw_params.validate().unwrap();
     print!("Successfully read the tgsw_params from the file\n\r");
 
+    //Read the netlist (the data flow description of the circuit) from the file, and check its structure
+    let file = std::fs::File::open("netlist_ODM.txt").unwrap();
+    let netlist: Netlist = safe_deserialize(file, DESERIALIZED_SIZE_LIMIT).unwrap();
+    netlist.validate().unwrap();
+    print!("Successfully read the netlist from the file\n\r");
+
+    //Read the gate parameters from the file, and check that there is one (C, cd) pair per gate of the netlist with the expected sizes
+    //C_gate_params: vector of encrypted gate multiplicative parameters
+    //The client sends them in the seeded (compressed) form, the masks are regenerated here.
+    //cd_gate_params: vector of encrypted gate additive parameters
+    let file = std::fs::File::open("gate_params_ODM.txt").unwrap();
+    let gate_params: GateParams = safe_deserialize_conformant(
+        file,
+        DESERIALIZED_SIZE_LIMIT,
+        &GateParamsConformanceParams::new(netlist.num_params().unwrap(), &tgsw_params),
+    )
+    .unwrap();
+    let C_gate_params: Vec<TgswCiphertext> = gate_params
+        .C_gate_params
+        .into_iter()
+        .map(SeededTgswCiphertext::decompress)
+        .collect();
+    let cd_gate_params = gate_params.cd_gate_params;
+    print!("Successfully read the C_gate_params and cd_gate_params from the file\n\r");
+
     //Successfull reading of the files
     println!("Successfully read all files\n\r");
 
@@ -50,6 +71,7 @@ fn main() {
     //Print the parameters for verification
     print!("search corpus ciphertexts size: {}\n\r", N);
     print!("number of bits in search ciphertexts: {}\n\r", B);
+    print!("number of netlist gates: {}\n\r", netlist.gates.len());
     print!(
         "number of tgsw gate paramaters: {}\n\r",
         C_gate_params.len()
@@ -60,71 +82,29 @@ fn main() {
     );
 
     //---------Oblivious Direct Matching operation------------
-    let mut params_counter: usize = 0; //Counter for the gate parameters (159 gates = 159 multiplicative parameters + 159 additive parameters in the given example)
-    let mut final_result: Ciphertext = Ciphertext::Trivial(false); //The final result of the ODM operation, to be sent to the client for decryption.
+    //The inputs of the netlist ports: the search character, and the search corpus characters one after the other
+    let inputs = vec![
+        search_ciphertexts,
+        search_corpus_ciphertexts.into_iter().flatten().collect(),
+    ];
+    let mut evaluator = ObliviousEvaluator::new(server_key, tgsw_params);
 
     //Start measuring the searching time
     let start = Instant::now();
-    for c in 0..N {
-        let mut char_result: Ciphertext = Ciphertext::Trivial(false);
-        for b in 0..B {
-            let bit_temp = ob_gate_2op(
-                &search_ciphertexts[b],
-                &search_corpus_ciphertexts[c][b],
-                &C_gate_params[params_counter],
-                &cd_gate_params[params_counter],
-                &tgsw_params,
-                &server_key,
-            );
-            params_counter += 1;
-            if b == 0
-            //For the first bit, just assign the result to the char_result
-            {
-                char_result = bit_temp;
-            } else
-            //For the rest of the bits, do the Obfuscated operation (AND in this example of ODM) to aggregate the bits
-            {
-                char_result = ob_gate_2op(
-                    &char_result,
-                    &bit_temp,
-                    &C_gate_params[params_counter],
-                    &cd_gate_params[params_counter],
-                    &tgsw_params,
-                    &server_key,
-                );
-                params_counter += 1;
-            }
-        }
-
-        if c == 0
-        //For the first character result, just assign the result to the final_result
-        {
-            final_result = char_result;
-        } else
-        //For the rest of the character results, aggregate using the Obfuscated operation (OR in the example of ODM)
-        {
-            final_result = ob_gate_2op(
-                &final_result,
-                &char_result,
-                &C_gate_params[params_counter],
-                &cd_gate_params[params_counter],
-                &tgsw_params,
-                &server_key,
-            );
-            params_counter += 1;
-        }
-    }
+    let outputs = netlist
+        .execute(&mut evaluator, &inputs, &C_gate_params, &cd_gate_params)
+        .unwrap();
     //measure time end
     let duration = start.elapsed();
     print!("ODM operation time: {:?}\n\r", duration);
 
-    //print the parameters counter, check if it is equal to the number of gate parameters
-    print!("params_counter: {}\n\r", params_counter);
+    //The final result of the ODM operation (the "match" output port), to be sent to the client for decryption.
+    let final_result: &Ciphertext = &outputs[0][0];
 
     //Write the final result to the file
     let mut file = std::fs::File::create("final_result_ODM.txt").unwrap();
     let mut serialized_data = Vec::new();
-    bincode::serialize_into(&mut serialized_data, &final_result).unwrap();
-    file.write(&serialized_data).unwrap();
+    bincode::serialize_into(&mut serialized_data, final_result).unwrap();
+    file.write_all(&serialized_data).unwrap();
     print!("Successfully wrote the final result to the file\n\r");
 }
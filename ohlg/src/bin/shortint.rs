@@ -85,9 +85,9 @@ fn benchmark_config(config: Config) -> BenchmarkRecord {
     let start = Instant::now();
     for _ in 0..BENCH_ITERATIONS {
         // Clone the inputs so each AND works on a fresh ciphertext pair.
-        let mut tmp_a = base_ct_a.clone();
-        let mut tmp_b = base_ct_b.clone();
-        last_ciphertext = Some(server_key.unchecked_bitand(&mut tmp_a, &mut tmp_b));
+        let tmp_a = base_ct_a.clone();
+        let tmp_b = base_ct_b.clone();
+        last_ciphertext = Some(server_key.unchecked_bitand(&tmp_a, &tmp_b));
     }
     let avg_duration = start.elapsed()  / BENCH_ITERATIONS;
 
@@ -0,0 +1,339 @@
+/*Client-side import of the Bristol Fashion circuits of the MPC tools (adders, comparators, AES, SHA-256, ...)
+The format is:
+    <number of gates> <number of wires>
+    <number of input values> <wires of input value 1> ... <wires of input value n>
+    <number of output values> <wires of output value 1> ... <wires of output value m>
+followed by one gate per line: <number of inputs> <number of outputs> <input wires> <output wires> <operation>
+The input values are the first wires (in order), the output values the last ones, and each wire is written once.
+The gates are mapped to obfuscated gates, with their plaintext parameters generated on the client side:
+- XOR, AND: 2-input gates (GateKind::Xor, GateKind::And)
+- INV: a 1-input gate (UnaryGateKind::Not)
+- EQ (a wire set to the constant 0 or 1): a 1-input gate (UnaryGateKind::Const0 or Const1), reading the first input wire,
+  so the server cannot tell the constants from the inverters and buffers
+- EQW (a copy of a wire): a 1-input gate (UnaryGateKind::Buf)
+- MAND (k ANDs in one line, 2k inputs then k outputs): k 2-input gates
+*/
+use crate::gate_compiler::{GateKind, UnaryGateKind};
+use crate::netlist::{Circuit, WireId};
+
+/*The largest number of wires of an imported circuit, checked on the header before anything is allocated
+(AES-128 has ~37k wires and SHA-256 ~136k, a netlist of this size takes minutes to hours to evaluate already)
+*/
+pub const MAX_WIRES: usize = 1 << 24;
+
+//The reasons why a Bristol Fashion circuit cannot be imported, the line numbers start at 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BristolError {
+    //A line does not follow the format
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    //An operation other than XOR, AND, INV, EQ, EQW and MAND
+    UnsupportedGate {
+        line: usize,
+        op: String,
+    },
+    //A wire index is not below the number of wires of the header
+    WireOutOfRange {
+        line: usize,
+        wire: usize,
+        num_wires: usize,
+    },
+    //A gate or an output value reads a wire that is not an input and not written by a previous gate
+    UndrivenWire {
+        line: usize,
+        wire: usize,
+    },
+    //A gate writes a wire that is an input or was already written
+    MultipleDrivers {
+        line: usize,
+        wire: usize,
+    },
+    //The number of gate lines does not match the header
+    GateCount {
+        expected: usize,
+        found: usize,
+    },
+    //The header announces more than MAX_WIRES wires
+    TooManyWires {
+        line: usize,
+        num_wires: usize,
+    },
+    //A constant (EQ gate) in a circuit without any input wire to attach it to
+    ConstantWithoutInputs {
+        line: usize,
+    },
+}
+
+impl std::fmt::Display for BristolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+            Self::UnsupportedGate { line, op } => {
+                write!(f, "Line {}: unsupported gate {}", line, op)
+            }
+            Self::WireOutOfRange {
+                line,
+                wire,
+                num_wires,
+            } => write!(
+                f,
+                "Line {}: the wire {} is out of range (the circuit has {} wires)",
+                line, wire, num_wires
+            ),
+            Self::UndrivenWire { line, wire } => write!(
+                f,
+                "Line {}: the wire {} is read before being written",
+                line, wire
+            ),
+            Self::MultipleDrivers { line, wire } => {
+                write!(f, "Line {}: the wire {} is written twice", line, wire)
+            }
+            Self::GateCount { expected, found } => write!(
+                f,
+                "The header announces {} gates, the circuit has {}",
+                expected, found
+            ),
+            Self::TooManyWires { line, num_wires } => write!(
+                f,
+                "Line {}: the circuit has {} wires, more than the limit of {}",
+                line, num_wires, MAX_WIRES
+            ),
+            Self::ConstantWithoutInputs { line } => write!(
+                f,
+                "Line {}: a constant needs at least one input wire in the circuit",
+                line
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BristolError {}
+
+/*Import a Bristol Fashion circuit: input value i becomes the input port "input<i>" and output value i the output port
+"output<i>", with their wires in the order of the file (e.g. bit 0 first when the circuit is written so)
+*/
+pub fn parse_bristol_fashion(text: &str) -> Result<Circuit, BristolError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.split_whitespace().collect::<Vec<&str>>()))
+        .filter(|(_, tokens)| !tokens.is_empty());
+
+    let mut header = |name: &str| {
+        lines.next().ok_or(BristolError::Syntax {
+            line: text.lines().count(),
+            message: format!("missing the {} line", name),
+        })
+    };
+    let (line, tokens) = header("gate and wire count")?;
+    let [num_gates, num_wires] = parse_numbers(line, &tokens)?[..] else {
+        return Err(syntax(line, "expected the number of gates and wires"));
+    };
+    if num_wires > MAX_WIRES {
+        return Err(BristolError::TooManyWires { line, num_wires });
+    }
+    let (inputs_line, input_sizes) = parse_values(header("input values")?)?;
+    let (outputs_line, output_sizes) = parse_values(header("output values")?)?;
+
+    //The sums are checked, so that the sizes of the values cannot wrap around to fit in num_wires
+    let too_many_wires = |line: usize| {
+        syntax(
+            line,
+            "the input and output values have more wires than the circuit",
+        )
+    };
+    let num_input_wires = checked_sum(&input_sizes).ok_or_else(|| too_many_wires(inputs_line))?;
+    let num_output_wires =
+        checked_sum(&output_sizes).ok_or_else(|| too_many_wires(outputs_line))?;
+    match num_input_wires.checked_add(num_output_wires) {
+        Some(num_value_wires) if num_value_wires <= num_wires => {}
+        _ => return Err(too_many_wires(outputs_line)),
+    }
+
+    //The netlist wire of each Bristol wire written so far
+    let mut circuit = Circuit::new();
+    let mut wires: Vec<Option<WireId>> = vec![None; num_wires];
+    let mut next_wire = 0;
+    for (index, &size) in input_sizes.iter().enumerate() {
+        let port = circuit
+            .netlist
+            .add_input_port(&format!("input{}", index), size);
+        for wire in port {
+            wires[next_wire] = Some(wire);
+            next_wire += 1;
+        }
+    }
+
+    let read = |wires: &[Option<WireId>], line: usize, wire: usize| match wires.get(wire) {
+        None => Err(BristolError::WireOutOfRange {
+            line,
+            wire,
+            num_wires,
+        }),
+        Some(None) => Err(BristolError::UndrivenWire { line, wire }),
+        Some(Some(netlist_wire)) => Ok(*netlist_wire),
+    };
+    let write =
+        |wires: &mut [Option<WireId>], line: usize, wire: usize, netlist_wire: WireId| match wires
+            .get_mut(wire)
+        {
+            None => Err(BristolError::WireOutOfRange {
+                line,
+                wire,
+                num_wires,
+            }),
+            Some(Some(_)) => Err(BristolError::MultipleDrivers { line, wire }),
+            Some(driven) => {
+                *driven = Some(netlist_wire);
+                Ok(())
+            }
+        };
+
+    let mut found_gates = 0;
+    for (line, tokens) in lines {
+        found_gates += 1;
+        let (op, numbers) = tokens.split_last().unwrap();
+        let numbers = parse_numbers(line, numbers)?;
+        let [num_in, num_out, ref gate_wires @ ..] = numbers[..] else {
+            return Err(syntax(line, "expected the number of inputs and outputs"));
+        };
+        if num_in.checked_add(num_out) != Some(gate_wires.len()) {
+            return Err(syntax(
+                line,
+                &format!(
+                    "expected {} input and {} output wires, found {} wires",
+                    num_in,
+                    num_out,
+                    gate_wires.len()
+                ),
+            ));
+        }
+        let (ins, outs) = gate_wires.split_at(num_in);
+        let arity = |expected_in: usize, expected_out: usize| {
+            if (num_in, num_out) == (expected_in, expected_out) {
+                Ok(())
+            } else {
+                Err(syntax(
+                    line,
+                    &format!(
+                        "{} takes {} inputs and {} outputs, found {} and {}",
+                        op, expected_in, expected_out, num_in, num_out
+                    ),
+                ))
+            }
+        };
+
+        match *op {
+            "XOR" | "AND" => {
+                arity(2, 1)?;
+                let kind = if *op == "XOR" {
+                    GateKind::Xor
+                } else {
+                    GateKind::And
+                };
+                let inputs = [read(&wires, line, ins[0])?, read(&wires, line, ins[1])?];
+                let output = circuit.add_gate_2op(kind, inputs);
+                write(&mut wires, line, outs[0], output)?;
+            }
+            "MAND" => {
+                if num_in != 2 * num_out || num_out == 0 {
+                    return Err(syntax(
+                        line,
+                        "MAND takes 2k inputs and k outputs, with k > 0",
+                    ));
+                }
+                let (left, right) = ins.split_at(num_out);
+                for ((&a, &b), &out) in left.iter().zip(right).zip(outs) {
+                    let inputs = [read(&wires, line, a)?, read(&wires, line, b)?];
+                    let output = circuit.add_gate_2op(GateKind::And, inputs);
+                    write(&mut wires, line, out, output)?;
+                }
+            }
+            "INV" | "EQW" => {
+                arity(1, 1)?;
+                let kind = if *op == "INV" {
+                    UnaryGateKind::Not
+                } else {
+                    UnaryGateKind::Buf
+                };
+                let output = circuit.add_gate_1op(kind, read(&wires, line, ins[0])?);
+                write(&mut wires, line, outs[0], output)?;
+            }
+            "EQ" => {
+                arity(1, 1)?;
+                let kind = match ins[0] {
+                    0 => UnaryGateKind::Const0,
+                    1 => UnaryGateKind::Const1,
+                    _ => return Err(syntax(line, "EQ takes the constant 0 or 1")),
+                };
+                if num_input_wires == 0 {
+                    return Err(BristolError::ConstantWithoutInputs { line });
+                }
+                let output = circuit.add_gate_1op(kind, read(&wires, line, 0)?);
+                write(&mut wires, line, outs[0], output)?;
+            }
+            _ => {
+                return Err(BristolError::UnsupportedGate {
+                    line,
+                    op: op.to_string(),
+                })
+            }
+        }
+    }
+    if found_gates != num_gates {
+        return Err(BristolError::GateCount {
+            expected: num_gates,
+            found: found_gates,
+        });
+    }
+
+    let mut next_wire = num_wires - num_output_wires;
+    for (index, &size) in output_sizes.iter().enumerate() {
+        let port = (next_wire..next_wire + size)
+            .map(|wire| read(&wires, outputs_line, wire))
+            .collect::<Result<Vec<WireId>, BristolError>>()?;
+        circuit
+            .netlist
+            .add_output_port(&format!("output{}", index), port);
+        next_wire += size;
+    }
+    Ok(circuit)
+}
+
+fn syntax(line: usize, message: &str) -> BristolError {
+    BristolError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn checked_sum(sizes: &[usize]) -> Option<usize> {
+    sizes
+        .iter()
+        .try_fold(0usize, |sum, &size| sum.checked_add(size))
+}
+
+fn parse_numbers(line: usize, tokens: &[&str]) -> Result<Vec<usize>, BristolError> {
+    tokens
+        .iter()
+        .map(|token| {
+            token
+                .parse()
+                .map_err(|_| syntax(line, &format!("expected a number, found {}", token)))
+        })
+        .collect()
+}
+
+//An input or output value line: the number of values, then the number of wires of each value
+fn parse_values((line, tokens): (usize, Vec<&str>)) -> Result<(usize, Vec<usize>), BristolError> {
+    let numbers = parse_numbers(line, &tokens)?;
+    match numbers.split_first() {
+        Some((&count, sizes)) if count == sizes.len() => Ok((line, sizes.to_vec())),
+        _ => Err(syntax(
+            line,
+            "expected the number of values, then the number of wires of each value",
+        )),
+    }
+}
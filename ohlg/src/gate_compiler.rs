@@ -0,0 +1,241 @@
+//Client-side compiler from 1- and 2-input boolean functions to the plaintext parameters (A, d) of ob_gate_1op_tgsw and ob_gate_2op
+use tfhe::boolean::prelude::{Ciphertext, ClientKey};
+
+use crate::gate_params::{new_encrypt_gate_params, GateParams};
+use crate::noise::EIGHTH;
+use crate::tgsw::{TgswCiphertext, TgswParams};
+
+/*The truth table of a 2-input boolean function: truth_table[2*m1 + m2] = f(m1, m2)
+i.e. the outputs for (false, false), (false, true), (true, false) and (true, true)
+*/
+pub type TruthTable = [bool; 4];
+
+/*The 2-input boolean functions an obfuscated gate A(c1+c2)+d can compute
+The gate only sees the sum of the inputs, (number of true inputs - 1)/4, so it computes exactly the 8 symmetric functions
+(the output only depends on the number of true inputs)
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GateKind {
+    False,
+    True,
+    And,
+    Nand,
+    Or,
+    Nor,
+    Xor,
+    Xnor,
+}
+
+/*The 1-input boolean functions, all computed by an obfuscated gate A*c1+d (see oblivious_gates::ob_gate_1op_tgsw)
+The constants hide the tie-offs of a circuit among the buffers and inverters
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryGateKind {
+    Buf,
+    Not,
+    Const0,
+    Const1,
+}
+
+//The reasons why a truth table cannot be compiled to an obfuscated gate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GateCompileError {
+    //f(false, true) != f(true, false): the function depends on which input is true, not only on how many
+    NotSymmetric { truth_table: TruthTable },
+}
+
+impl std::fmt::Display for GateCompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotSymmetric { truth_table } => write!(
+                f,
+                "The truth table {truth_table:?} is not symmetric in its inputs, \
+                 an obfuscated gate A(c1+c2)+d cannot compute it"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GateCompileError {}
+
+impl GateKind {
+    pub const ALL: [GateKind; 8] = [
+        Self::False,
+        Self::True,
+        Self::And,
+        Self::Nand,
+        Self::Or,
+        Self::Nor,
+        Self::Xor,
+        Self::Xnor,
+    ];
+
+    /*The plaintext (A, d) of the gate, with true and false encoded as +-1/8
+    c1+c2 is -1/4, 0 or 1/4 for 0, 1 or 2 true inputs, and the output is true iff A(c1+c2)+d is in (0, 1/2)
+    - A = 0: the constants, d = +-1/8
+    - A = 1: the levels -1/4, 0, 1/4 are shifted by d = -1/8 (AND), 1/8 (OR), or 1/2 more for the negations
+    - A = 2: the levels -1/2, 0, 1/2 coincide for 0 and 2 true inputs, d = 1/4 (XOR) or 3/4 (XNOR)
+    Every level is at least 1/8 away from 0 and 1/2
+    */
+    pub fn plaintexts(self) -> (u32, u32) {
+        match self {
+            Self::False => (0, EIGHTH.wrapping_neg()),
+            Self::True => (0, EIGHTH),
+            Self::And => (1, 7 * EIGHTH),
+            Self::Nand => (1, 3 * EIGHTH),
+            Self::Or => (1, EIGHTH),
+            Self::Nor => (1, 5 * EIGHTH),
+            Self::Xor => (2, 2 * EIGHTH),
+            Self::Xnor => (2, 6 * EIGHTH),
+        }
+    }
+
+    //The gate with the plaintext (A, d), None for the parameters of another function (e.g. of a threshold gate)
+    pub fn from_plaintexts(plaintexts: (u32, u32)) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.plaintexts() == plaintexts)
+    }
+
+    //The gate computing the negation of the function (the output negated by adding 1/2 to d)
+    pub fn negated(self) -> Self {
+        match self {
+            Self::False => Self::True,
+            Self::True => Self::False,
+            Self::And => Self::Nand,
+            Self::Nand => Self::And,
+            Self::Or => Self::Nor,
+            Self::Nor => Self::Or,
+            Self::Xor => Self::Xnor,
+            Self::Xnor => Self::Xor,
+        }
+    }
+
+    pub fn truth_table(self) -> TruthTable {
+        let f = |m1: bool, m2: bool| self.evaluate(m1, m2);
+        [
+            f(false, false),
+            f(false, true),
+            f(true, false),
+            f(true, true),
+        ]
+    }
+
+    //The plaintext function of the gate
+    pub fn evaluate(self, m1: bool, m2: bool) -> bool {
+        match self {
+            Self::False => false,
+            Self::True => true,
+            Self::And => m1 && m2,
+            Self::Nand => !(m1 && m2),
+            Self::Or => m1 || m2,
+            Self::Nor => !(m1 || m2),
+            Self::Xor => m1 != m2,
+            Self::Xnor => m1 == m2,
+        }
+    }
+
+    pub fn from_truth_table(truth_table: &TruthTable) -> Result<Self, GateCompileError> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.truth_table() == *truth_table)
+            .ok_or(GateCompileError::NotSymmetric {
+                truth_table: *truth_table,
+            })
+    }
+}
+
+impl UnaryGateKind {
+    pub const ALL: [UnaryGateKind; 4] = [Self::Buf, Self::Not, Self::Const0, Self::Const1];
+
+    /*The plaintext (A, d) of the gate, with true and false encoded as +-1/8
+    - A = 1: the input is kept, d = 0 (BUF) or 1/2 (NOT) as in ob_gate_1op
+    - A = 0: the input is cancelled, d = -1/8 (CONST0) or 1/8 (CONST1)
+    */
+    pub fn plaintexts(self) -> (u32, u32) {
+        match self {
+            Self::Buf => (1, 0),
+            Self::Not => (1, 4 * EIGHTH),
+            Self::Const0 => (0, EIGHTH.wrapping_neg()),
+            Self::Const1 => (0, EIGHTH),
+        }
+    }
+
+    //The gate with the plaintext (A, d), None for the parameters of another function
+    pub fn from_plaintexts(plaintexts: (u32, u32)) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.plaintexts() == plaintexts)
+    }
+
+    //The gate computing the 1-input function with the outputs [f(false), f(true)]
+    pub fn from_truth_table(truth_table: [bool; 2]) -> Self {
+        match truth_table {
+            [false, true] => Self::Buf,
+            [true, false] => Self::Not,
+            [false, false] => Self::Const0,
+            [true, true] => Self::Const1,
+        }
+    }
+
+    //The plaintext function of the gate
+    pub fn evaluate(self, m: bool) -> bool {
+        match self {
+            Self::Buf => m,
+            Self::Not => !m,
+            Self::Const0 => false,
+            Self::Const1 => true,
+        }
+    }
+}
+
+//The plaintext (A, d) of the obfuscated gate computing the truth table, if it is expressible (see GateKind)
+pub fn compile_truth_table(truth_table: &TruthTable) -> Result<(u32, u32), GateCompileError> {
+    GateKind::from_truth_table(truth_table).map(GateKind::plaintexts)
+}
+
+//Encrypt the parameters of a single gate, ready to be used by ob_gate_2op
+pub fn new_encrypt_gate(
+    kind: GateKind,
+    tgsw_params: &TgswParams,
+    client_key: &ClientKey,
+) -> (TgswCiphertext, Ciphertext) {
+    let (A, d) = kind.plaintexts();
+    (
+        TgswCiphertext::new_encrypt(A, tgsw_params, client_key),
+        client_key.encrypt_abs(d),
+    )
+}
+
+//Same as new_encrypt_gate, from a truth table
+pub fn new_encrypt_truth_table_gate(
+    truth_table: &TruthTable,
+    tgsw_params: &TgswParams,
+    client_key: &ClientKey,
+) -> Result<(TgswCiphertext, Ciphertext), GateCompileError> {
+    GateKind::from_truth_table(truth_table)
+        .map(|kind| new_encrypt_gate(kind, tgsw_params, client_key))
+}
+
+//Encrypt the parameters of a list of gates in parallel, in the order of kinds (see new_encrypt_gate_params)
+pub fn new_encrypt_gate_kinds(
+    kinds: &[GateKind],
+    tgsw_params: &TgswParams,
+    client_key: &ClientKey,
+) -> GateParams {
+    let gate_plaintexts: Vec<(u32, u32)> = kinds.iter().map(|kind| kind.plaintexts()).collect();
+    new_encrypt_gate_params(&gate_plaintexts, tgsw_params, client_key)
+}
+
+//Encrypt the parameters of a single 1-input gate, ready to be used by ob_gate_1op_tgsw
+pub fn new_encrypt_unary_gate(
+    kind: UnaryGateKind,
+    tgsw_params: &TgswParams,
+    client_key: &ClientKey,
+) -> (TgswCiphertext, Ciphertext) {
+    let (A, d) = kind.plaintexts();
+    (
+        TgswCiphertext::new_encrypt(A, tgsw_params, client_key),
+        client_key.encrypt_abs(d),
+    )
+}
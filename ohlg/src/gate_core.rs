@@ -0,0 +1,511 @@
+//The shared core of the obfuscated gates on boolean ciphertexts, used by oblivious_gates and oblivious_evaluator:
+//the typed error, the input encoding, the checks of the inputs and gate parameters against the parameter set,
+//the gates themselves on reusable scratch buffers, and the external product of a TGSW parameter with a boolean ciphertext
+use std::borrow::{Borrow, Cow};
+use tfhe::boolean::engine::bootstrapping::Bootstrapper;
+use tfhe::boolean::prelude::{Ciphertext, ServerKey};
+use tfhe::core_crypto::algorithms::lwe_linear_algebra::*;
+use tfhe::core_crypto::commons::traits::Container;
+use tfhe::core_crypto::entities::lwe_ciphertext::{LweCiphertext, LweCiphertextOwned};
+use tfhe::core_crypto::prelude::{
+    allocate_and_trivially_encrypt_new_lwe_ciphertext, CiphertextModulus, LweSize, Plaintext,
+};
+use tfhe::core_crypto::seeders::new_seeder;
+
+use crate::noise::EIGHTH;
+use crate::tgsw::{TgswCiphertext, TgswParams};
+
+//The reasons why an obfuscated gate cannot be evaluated on its ciphertexts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObGateError {
+    //An input or a gate parameter d is not encrypted under the key of the parameter set (see encryption_key_choice)
+    LweSizeMismatch {
+        expected: LweSize,
+        found: LweSize,
+    },
+    //The TGSW parameter A was not encrypted with the parameter set (other key or decomposition level)
+    TgswMismatch {
+        expected_lwe_size: LweSize,
+        found_lwe_size: LweSize,
+        expected_decomp_level: usize,
+        found_decomp_level: usize,
+    },
+    //A gate parameter d is a trivial ciphertext: it would reveal the gate function to the server
+    TrivialGateParameter,
+    //An n-input gate without any input
+    NoInputs,
+    //An n-input gate whose inputs do not fit on the u32 torus (see nop_input_magnitude)
+    FanInTooLarge {
+        fan_in: usize,
+    },
+    //A threshold gate whose threshold is above fan_in + 1 (see gate_params::threshold_gate_plaintexts)
+    ThresholdOutOfRange {
+        threshold: usize,
+        fan_in: usize,
+    },
+    //A multiplexer whose input indices are not distinct indices in 0..3 (see gate_params::mux_gate_plaintexts)
+    InvalidMuxInputs {
+        select: usize,
+        then: usize,
+        else_: usize,
+    },
+}
+
+impl std::fmt::Display for ObGateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LweSizeMismatch { expected, found } => write!(
+                f,
+                "Mismatch between the ciphertext size ({}) and the encryption key of the parameter set ({})",
+                found.0, expected.0
+            ),
+            Self::TgswMismatch {
+                expected_lwe_size,
+                found_lwe_size,
+                expected_decomp_level,
+                found_decomp_level,
+            } => write!(
+                f,
+                "The TGSW ciphertext (LWE size {}, level {}) does not match the parameter set (LWE size {}, level {})",
+                found_lwe_size.0, found_decomp_level, expected_lwe_size.0, expected_decomp_level
+            ),
+            Self::TrivialGateParameter => write!(
+                f,
+                "The gate parameter is a trivial ciphertext, it must be encrypted by the client"
+            ),
+            Self::NoInputs => write!(f, "Expected at least one input"),
+            Self::FanInTooLarge { fan_in } => {
+                write!(f, "The fan-in {fan_in} is too large for the u32 torus")
+            }
+            Self::ThresholdOutOfRange { threshold, fan_in } => write!(
+                f,
+                "The threshold {threshold} is out of range for {fan_in} inputs"
+            ),
+            Self::InvalidMuxInputs {
+                select,
+                then,
+                else_,
+            } => write!(
+                f,
+                "Expected distinct input indices in 0..3, got select {select}, then {then}, else {else_}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ObGateError {}
+
+/*The magnitude of the input encoding of an n-input obfuscated gate (see ob_gate_nop), on the u32 torus
+The inputs encode true and false as +mu and -mu, so the sum of n inputs with t true ones is (2t-n)*mu,
+i.e. n+1 levels spaced by 2*mu. For any threshold the levels must fit in the two half-tori of the sign bootstrapping,
+which holds for 2*mu = 2^-p with 2^p >= 2n (the level closest to a boundary is then mu away from it).
+mu = 1/8 (the boolean encoding) for n <= 2, 1/16 for n <= 4, 1/32 for n <= 8, ...
+Fails for n = 0 (NoInputs) and n > 2^30 (FanInTooLarge, mu would be below 1)
+*/
+pub fn nop_input_magnitude(fan_in: usize) -> Result<u32, ObGateError> {
+    if fan_in == 0 {
+        return Err(ObGateError::NoInputs);
+    }
+    match fan_in
+        .checked_mul(2)
+        .and_then(usize::checked_next_power_of_two)
+    {
+        Some(spacing) if spacing.ilog2() < 32 => Ok(1 << (32 - spacing.ilog2().max(2) - 1)),
+        _ => Err(ObGateError::FanInTooLarge { fan_in }),
+    }
+}
+
+//Check that an encrypted gate input has the size lwe_size (a trivial input takes any size)
+pub(crate) fn check_input_size(ct: &Ciphertext, lwe_size: LweSize) -> Result<(), ObGateError> {
+    match ct {
+        Ciphertext::Encrypted(ct_lwe) if ct_lwe.lwe_size() != lwe_size => {
+            Err(ObGateError::LweSizeMismatch {
+                expected: lwe_size,
+                found: ct_lwe.lwe_size(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+//The noiseless LWE ciphertext of a trivial input, encoded as +-magnitude
+pub(crate) fn trivial_lwe(
+    m: bool,
+    lwe_size: LweSize,
+    magnitude: u32,
+    ciphertext_modulus: CiphertextModulus<u32>,
+) -> LweCiphertextOwned<u32> {
+    allocate_and_trivially_encrypt_new_lwe_ciphertext(
+        lwe_size,
+        Plaintext(if m {
+            magnitude
+        } else {
+            magnitude.wrapping_neg()
+        }),
+        ciphertext_modulus,
+    )
+}
+
+/*The LWE ciphertext of a gate input of size lwe_size
+A trivial input (e.g. an accumulator seeded with Ciphertext::Trivial) is encoded as a noiseless LWE of +-magnitude
+*/
+pub(crate) fn input_lwe(
+    ct: &Ciphertext,
+    lwe_size: LweSize,
+    magnitude: u32,
+) -> Result<Cow<'_, LweCiphertextOwned<u32>>, ObGateError> {
+    check_input_size(ct, lwe_size)?;
+    match ct {
+        Ciphertext::Encrypted(ct_lwe) => Ok(Cow::Borrowed(ct_lwe)),
+        Ciphertext::Trivial(m) => Ok(Cow::Owned(trivial_lwe(
+            *m,
+            lwe_size,
+            magnitude,
+            CiphertextModulus::new_native(),
+        ))),
+    }
+}
+
+//The LWE ciphertext of a gate parameter d of size lwe_size, which must be encrypted by the client
+pub(crate) fn parameter_lwe(
+    ct: &Ciphertext,
+    lwe_size: LweSize,
+) -> Result<&LweCiphertextOwned<u32>, ObGateError> {
+    match ct {
+        Ciphertext::Encrypted(ct_lwe) if ct_lwe.lwe_size() == lwe_size => Ok(ct_lwe),
+        Ciphertext::Encrypted(ct_lwe) => Err(ObGateError::LweSizeMismatch {
+            expected: lwe_size,
+            found: ct_lwe.lwe_size(),
+        }),
+        Ciphertext::Trivial(_) => Err(ObGateError::TrivialGateParameter),
+    }
+}
+
+//Check that the TGSW parameter A was encrypted with tgsw_params
+pub(crate) fn check_tgsw<C: Container<Element = u32>>(
+    A: &TgswCiphertext<C>,
+    tgsw_params: &TgswParams,
+) -> Result<(), ObGateError> {
+    let expected_lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+    if A.lwe_size() != expected_lwe_size || A.decomp_level() != tgsw_params.decomp_level {
+        return Err(ObGateError::TgswMismatch {
+            expected_lwe_size,
+            found_lwe_size: A.lwe_size(),
+            expected_decomp_level: tgsw_params.decomp_level,
+            found_decomp_level: A.decomp_level(),
+        });
+    }
+    Ok(())
+}
+
+impl<C: Container<Element = u32>> TgswCiphertext<C> {
+    /*Perform an external product between TGSW and TLWE ciphertexts
+    for C1 = TGSW(M1) and c2 = TLWE(m2), the external product is defined as
+    cr = GT^-1(c2) * C1 = GT^-1(c2) * (Z+M1.GT) =  GT^-1(c2) * Z + M1 GT^-1(c2) * GT = LWE(0) + M1c2
+    Which means cr is an LWE encryption of M1*m2, given a small "limited" round and multiplicative noise
+    See: Marc Joye, "Guide to Fully Homomorphic Encryption over the [Discretized] Torus" : https://eprint.iacr.org/2021/1402
+    A trivial c2 is encoded as a noiseless LWE of +-1/8, an LWE size that differs from the TGSW one is an error
+    */
+    pub fn ext_product(
+        &self,
+        ct_lwe: &Ciphertext,
+        tgsw_params: &TgswParams, /*, server_key:&ServerKey*/
+    ) -> Result<Ciphertext, ObGateError> {
+        check_tgsw(self, tgsw_params)?;
+        let ct_lwe_obj = input_lwe(ct_lwe, self.lwe_size(), EIGHTH)?;
+
+        let x = self.ext_product_lwe(
+            &ct_lwe_obj,
+            tgsw_params.decomp_base,
+            tgsw_params.decomp_level,
+            tgsw_params.decomp_kind,
+        );
+
+        //CAUTION: Bootstrapping here can lead to unintended results (i.e. reducess -3/8 to -1/8).
+        //The bootstrapping should be done at the end of the computation.
+
+        /*OB_BOOLEAN_ENGINE.with(|engine_cell| {
+             let eng = & mut engine_cell.borrow_mut() as &mut BooleanEngine;
+             let bootstrapper = &mut eng.bootstrapper;
+             bootstrapper.apply_bootstrapping_pattern(x, &server_key)
+
+        })*/
+        Ok(Ciphertext::Encrypted(x))
+    }
+}
+
+/*The bootstrapping buffers and the scratch space of the external product, reused from one gate to the next
+so that a gate only allocates when a trivial input is turned into an LWE ciphertext
+*/
+pub(crate) struct GateScratch {
+    bootstrapper: Bootstrapper,
+    //The gate output before the bootstrapping, and the first branch of a multiplexer (LWE size of the parameters)
+    product: Vec<u32>,
+    branch: Vec<u32>,
+    //The l digits of an LWE coefficient
+    digits: Vec<u32>,
+}
+
+impl GateScratch {
+    pub(crate) fn new() -> Self {
+        let mut seeder = new_seeder();
+        Self {
+            bootstrapper: Bootstrapper::new(seeder.as_mut()),
+            product: Vec::new(),
+            branch: Vec::new(),
+            digits: Vec::new(),
+        }
+    }
+
+    //Sign bootstrapping of ct_lwe in place, to +-output_magnitude
+    fn bootstrap(
+        &mut self,
+        ct_lwe: LweCiphertextOwned<u32>,
+        server_key: &ServerKey,
+        output_magnitude: u32,
+    ) -> LweCiphertextOwned<u32> {
+        match self.bootstrapper.apply_bootstrapping_pattern_with_output(
+            ct_lwe,
+            server_key,
+            output_magnitude,
+        ) {
+            Ciphertext::Encrypted(ct_lwe) => ct_lwe,
+            Ciphertext::Trivial(_) => {
+                unreachable!("The bootstrapping outputs an encrypted ciphertext")
+            }
+        }
+    }
+
+    //The product buffer, initialized with the additive parameter d
+    fn product_from(&mut self, ct_d_lwe: &LweCiphertextOwned<u32>) -> LweCiphertextOwned<u32> {
+        let mut product = std::mem::take(&mut self.product);
+        product.clear();
+        product.extend_from_slice(ct_d_lwe.as_ref());
+        LweCiphertext::from_container(product, ct_d_lwe.ciphertext_modulus())
+    }
+
+    //output += A x ct_lwe, with the decomposition parameters of tgsw_params
+    fn ext_product_add_assign<C: Container<Element = u32>>(
+        &mut self,
+        output: &mut LweCiphertextOwned<u32>,
+        A: &TgswCiphertext<C>,
+        ct_lwe: &LweCiphertextOwned<u32>,
+        tgsw_params: &TgswParams,
+    ) {
+        self.digits.resize(tgsw_params.decomp_level, 0);
+        A.ext_product_lwe_add_assign(
+            output,
+            ct_lwe,
+            tgsw_params.decomp_base,
+            tgsw_params.decomp_level,
+            tgsw_params.decomp_kind,
+            &mut self.digits,
+        );
+    }
+
+    /*ct_lwe = bootstrap(A x ct_lwe + d), the core of the gates with a TGSW parameter
+    The product buffer takes the bootstrapped ciphertext and ct_lwe takes the buffer back, so nothing is allocated
+    */
+    fn gate_assign<C: Container<Element = u32>>(
+        &mut self,
+        ct_lwe: &mut LweCiphertextOwned<u32>,
+        A: &TgswCiphertext<C>,
+        ct_d_lwe: &LweCiphertextOwned<u32>,
+        tgsw_params: &TgswParams,
+        server_key: &ServerKey,
+        output_magnitude: u32,
+    ) {
+        let mut product = self.product_from(ct_d_lwe);
+        self.ext_product_add_assign(&mut product, A, ct_lwe, tgsw_params);
+
+        let product = self.bootstrap(product, server_key, output_magnitude);
+        self.product = std::mem::replace(ct_lwe, product).into_container();
+    }
+
+    //See oblivious_gates::ob_gate_2op, ct_1 is replaced with the output
+    pub(crate) fn ob_gate_2op_assign<C: Container<Element = u32>>(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        ct_2: &Ciphertext,
+        A: &TgswCiphertext<C>,
+        ct_d: &Ciphertext,
+        tgsw_params: &TgswParams,
+        server_key: &ServerKey,
+    ) -> Result<(), ObGateError> {
+        let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+        check_tgsw(A, tgsw_params)?;
+        let ct_2_lwe = input_lwe(ct_2, lwe_size, EIGHTH)?;
+        let ct_d_lwe = parameter_lwe(ct_d, lwe_size)?;
+        let mut ct_1_lwe = take_input_lwe(ct_1, lwe_size, EIGHTH)?;
+
+        lwe_ciphertext_add_assign(&mut ct_1_lwe, &*ct_2_lwe);
+        self.gate_assign(&mut ct_1_lwe, A, ct_d_lwe, tgsw_params, server_key, EIGHTH);
+
+        *ct_1 = Ciphertext::Encrypted(ct_1_lwe);
+        Ok(())
+    }
+
+    //See oblivious_gates::ob_gate_1op, ct_1 is replaced with the output
+    pub(crate) fn ob_gate_1op_assign(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        ct_arg_1: &Ciphertext,
+        server_key: &ServerKey,
+    ) -> Result<(), ObGateError> {
+        let ct_arg_1_lwe = match ct_arg_1 {
+            Ciphertext::Encrypted(ct_lwe) => ct_lwe,
+            Ciphertext::Trivial(_) => return Err(ObGateError::TrivialGateParameter),
+        };
+        let mut ct_1_lwe = take_input_lwe(ct_1, ct_arg_1_lwe.lwe_size(), EIGHTH)?;
+
+        lwe_ciphertext_add_assign(&mut ct_1_lwe, ct_arg_1_lwe);
+
+        *ct_1 = Ciphertext::Encrypted(self.bootstrap(ct_1_lwe, server_key, EIGHTH));
+        Ok(())
+    }
+
+    //See oblivious_gates::ob_gate_1op_tgsw, ct_1 is replaced with the output
+    pub(crate) fn ob_gate_1op_tgsw_assign<C: Container<Element = u32>>(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        A: &TgswCiphertext<C>,
+        ct_d: &Ciphertext,
+        tgsw_params: &TgswParams,
+        server_key: &ServerKey,
+    ) -> Result<(), ObGateError> {
+        let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+        check_tgsw(A, tgsw_params)?;
+        let ct_d_lwe = parameter_lwe(ct_d, lwe_size)?;
+        let mut ct_1_lwe = take_input_lwe(ct_1, lwe_size, EIGHTH)?;
+
+        self.gate_assign(&mut ct_1_lwe, A, ct_d_lwe, tgsw_params, server_key, EIGHTH);
+
+        *ct_1 = Ciphertext::Encrypted(ct_1_lwe);
+        Ok(())
+    }
+
+    /*See oblivious_gates::ob_gate_nop_with_output, with the inputs ct_1 and others (fan-in 1 + others.len()),
+    ct_1 is replaced with the output
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn ob_gate_nop_assign<C: Container<Element = u32>, B: Borrow<Ciphertext>>(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        others: &[B],
+        A: &TgswCiphertext<C>,
+        ct_d: &Ciphertext,
+        tgsw_params: &TgswParams,
+        server_key: &ServerKey,
+        output_magnitude: u32,
+    ) -> Result<(), ObGateError> {
+        let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+        let input_magnitude = nop_input_magnitude(1 + others.len())?;
+        check_tgsw(A, tgsw_params)?;
+        let ct_d_lwe = parameter_lwe(ct_d, lwe_size)?;
+        for ct in others {
+            check_input_size(ct.borrow(), lwe_size)?;
+        }
+        let mut ct_1_lwe = take_input_lwe(ct_1, lwe_size, input_magnitude)?;
+
+        //Sum of the inputs
+        for ct in others {
+            let ct_lwe = input_lwe(ct.borrow(), lwe_size, input_magnitude)?;
+            lwe_ciphertext_add_assign(&mut ct_1_lwe, &*ct_lwe);
+        }
+        self.gate_assign(
+            &mut ct_1_lwe,
+            A,
+            ct_d_lwe,
+            tgsw_params,
+            server_key,
+            output_magnitude,
+        );
+
+        *ct_1 = Ciphertext::Encrypted(ct_1_lwe);
+        Ok(())
+    }
+
+    //See oblivious_gates::ob_reencode, ct is replaced with the output
+    pub(crate) fn ob_reencode_assign(
+        &mut self,
+        ct: &mut Ciphertext,
+        output_magnitude: u32,
+        tgsw_params: &TgswParams,
+        server_key: &ServerKey,
+    ) -> Result<(), ObGateError> {
+        let ct_lwe = take_input_lwe(ct, tgsw_params.lwe_dimension().to_lwe_size(), EIGHTH)?;
+
+        *ct = Ciphertext::Encrypted(self.bootstrap(ct_lwe, server_key, output_magnitude));
+        Ok(())
+    }
+
+    //See oblivious_gates::ob_mux, with the inputs ct_0, ct_1 and ct_2, ct_0 is replaced with the output
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn ob_mux_assign<
+        C: Container<Element = u32>,
+        T: Borrow<TgswCiphertext<C>>,
+        D: Borrow<Ciphertext>,
+    >(
+        &mut self,
+        ct_0: &mut Ciphertext,
+        ct_1: &Ciphertext,
+        ct_2: &Ciphertext,
+        A: &[[T; 3]; 2],
+        ct_d: &[D; 2],
+        tgsw_params: &TgswParams,
+        server_key: &ServerKey,
+    ) -> Result<(), ObGateError> {
+        let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+        for A_i in A.iter().flatten() {
+            check_tgsw(A_i.borrow(), tgsw_params)?;
+        }
+        let ct_d_lwe = [
+            parameter_lwe(ct_d[0].borrow(), lwe_size)?,
+            parameter_lwe(ct_d[1].borrow(), lwe_size)?,
+        ];
+        let ct_1_lwe = input_lwe(ct_1, lwe_size, EIGHTH)?;
+        let ct_2_lwe = input_lwe(ct_2, lwe_size, EIGHTH)?;
+        let mut ct_0_lwe = take_input_lwe(ct_0, lwe_size, EIGHTH)?;
+
+        //Both branches are computed before ct_0 is overwritten, the first one is kept in the branch buffer
+        for (j, (A_branch, ct_d_lwe)) in A.iter().zip(ct_d_lwe).enumerate() {
+            let mut product = self.product_from(ct_d_lwe);
+            for (A_i, ct_lwe) in A_branch.iter().zip([&ct_0_lwe, &*ct_1_lwe, &*ct_2_lwe]) {
+                self.ext_product_add_assign(&mut product, A_i.borrow(), ct_lwe, tgsw_params);
+            }
+            let product = self.bootstrap(product, server_key, EIGHTH);
+            if j == 0 {
+                self.product = std::mem::replace(&mut self.branch, product.into_container());
+            } else {
+                //out = b_0 + b_1 + 1/8
+                ct_0_lwe.as_mut().copy_from_slice(&self.branch);
+                lwe_ciphertext_add_assign(&mut ct_0_lwe, &product);
+                lwe_ciphertext_plaintext_add_assign(&mut ct_0_lwe, Plaintext(EIGHTH));
+                self.product = product.into_container();
+            }
+        }
+
+        *ct_0 = Ciphertext::Encrypted(ct_0_lwe);
+        Ok(())
+    }
+}
+
+/*Take the LWE ciphertext of an input that is overwritten by the gate output (see input_lwe)
+Called once every other check passed, so that ct is left untouched on an error
+*/
+fn take_input_lwe(
+    ct: &mut Ciphertext,
+    lwe_size: LweSize,
+    magnitude: u32,
+) -> Result<LweCiphertextOwned<u32>, ObGateError> {
+    check_input_size(ct, lwe_size)?;
+    match std::mem::replace(ct, Ciphertext::Trivial(false)) {
+        Ciphertext::Encrypted(ct_lwe) => Ok(ct_lwe),
+        Ciphertext::Trivial(m) => Ok(trivial_lwe(
+            m,
+            lwe_size,
+            magnitude,
+            CiphertextModulus::new_native(),
+        )),
+    }
+}
@@ -0,0 +1,251 @@
+#![allow(non_snake_case)]
+//Batched, parallel encryption of the obfuscated gate parameters (A, d) on the client side
+//and the derivation of the plaintext parameters of the n-input threshold gates and of the multiplexer
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::{Ciphertext, ClientKey};
+use tfhe::conformance::ParameterSetConformant;
+use tfhe::core_crypto::commons::generators::DeterministicSeeder;
+use tfhe::core_crypto::commons::math::random::Seed;
+use tfhe::core_crypto::prelude::*;
+use tfhe::core_crypto::seeders::new_seeder;
+use tfhe::named::Named;
+use tfhe_versionable::Versionize;
+
+use crate::backward_compatibility::GateParamsVersions;
+use crate::gate_core::{nop_input_magnitude, ObGateError};
+use crate::noise::EIGHTH;
+use crate::tgsw::{
+    SeededTgswCiphertext, TgswCiphertext, TgswCiphertextConformanceParams, TgswParams,
+};
+
+/*The encrypted parameters of a list of obfuscated gates, as sent by the client to the server
+C_gate_params[i] is the TGSW encryption of the multiplicative parameter A of gate i (seeded, see SeededTgswCiphertext)
+cd_gate_params[i] is the LWE encryption of its additive parameter d
+*/
+#[derive(Serialize, Deserialize, Versionize)]
+#[versionize(GateParamsVersions)]
+pub struct GateParams {
+    pub C_gate_params: Vec<SeededTgswCiphertext>,
+    pub cd_gate_params: Vec<Ciphertext>,
+}
+
+impl Named for GateParams {
+    const NAME: &'static str = "ohlg::GateParams";
+}
+
+//The expected number of gates and ciphertext shapes, to check the deserialized gate parameters
+#[derive(Copy, Clone)]
+pub struct GateParamsConformanceParams {
+    pub gate_count: usize,
+    pub tgsw_params: TgswCiphertextConformanceParams<u32>,
+}
+
+impl GateParamsConformanceParams {
+    pub fn new(gate_count: usize, tgsw_params: &TgswParams) -> Self {
+        Self {
+            gate_count,
+            tgsw_params: tgsw_params.into(),
+        }
+    }
+}
+
+impl ParameterSetConformant for GateParams {
+    type ParameterSet = GateParamsConformanceParams;
+
+    fn is_conformant(&self, parameter_set: &Self::ParameterSet) -> bool {
+        let lwe_params = LweCiphertextConformanceParams {
+            lwe_dim: parameter_set.tgsw_params.lwe_dimension,
+            ct_modulus: parameter_set.tgsw_params.ciphertext_modulus,
+            ms_decompression_method: MsDecompressionType::ClassicPbs,
+        };
+
+        self.C_gate_params.len() == parameter_set.gate_count
+            && self.cd_gate_params.len() == parameter_set.gate_count
+            && self
+                .C_gate_params
+                .iter()
+                .all(|C_gate| C_gate.is_conformant(&parameter_set.tgsw_params))
+            && self.cd_gate_params.iter().all(|cd_gate| match cd_gate {
+                Ciphertext::Encrypted(ct_lwe) => ct_lwe.is_conformant(&lwe_params),
+                Ciphertext::Trivial(_) => false,
+            })
+    }
+}
+
+/*Encrypt a batch of gate parameters in parallel, one (A, d) plaintext pair per obfuscated gate
+A: the multiplicative parameter in Z/PZ, encrypted as a seeded TGSW ciphertext
+d: the additive parameter on the u32 torus (e.g. 7<<29 for AND), encrypted as is (as ClientKey::encrypt_abs does)
+The gate parameters are in the order of gate_plaintexts, ready to be sent to the server
+*/
+pub fn new_encrypt_gate_params(
+    gate_plaintexts: &[(u32, u32)],
+    tgsw_params: &TgswParams,
+    client_key: &ClientKey,
+) -> GateParams {
+    let mut seeder = new_seeder();
+    new_encrypt_gate_params_with_seed(gate_plaintexts, tgsw_params, client_key, seeder.seed())
+}
+
+/*Same as new_encrypt_gate_params, with all the randomness derived from seed
+A master CSPRNG seeded with seed is forked into one child seed per gate (drawn sequentially, in the gate order),
+and each gate is then encrypted in parallel from its own deterministic seeder.
+The output only depends on the seed and the inputs, not on the number of threads or the scheduling.
+CAUTION: anyone who knows the seed can recompute the encryption noise, so it must be kept as secret as the client key
+*/
+pub fn new_encrypt_gate_params_with_seed(
+    gate_plaintexts: &[(u32, u32)],
+    tgsw_params: &TgswParams,
+    client_key: &ClientKey,
+    seed: Seed,
+) -> GateParams {
+    let (lwe_sk, encryption_noise) = client_key.encryption_key_and_noise();
+    let ciphertext_modulus = CiphertextModulus::new_native();
+
+    let mut master_seeder = DeterministicSeeder::<DefaultRandomGenerator>::new(seed);
+    let gate_seeds: Vec<Seed> = gate_plaintexts
+        .iter()
+        .map(|_| master_seeder.seed())
+        .collect();
+
+    let (C_gate_params, cd_gate_params) = gate_plaintexts
+        .par_iter()
+        .zip(gate_seeds.into_par_iter())
+        .map(|(&(A, d), gate_seed)| {
+            let mut gate_seeder = DeterministicSeeder::<DefaultRandomGenerator>::new(gate_seed);
+
+            let C_gate = SeededTgswCiphertext::new_encrypt_with_key(
+                A,
+                tgsw_params.decomp_base,
+                tgsw_params.decomp_level,
+                &lwe_sk,
+                encryption_noise,
+                ciphertext_modulus,
+                &mut gate_seeder,
+            )
+            .expect("The native modulus can be seeded");
+
+            let mut generator = EncryptionRandomGenerator::<DefaultRandomGenerator>::new(
+                gate_seeder.seed(),
+                &mut gate_seeder,
+            );
+            let cd_gate = allocate_and_encrypt_new_lwe_ciphertext(
+                &lwe_sk,
+                Plaintext(d),
+                encryption_noise,
+                ciphertext_modulus,
+                &mut generator,
+            );
+
+            (C_gate, Ciphertext::Encrypted(cd_gate))
+        })
+        .unzip();
+
+    GateParams {
+        C_gate_params,
+        cd_gate_params,
+    }
+}
+
+/*The plaintext (A, d) of an n-input threshold gate (see ob_gate_nop): the output is true iff at least `threshold`
+of the fan_in inputs are true, or the opposite when negated, e.g.
+- majority of 3 (the carry of a full adder): (3, 2, false)
+- n-input AND / OR / NAND / NOR: (n, n, false) / (n, 1, false) / (n, n, true) / (n, 1, true)
+With inputs encoded as +-mu (mu = nop_input_magnitude(fan_in)) and A = 1, the sum of the inputs with t true ones is
+(2t - fan_in)*mu, and d = (fan_in - 2*threshold + 1)*mu moves it to (2(t - threshold) + 1)*mu, which is positive
+(true after the sign bootstrapping) iff t >= threshold. Adding 1/2 to d negates the output.
+The thresholds 0 and fan_in+1 are the constant functions, computed with A = 0 and d = +-1/8.
+For fan_in = 2, the encoding is the boolean one and (A, d) are the ob_gate_2op parameters (e.g. d = 7<<29 for AND)
+Fails for fan_in = 0 (NoInputs), a fan-in too large for the torus (FanInTooLarge) or threshold > fan_in + 1
+*/
+pub fn threshold_gate_plaintexts(
+    fan_in: usize,
+    threshold: usize,
+    negated: bool,
+) -> Result<(u32, u32), ObGateError> {
+    let mu = nop_input_magnitude(fan_in)?;
+    if threshold > fan_in + 1 {
+        return Err(ObGateError::ThresholdOutOfRange { threshold, fan_in });
+    }
+    let negation = if negated { 1u32 << 31 } else { 0 };
+
+    if threshold == 0 || threshold == fan_in + 1 {
+        let constant = if threshold == 0 {
+            EIGHTH
+        } else {
+            EIGHTH.wrapping_neg()
+        };
+        return Ok((0, constant.wrapping_add(negation)));
+    }
+
+    let d = (fan_in as i64 - 2 * threshold as i64 + 1) as u32;
+    Ok((1, d.wrapping_mul(mu).wrapping_add(negation)))
+}
+
+//Encrypt a boolean input of an n-input gate with the encoding of ob_gate_nop (+-nop_input_magnitude(fan_in))
+pub fn encrypt_nop_input(
+    m: bool,
+    fan_in: usize,
+    client_key: &ClientKey,
+) -> Result<Ciphertext, ObGateError> {
+    let mu = nop_input_magnitude(fan_in)?;
+    Ok(client_key.encrypt_abs(if m { mu } else { mu.wrapping_neg() }))
+}
+
+/*The plaintext (A, d) of an obfuscated multiplexer (see ob_mux): the output is m[then] if m[select] is true,
+m[else_] otherwise. Fails with InvalidMuxInputs unless the input indices are distinct indices in 0..3
+Branch 0 computes AND(m[select], m[then]) and branch 1 AND(NOT m[select], m[else_]), with A = -1 (u32::MAX) negating
+the select: both levels of the select are +-1/8, so -c_s is an encryption of NOT m[select]
+*/
+pub fn mux_gate_plaintexts(
+    select: usize,
+    then: usize,
+    else_: usize,
+) -> Result<([[u32; 3]; 2], [u32; 2]), ObGateError> {
+    if select >= 3 || then >= 3 || else_ >= 3 || select == then || select == else_ || then == else_
+    {
+        return Err(ObGateError::InvalidMuxInputs {
+            select,
+            then,
+            else_,
+        });
+    }
+    let mut A = [[0u32; 3]; 2];
+    A[0][select] = 1;
+    A[0][then] = 1;
+    A[1][select] = 1u32.wrapping_neg();
+    A[1][else_] = 1;
+    let d = EIGHTH.wrapping_neg();
+    Ok((A, [d, d]))
+}
+
+/*The parameters of an obfuscated multiplexer as 6 consecutive (A, d) gate parameter slots, as used by a netlist::GateOp::Mux:
+slot 3j+i holds (A[j][i], d[j]), the d of each branch is repeated in its 3 slots (only the first one is used)
+*/
+pub fn mux_gate_param_slots(
+    select: usize,
+    then: usize,
+    else_: usize,
+) -> Result<[(u32, u32); 6], ObGateError> {
+    let (A, d) = mux_gate_plaintexts(select, then, else_)?;
+    Ok(std::array::from_fn(|slot| {
+        (A[slot / 3][slot % 3], d[slot / 3])
+    }))
+}
+
+//Encrypt the parameters of an obfuscated multiplexer (see mux_gate_plaintexts), ready to be used by ob_mux
+pub fn new_encrypt_mux_params(
+    select: usize,
+    then: usize,
+    else_: usize,
+    tgsw_params: &TgswParams,
+    client_key: &ClientKey,
+) -> Result<([[TgswCiphertext; 3]; 2], [Ciphertext; 2]), ObGateError> {
+    let (A, d) = mux_gate_plaintexts(select, then, else_)?;
+    Ok((
+        A.map(|A_branch| {
+            A_branch.map(|A_i| TgswCiphertext::new_encrypt(A_i, tgsw_params, client_key))
+        }),
+        d.map(|d| client_key.encrypt_abs(d)),
+    ))
+}
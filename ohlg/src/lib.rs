@@ -1,3 +1,14 @@
 #![allow(non_snake_case)]
+pub mod backward_compatibility;
+pub mod bristol;
+pub mod gate_compiler;
+mod gate_core;
+pub mod gate_params;
+pub mod netlist;
+pub mod noise;
+pub mod oblivious_evaluator;
 pub mod oblivious_gates;
+pub mod optimizer;
 pub mod tgsw;
+pub mod topology;
+pub mod yosys;
@@ -1,4 +1,5 @@
 #![allow(non_snake_case)]
+use ohlg::gate_compiler::GateKind;
 use ohlg::oblivious_gates::*;
 use ohlg::tgsw::*;
 use std::time::Duration;
@@ -9,30 +10,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     /*
     For TFHE-io parameters, the Decomposition base B = 16 and level l=3 are used. Obfuscated gate error probability is 10^-18
     For TFHE-rs default Boolean parameters, the Decomposition base B = 64 and level l=2 are used. Obfuscated gate error probability is 10^-25
+    For TFHE-rs KS->PBS Boolean parameters (encrypted under the big key), B = 64 and l = 2 are used as well
     */
     let param_choice = "TFHE_RS";
-    let tgsw_params: TgswParams;
-
-    match param_choice {
+    let tgsw_params: TgswParams = match param_choice {
         "TFHE_IO" => {
             //tgsw_decomposition_base = 16;
             //tgsw_decomposition_level = 3;
             //selected_parameters = TFHE_LIB_PARAMETERS;
-            tgsw_params = TgswParams::new(TFHE_LIB_PARAMETERS, 16, 3);
+            TgswParams::new(TFHE_LIB_PARAMETERS, 16, 3)
         }
         "TFHE_RS" => {
             //tgsw_decomposition_base = 64;
             //tgsw_decomposition_level = 2;
             //selected_parameters = DEFAULT_PARAMETERS;
-            tgsw_params = TgswParams::new(DEFAULT_PARAMETERS, 64, 2);
+            TgswParams::new(DEFAULT_PARAMETERS, 64, 2)
+        }
+        "TFHE_RS_KS_PBS" => {
+            //The ciphertexts are encrypted under the big key (glwe_dimension*polynomial_size = 1536)
+            TgswParams::new(DEFAULT_PARAMETERS_KS_PBS, 64, 2)
         }
         _ => {
             //tgsw_decomposition_base = 64;
             //tgsw_decomposition_level = 2;
             //selected_parameters = DEFAULT_PARAMETERS;
-            tgsw_params = TgswParams::new(DEFAULT_PARAMETERS, 64, 2);
+            TgswParams::new(DEFAULT_PARAMETERS, 64, 2)
         }
-    }
+    };
+    //Check the decomposition against the modulus and the parameter set before encrypting anything
+    tgsw_params.validate()?;
 
     //Create the client and server keys
     let client_key = ClientKey::new(&tgsw_params.tfhe_params);
@@ -42,12 +48,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     //m1 and m2 are the Boolean plaintext values to be encrypted
     //M is the plaintext value of the multiplicative parameter (=1 for NAND operation)
     //d is the plaintext value of the additive parameter (=3/8 for NAND operation)
+    //See GateKind for the other gates (or gate_compiler::compile_truth_table for a truth table)
 
     let m1: bool = false;
     let m2: bool = false;
-    let M: u32 = 1;
-    let d: u32 = (1 << (32 - 3)) * 3;
-    let opr_str = "NAND";
+    let gate_kind = GateKind::Nand;
+    let (M, d) = gate_kind.plaintexts();
+    let opr_str = format!("{:?}", gate_kind).to_uppercase();
 
     //Check the operation correctness (The result of the obfuscated NAND gate should be true if both m1 and m2 are false)
     //The counters for the true and false results (the result of the obfuscated gate, depends on the input values)
@@ -70,7 +77,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let start = Instant::now();
 
         //Execute the obfuscated gate
-        let ct_res = ob_gate_2op(&ct_1, &ct_2, &tgsw_ct, &ct_d, &tgsw_params, &server_key);
+        let ct_res = ob_gate_2op(&ct_1, &ct_2, &tgsw_ct, &ct_d, &tgsw_params, &server_key)?;
 
         //Capture end time
         let duration = start.elapsed();
@@ -78,7 +85,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         //Decrypt the result and check the correctness.
         let dec_val = client_key.decrypt(&ct_res);
-        if dec_val == false {
+        if !dec_val {
             false_counter += 1;
         } else {
             true_counter += 1;
@@ -0,0 +1,719 @@
+#![allow(non_snake_case)]
+/*A serializable description of an obfuscated circuit (the "data flow description" sent by the client):
+wires, input and output ports, and the obfuscated gates with the index of their encrypted parameters (see GateParams).
+The netlist only reveals the topology of the circuit, the function of each gate is hidden in its parameters.
+Netlist::execute runs it on the server with any encrypted inputs and gate parameters,
+and Netlist::simulate runs it on the plaintexts, to check a circuit and its gate parameters without a key
+*/
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use tfhe::boolean::prelude::{Ciphertext, ClientKey};
+use tfhe::core_crypto::commons::traits::Container;
+use tfhe::named::Named;
+use tfhe_versionable::Versionize;
+
+use crate::backward_compatibility::{GateOpVersions, GateVersions, NetlistVersions, PortVersions};
+use crate::gate_compiler::{GateKind, TruthTable, UnaryGateKind};
+use crate::gate_core::{nop_input_magnitude, ObGateError};
+use crate::gate_params::{new_encrypt_gate_params, GateParams};
+use crate::noise::EIGHTH;
+use crate::oblivious_evaluator::ObliviousEvaluator;
+use crate::tgsw::{TgswCiphertext, TgswParams};
+
+//The index of a wire, in 0..Netlist::num_wires
+pub type WireId = usize;
+
+//The obfuscated gate evaluated by a Gate of the netlist
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Versionize)]
+#[versionize(GateOpVersions)]
+pub enum GateOp {
+    //ob_gate_2op on 2 inputs, with the parameters (A, d) = (C_gate_params[param], cd_gate_params[param])
+    TwoOp,
+    //ob_gate_1op_tgsw on 1 input (BUF, NOT, CONST0 or CONST1), with the parameters of slot param
+    OneOp,
+    /*ob_gate_nop_with_output on 1 or more inputs, with the parameters of slot param
+    The inputs must be encoded as +-nop_input_magnitude(fan-in), see Reencode and output_magnitude
+    */
+    Threshold { output_magnitude: u32 },
+    /*ob_mux on 3 inputs, with the 6 parameter slots param..param+6 (see gate_params::mux_gate_param_slots):
+    A[j][i] = C_gate_params[param+3j+i] and d[j] = cd_gate_params[param+3j]
+    */
+    Mux,
+    //ob_reencode of a boolean input to +-output_magnitude, without any parameter (param is ignored)
+    Reencode { output_magnitude: u32 },
+}
+
+impl GateOp {
+    //The number of inputs of the gate, None for any number of inputs (at least 1)
+    pub fn arity(self) -> Option<usize> {
+        match self {
+            Self::TwoOp => Some(2),
+            Self::OneOp | Self::Reencode { .. } => Some(1),
+            Self::Threshold { .. } => None,
+            Self::Mux => Some(3),
+        }
+    }
+
+    //The number of consecutive gate parameter slots used by the gate
+    pub fn param_count(self) -> usize {
+        match self {
+            Self::TwoOp | Self::OneOp | Self::Threshold { .. } => 1,
+            Self::Mux => 6,
+            Self::Reencode { .. } => 0,
+        }
+    }
+
+    //The number of bootstrappings of the gate
+    pub fn bootstrap_count(self) -> usize {
+        match self {
+            Self::Mux => 2,
+            _ => 1,
+        }
+    }
+
+    //The magnitude of the output encoding (+-1/8 for the boolean gates)
+    pub fn output_magnitude(self) -> u32 {
+        match self {
+            Self::Threshold { output_magnitude } | Self::Reencode { output_magnitude } => {
+                output_magnitude
+            }
+            _ => EIGHTH,
+        }
+    }
+}
+
+//A gate of the netlist: the output wire is driven by op applied to the input wires
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Versionize)]
+#[versionize(GateVersions)]
+pub struct Gate {
+    pub op: GateOp,
+    pub inputs: Vec<WireId>,
+    pub output: WireId,
+    //The first gate parameter slot of the gate
+    pub param: usize,
+}
+
+impl Gate {
+    /*The gate parameter slots of the gate, param..param+op.param_count() (empty for a gate without parameters,
+    whose param is ignored), None if they do not fit in usize
+    */
+    pub fn param_slots(&self) -> Option<Range<usize>> {
+        match self.op.param_count() {
+            0 => Some(0..0),
+            count => Some(self.param..self.param.checked_add(count)?),
+        }
+    }
+}
+
+//A named group of wires, e.g. the 8 bits of a character (bit 0 first)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Versionize)]
+#[versionize(PortVersions)]
+pub struct Port {
+    pub name: String,
+    pub wires: Vec<WireId>,
+}
+
+/*The circuit: the input port wires are driven by the client inputs, the other wires by exactly one gate each.
+The gates are in topological order (each gate only reads the input wires and the outputs of the previous gates),
+which is the evaluation order of the server
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Versionize)]
+#[versionize(NetlistVersions)]
+pub struct Netlist {
+    pub num_wires: usize,
+    pub inputs: Vec<Port>,
+    pub outputs: Vec<Port>,
+    pub gates: Vec<Gate>,
+}
+
+impl Named for Netlist {
+    const NAME: &'static str = "ohlg::Netlist";
+}
+
+//The reasons why a netlist cannot be evaluated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetlistError {
+    //A wire index is not below num_wires
+    WireOutOfRange {
+        wire: WireId,
+        num_wires: usize,
+    },
+    //A wire is driven by more than one input or gate
+    MultipleDrivers {
+        wire: WireId,
+    },
+    //A gate or an output port reads a wire that is not driven by an input or a previous gate
+    UndrivenWire {
+        wire: WireId,
+    },
+    //A gate has a number of inputs that its operation does not take
+    InvalidArity {
+        gate: usize,
+        op: GateOp,
+        found: usize,
+    },
+    //A threshold or reencode gate with an output magnitude out of (0, 1/4]
+    InvalidMagnitude {
+        gate: usize,
+        output_magnitude: u32,
+    },
+    //The gate parameter slots of a gate do not fit in usize (see Gate::param_slots)
+    ParamOutOfRange {
+        gate: usize,
+        param: usize,
+    },
+    //There are fewer gate parameters than used by the netlist
+    MissingGateParams {
+        expected: usize,
+        found: usize,
+    },
+    //The number of input ports does not match
+    InputPortCount {
+        expected: usize,
+        found: usize,
+    },
+    //The number of wires of an input port does not match
+    InputWidth {
+        port: String,
+        expected: usize,
+        found: usize,
+    },
+    //A gate reads a wire with another encoding than its inputs (see Netlist::validate)
+    InputEncoding {
+        gate: usize,
+        wire: WireId,
+        expected: u32,
+        found: u32,
+    },
+    //A gate cannot be evaluated on its ciphertexts
+    Gate {
+        gate: usize,
+        error: ObGateError,
+    },
+    //In a simulation, the phase of a gate is on a boundary of the sign bootstrapping (0 or 1/2)
+    AmbiguousPhase {
+        gate: usize,
+    },
+}
+
+impl std::fmt::Display for NetlistError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WireOutOfRange { wire, num_wires } => write!(
+                f,
+                "The wire {} is out of range (the netlist has {} wires)",
+                wire, num_wires
+            ),
+            Self::MultipleDrivers { wire } => {
+                write!(f, "The wire {} is driven more than once", wire)
+            }
+            Self::UndrivenWire { wire } => write!(
+                f,
+                "The wire {} is read before being driven by an input or a gate",
+                wire
+            ),
+            Self::InvalidArity { gate, op, found } => {
+                write!(
+                    f,
+                    "The gate {} ({:?}) cannot take {} inputs",
+                    gate, op, found
+                )
+            }
+            Self::InvalidMagnitude {
+                gate,
+                output_magnitude,
+            } => write!(
+                f,
+                "The output magnitude {:#x} of the gate {} is out of (0, 1/4]",
+                output_magnitude, gate
+            ),
+            Self::ParamOutOfRange { gate, param } => write!(
+                f,
+                "The gate parameter slots of the gate {} starting at {} are out of range",
+                gate, param
+            ),
+            Self::MissingGateParams { expected, found } => write!(
+                f,
+                "The netlist uses {} gate parameters, only {} were given",
+                expected, found
+            ),
+            Self::InputPortCount { expected, found } => write!(
+                f,
+                "The netlist has {} input ports, {} inputs were given",
+                expected, found
+            ),
+            Self::InputWidth {
+                port,
+                expected,
+                found,
+            } => write!(
+                f,
+                "The input port {} has {} wires, {} ciphertexts were given",
+                port, expected, found
+            ),
+            Self::InputEncoding {
+                gate,
+                wire,
+                expected,
+                found,
+            } => write!(
+                f,
+                "The gate {} expects inputs encoded as +-{:#x}, the wire {} is encoded as +-{:#x}",
+                gate, expected, wire, found
+            ),
+            Self::Gate { gate, error } => write!(f, "The gate {} failed: {}", gate, error),
+            Self::AmbiguousPhase { gate } => write!(
+                f,
+                "The phase of the gate {} is on a boundary of the sign bootstrapping",
+                gate
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NetlistError {}
+
+impl Netlist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Allocate a new wire
+    pub fn new_wire(&mut self) -> WireId {
+        self.num_wires += 1;
+        self.num_wires - 1
+    }
+
+    //Add an input port of width new wires, returns its wires
+    pub fn add_input_port(&mut self, name: &str, width: usize) -> Vec<WireId> {
+        let wires: Vec<WireId> = (0..width).map(|_| self.new_wire()).collect();
+        self.inputs.push(Port {
+            name: name.to_string(),
+            wires: wires.clone(),
+        });
+        wires
+    }
+
+    pub fn add_output_port(&mut self, name: &str, wires: Vec<WireId>) {
+        self.outputs.push(Port {
+            name: name.to_string(),
+            wires,
+        });
+    }
+
+    //Add a gate driving a new wire, returns the new wire
+    pub fn add_gate(&mut self, op: GateOp, inputs: Vec<WireId>, param: usize) -> WireId {
+        let output = self.new_wire();
+        self.gates.push(Gate {
+            op,
+            inputs,
+            output,
+            param,
+        });
+        output
+    }
+
+    /*The number of gate parameter slots used by the netlist (one past the last slot used)
+    Fails with ParamOutOfRange if the slots of a gate do not fit in usize
+    */
+    pub fn num_params(&self) -> Result<usize, NetlistError> {
+        self.gates
+            .iter()
+            .enumerate()
+            .try_fold(0, |num_params, (index, gate)| {
+                let slots = gate.param_slots().ok_or(NetlistError::ParamOutOfRange {
+                    gate: index,
+                    param: gate.param,
+                })?;
+                Ok(num_params.max(slots.end))
+            })
+    }
+
+    //The number of bootstrappings of an evaluation of the netlist
+    pub fn bootstrap_count(&self) -> usize {
+        self.gates
+            .iter()
+            .map(|gate| gate.op.bootstrap_count())
+            .sum()
+    }
+
+    //The depth of each wire of a valid netlist: 0 for the input ports, 1 + the depth of its deepest input for a gate output
+    pub fn wire_depths(&self) -> Vec<usize> {
+        let mut depths = vec![0; self.num_wires];
+        for gate in &self.gates {
+            depths[gate.output] = 1 + gate
+                .inputs
+                .iter()
+                .map(|&wire| depths[wire])
+                .max()
+                .unwrap_or(0);
+        }
+        depths
+    }
+
+    //The number of gates on the longest path of the netlist (the number of sequential gate layers)
+    pub fn depth(&self) -> usize {
+        self.wire_depths().into_iter().max().unwrap_or(0)
+    }
+
+    /*Check the structure of the netlist (see Netlist), so that it can be evaluated gate by gate,
+    the gate parameter slots (see num_params),
+    and the encoding of the gate inputs: +-nop_input_magnitude(fan-in) for the threshold gates, +-1/8 for the others
+    (the input ports are +-1/8, the output of a gate is +-GateOp::output_magnitude)
+    */
+    pub fn validate(&self) -> Result<(), NetlistError> {
+        self.num_params()?;
+        //The output magnitude of the driver of each wire, None if not driven yet
+        let mut magnitudes: Vec<Option<u32>> = vec![None; self.num_wires];
+        let drive = |magnitudes: &mut [Option<u32>], wire: WireId, magnitude: u32| match magnitudes
+            .get_mut(wire)
+        {
+            None => Err(NetlistError::WireOutOfRange {
+                wire,
+                num_wires: self.num_wires,
+            }),
+            Some(Some(_)) => Err(NetlistError::MultipleDrivers { wire }),
+            Some(driver) => {
+                *driver = Some(magnitude);
+                Ok(())
+            }
+        };
+        let read = |magnitudes: &[Option<u32>], wire: WireId| match magnitudes.get(wire) {
+            None => Err(NetlistError::WireOutOfRange {
+                wire,
+                num_wires: self.num_wires,
+            }),
+            Some(None) => Err(NetlistError::UndrivenWire { wire }),
+            Some(Some(magnitude)) => Ok(*magnitude),
+        };
+
+        for port in &self.inputs {
+            for &wire in &port.wires {
+                drive(&mut magnitudes, wire, EIGHTH)?;
+            }
+        }
+
+        for (index, gate) in self.gates.iter().enumerate() {
+            let found = gate.inputs.len();
+            if found == 0 || gate.op.arity().is_some_and(|arity| arity != found) {
+                return Err(NetlistError::InvalidArity {
+                    gate: index,
+                    op: gate.op,
+                    found,
+                });
+            }
+            let output_magnitude = gate.op.output_magnitude();
+            if output_magnitude == 0 || output_magnitude > 2 * EIGHTH {
+                return Err(NetlistError::InvalidMagnitude {
+                    gate: index,
+                    output_magnitude,
+                });
+            }
+            let expected = match gate.op {
+                GateOp::Threshold { .. } => nop_input_magnitude(found)
+                    .map_err(|error| NetlistError::Gate { gate: index, error })?,
+                _ => EIGHTH,
+            };
+            for &wire in &gate.inputs {
+                let magnitude = read(&magnitudes, wire)?;
+                if magnitude != expected {
+                    return Err(NetlistError::InputEncoding {
+                        gate: index,
+                        wire,
+                        expected,
+                        found: magnitude,
+                    });
+                }
+            }
+            drive(&mut magnitudes, gate.output, output_magnitude)?;
+        }
+
+        for port in &self.outputs {
+            for &wire in &port.wires {
+                read(&magnitudes, wire)?;
+            }
+        }
+        Ok(())
+    }
+
+    /*Evaluate the netlist on the encrypted inputs (one Vec per input port, in the order of its wires),
+    with the encrypted gate parameters of the client (see GateParams, the seeded C_gate_params must be decompressed)
+    Returns the encrypted outputs, one Vec per output port
+    */
+    pub fn execute<C: Container<Element = u32>>(
+        &self,
+        evaluator: &mut ObliviousEvaluator,
+        inputs: &[Vec<Ciphertext>],
+        C_gate_params: &[TgswCiphertext<C>],
+        cd_gate_params: &[Ciphertext],
+    ) -> Result<Vec<Vec<Ciphertext>>, NetlistError> {
+        let num_params = self.num_params()?;
+        let found = C_gate_params.len().min(cd_gate_params.len());
+        if found < num_params {
+            return Err(NetlistError::MissingGateParams {
+                expected: num_params,
+                found,
+            });
+        }
+
+        //num_params() guarantees that the slots of every gate are below the number of parameters
+        self.evaluate(inputs, |index, gate, gate_inputs| {
+            let p = gate.param;
+            let mut ct_res = gate_inputs[0].clone();
+            match gate.op {
+                GateOp::TwoOp => evaluator.ob_gate_2op_assign(
+                    &mut ct_res,
+                    gate_inputs[1],
+                    &C_gate_params[p],
+                    &cd_gate_params[p],
+                ),
+                GateOp::OneOp => evaluator.ob_gate_1op_tgsw_assign(
+                    &mut ct_res,
+                    &C_gate_params[p],
+                    &cd_gate_params[p],
+                ),
+                GateOp::Threshold { output_magnitude } => evaluator.ob_gate_nop_assign(
+                    &mut ct_res,
+                    &gate_inputs[1..],
+                    &C_gate_params[p],
+                    &cd_gate_params[p],
+                    output_magnitude,
+                ),
+                GateOp::Mux => evaluator.ob_mux_assign(
+                    &mut ct_res,
+                    gate_inputs[1],
+                    gate_inputs[2],
+                    &[0, 1].map(|j| [0, 1, 2].map(|i| &C_gate_params[p + 3 * j + i])),
+                    &[&cd_gate_params[p], &cd_gate_params[p + 3]],
+                ),
+                GateOp::Reencode { output_magnitude } => {
+                    evaluator.ob_reencode_assign(&mut ct_res, output_magnitude)
+                }
+            }
+            .map(|_| ct_res)
+            .map_err(|error| NetlistError::Gate { gate: index, error })
+        })
+    }
+
+    /*Evaluate the netlist on the plaintexts, with the plaintext (A, d) of each gate parameter slot
+    (as given to new_encrypt_gate_params): the phases of the encrypted evaluation without noise.
+    A gate whose phase lands on a boundary of the sign bootstrapping (0 or 1/2) is reported as an error
+    (NetlistError::AmbiguousPhase), since its encrypted output would be random.
+    */
+    pub fn simulate(
+        &self,
+        inputs: &[Vec<bool>],
+        gate_plaintexts: &[(u32, u32)],
+    ) -> Result<Vec<Vec<bool>>, NetlistError> {
+        let num_params = self.num_params()?;
+        if gate_plaintexts.len() < num_params {
+            return Err(NetlistError::MissingGateParams {
+                expected: num_params,
+                found: gate_plaintexts.len(),
+            });
+        }
+
+        let inputs: Vec<Vec<u32>> = inputs
+            .iter()
+            .map(|port| port.iter().map(|&m| encode(m, EIGHTH)).collect())
+            .collect();
+        //num_params() guarantees that the slots of every gate are below gate_plaintexts.len()
+        let outputs = self.evaluate(&inputs, |index, gate, gate_inputs| {
+            let p = gate.param;
+            let ambiguous = NetlistError::AmbiguousPhase { gate: index };
+            match gate.op {
+                GateOp::TwoOp | GateOp::OneOp | GateOp::Threshold { .. } => {
+                    //A*(sum of the inputs)+d
+                    let (A, d) = gate_plaintexts[p];
+                    let sum = gate_inputs
+                        .iter()
+                        .fold(0u32, |sum, &&phase| sum.wrapping_add(phase));
+                    sign(
+                        A.wrapping_mul(sum).wrapping_add(d),
+                        gate.op.output_magnitude(),
+                    )
+                    .ok_or(ambiguous)
+                }
+                GateOp::Mux => {
+                    let branch = |j: usize| {
+                        let d = gate_plaintexts[p + 3 * j].1;
+                        let phase = (0..3).fold(d, |phase, i| {
+                            let A = gate_plaintexts[p + 3 * j + i].0;
+                            phase.wrapping_add(A.wrapping_mul(*gate_inputs[i]))
+                        });
+                        sign(phase, EIGHTH)
+                    };
+                    //out = b_0 + b_1 + 1/8
+                    match (branch(0), branch(1)) {
+                        (Some(b_0), Some(b_1)) => Ok(b_0.wrapping_add(b_1).wrapping_add(EIGHTH)),
+                        _ => Err(ambiguous),
+                    }
+                }
+                GateOp::Reencode { output_magnitude } => {
+                    sign(*gate_inputs[0], output_magnitude).ok_or(ambiguous)
+                }
+            }
+        })?;
+        Ok(outputs
+            .into_iter()
+            .map(|port| {
+                port.into_iter()
+                    .map(|phase| phase != 0 && phase < 1 << 31)
+                    .collect()
+            })
+            .collect())
+    }
+
+    //Evaluate the gates in order with evaluate_gate(gate index, gate, gate inputs), after checking the netlist and the inputs
+    fn evaluate<T: Clone>(
+        &self,
+        inputs: &[Vec<T>],
+        mut evaluate_gate: impl FnMut(usize, &Gate, &[&T]) -> Result<T, NetlistError>,
+    ) -> Result<Vec<Vec<T>>, NetlistError> {
+        self.validate()?;
+        if inputs.len() != self.inputs.len() {
+            return Err(NetlistError::InputPortCount {
+                expected: self.inputs.len(),
+                found: inputs.len(),
+            });
+        }
+
+        let mut values: Vec<Option<T>> = vec![None; self.num_wires];
+        for (port, input) in self.inputs.iter().zip(inputs) {
+            if input.len() != port.wires.len() {
+                return Err(NetlistError::InputWidth {
+                    port: port.name.clone(),
+                    expected: port.wires.len(),
+                    found: input.len(),
+                });
+            }
+            for (&wire, value) in port.wires.iter().zip(input) {
+                values[wire] = Some(value.clone());
+            }
+        }
+
+        //validate() guarantees that every wire read is driven
+        for (index, gate) in self.gates.iter().enumerate() {
+            let gate_inputs: Vec<&T> = gate
+                .inputs
+                .iter()
+                .map(|&wire| values[wire].as_ref().unwrap())
+                .collect();
+            let output = evaluate_gate(index, gate, &gate_inputs)?;
+            values[gate.output] = Some(output);
+        }
+
+        Ok(self
+            .outputs
+            .iter()
+            .map(|port| {
+                port.wires
+                    .iter()
+                    .map(|&wire| values[wire].clone().unwrap())
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+/*A netlist with the plaintext (A, d) of each of its gate parameter slots, as built by the client (e.g. by an importer)
+The netlist is sent to the server as is, the gate_plaintexts are secret and only sent encrypted (see new_encrypt_gate_params)
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Circuit {
+    pub netlist: Netlist,
+    pub gate_plaintexts: Vec<(u32, u32)>,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Add an obfuscated 2-input gate computing kind, returns its output wire
+    pub fn add_gate_2op(&mut self, kind: GateKind, inputs: [WireId; 2]) -> WireId {
+        self.add_gate(GateOp::TwoOp, inputs.to_vec(), kind.plaintexts())
+    }
+
+    //Add an obfuscated 1-input gate computing kind, returns its output wire
+    pub fn add_gate_1op(&mut self, kind: UnaryGateKind, input: WireId) -> WireId {
+        self.add_gate(GateOp::OneOp, vec![input], kind.plaintexts())
+    }
+
+    /*Add the gates computing any 2-input function of the inputs (see TruthTable), returns the output wire:
+    - a symmetric function: one 2-input gate
+    - a function of a single input (m1, NOT m2, ...): one 1-input gate on that input
+    - otherwise f(m1, m2) = g(m1, NOT m2) with g symmetric (e.g. m1 AND NOT m2): a NOT gate on m2, then a 2-input gate
+    */
+    pub fn add_truth_table_gate(
+        &mut self,
+        inputs: [WireId; 2],
+        truth_table: &TruthTable,
+    ) -> WireId {
+        if let Ok(kind) = GateKind::from_truth_table(truth_table) {
+            return self.add_gate_2op(kind, inputs);
+        }
+        let t = truth_table;
+        if t[0] == t[1] && t[2] == t[3] {
+            return self.add_gate_1op(UnaryGateKind::from_truth_table([t[0], t[2]]), inputs[0]);
+        }
+        if t[0] == t[2] && t[1] == t[3] {
+            return self.add_gate_1op(UnaryGateKind::from_truth_table([t[0], t[1]]), inputs[1]);
+        }
+        let kind = GateKind::from_truth_table(&[t[1], t[0], t[3], t[2]])
+            .expect("f(m1, NOT m2) is symmetric for the functions of both inputs");
+        let not_m2 = self.add_gate_1op(UnaryGateKind::Not, inputs[1]);
+        self.add_gate_2op(kind, [inputs[0], not_m2])
+    }
+
+    //Add a gate with a single parameter slot, appended to gate_plaintexts
+    fn add_gate(&mut self, op: GateOp, inputs: Vec<WireId>, plaintexts: (u32, u32)) -> WireId {
+        self.gate_plaintexts.push(plaintexts);
+        self.netlist
+            .add_gate(op, inputs, self.gate_plaintexts.len() - 1)
+    }
+
+    //Check the netlist (see Netlist::validate) and that gate_plaintexts has every gate parameter slot it uses
+    pub fn validate(&self) -> Result<(), NetlistError> {
+        self.netlist.validate()?;
+        let num_params = self.netlist.num_params()?;
+        if self.gate_plaintexts.len() < num_params {
+            return Err(NetlistError::MissingGateParams {
+                expected: num_params,
+                found: self.gate_plaintexts.len(),
+            });
+        }
+        Ok(())
+    }
+
+    //See Netlist::simulate
+    pub fn simulate(&self, inputs: &[Vec<bool>]) -> Result<Vec<Vec<bool>>, NetlistError> {
+        self.netlist.simulate(inputs, &self.gate_plaintexts)
+    }
+
+    //Encrypt the gate parameters of the circuit, ready to be sent to the server with the netlist
+    pub fn new_encrypt_gate_params(
+        &self,
+        tgsw_params: &TgswParams,
+        client_key: &ClientKey,
+    ) -> GateParams {
+        new_encrypt_gate_params(&self.gate_plaintexts, tgsw_params, client_key)
+    }
+}
+
+//The noiseless phase of the boolean m encoded as +-magnitude
+fn encode(m: bool, magnitude: u32) -> u32 {
+    if m {
+        magnitude
+    } else {
+        magnitude.wrapping_neg()
+    }
+}
+
+//The noiseless sign bootstrapping: +magnitude if the phase is in (0, 1/2), -magnitude if in (1/2, 1), None on a boundary
+fn sign(phase: u32, magnitude: u32) -> Option<u32> {
+    match phase {
+        0 | 0x8000_0000 => None,
+        _ => Some(encode(phase < 1 << 31, magnitude)),
+    }
+}
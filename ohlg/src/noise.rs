@@ -0,0 +1,187 @@
+//Client-side helpers to inspect the phase and noise of boolean ciphertexts (for debugging the obfuscated gates)
+//and a noise model of the external product, to choose the decomposition parameters
+use tfhe::boolean::prelude::{BooleanParameters, Ciphertext, ClientKey, EncryptionKeyChoice};
+use tfhe::core_crypto::prelude::*;
+
+use crate::gate_core::nop_input_magnitude;
+use crate::tgsw::{DecompositionKind, TgswParams};
+
+//The boolean messages and the gate parameters (see oblivious_gates) are encoded on the 1/8 grid of the u32 torus
+pub const EIGHTH: u32 = 1 << (32 - 3);
+
+/*Return the raw torus phase b - <a, s> of a boolean ciphertext
+true and false are encoded as +1/8 and -1/8, so an encrypted bit has a phase close to 1<<29 or 7<<29.
+Trivial ciphertexts have no noise, their phase is the exact encoding.
+*/
+pub fn decrypt_phase(ct: &Ciphertext, client_key: &ClientKey) -> u32 {
+    match ct {
+        Ciphertext::Trivial(true) => EIGHTH,
+        Ciphertext::Trivial(false) => EIGHTH.wrapping_neg(),
+        Ciphertext::Encrypted(ct_lwe) => {
+            let (lwe_sk, _) = client_key.encryption_key_and_noise();
+            decrypt_lwe_ciphertext(&lwe_sk, ct_lwe).0
+        }
+    }
+}
+
+//Return the phase rounded to the closest multiple of 1/8, as a number of eighths in 0..8
+pub fn decrypt_eighths(ct: &Ciphertext, client_key: &ClientKey) -> u32 {
+    decrypt_phase(ct, client_key).wrapping_add(EIGHTH / 2) / EIGHTH
+}
+
+/*Return the signed distance between the phase and the closest multiple of 1/8, in units of 2^-32
+An obfuscated gate output is decrypted correctly as long as |noise| < 1/16 (1<<28) before the bootstrapping
+*/
+pub fn noise(ct: &Ciphertext, client_key: &ClientKey) -> i32 {
+    let phase = decrypt_phase(ct, client_key);
+    phase.wrapping_sub(decrypt_eighths(ct, client_key).wrapping_mul(EIGHTH)) as i32
+}
+
+/*Noise model of the external product TGSW(M) x LWE(m) of the obfuscated gates (see DecompositionKind for the analysis)
+All the values are on the torus [0, 1), i.e. in units of q = 2^32
+- rounding: each of the n+1 coefficients is rounded to a multiple of 1/B^l, which drops log2(q/B^l) low bits.
+  The rounding errors are multiplied by M*(-s, 1), hence a variance of M^2 * (1 + n/2) * e_round^2 (binary key)
+- digits: the l(n+1) digits weight the noise of the TGSW rows, hence a variance of l(n+1) * E[d^2] * s^2
+The rounding part grows with the message M and vanishes for an exact decomposition (B^l = q), the digit part grows with
+l and B, so a smaller l trades a smaller TGSW ciphertext for a larger rounding error.
+*/
+#[derive(Clone, Copy, Debug)]
+pub struct ExtProductNoise {
+    //The number of low bits dropped by the rounding, log2(q/B^l)
+    pub dropped_bits: f64,
+    //The largest rounding error of a single coefficient, 1/(2B^l)
+    pub rounding_error_bound: f64,
+    pub rounding_variance: f64,
+    pub digit_variance: f64,
+    //The noise variance of a fresh encryption (the TGSW rows and the additive gate parameter d)
+    pub fresh_variance: f64,
+    pub message_bound: u32,
+}
+
+impl ExtProductNoise {
+    //The variance of the noise added by the external product
+    pub fn variance(&self) -> f64 {
+        self.rounding_variance + self.digit_variance
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /*The variance of the obfuscated gate output A(c1+c2)+d before the bootstrapping, for A <= message_bound
+    input_variance is the noise variance of each input c1, c2 (e.g. fresh_noise_variance for fresh encryptions,
+    larger for the output of a previous gate)
+    */
+    pub fn gate_variance(&self, input_variance: f64) -> f64 {
+        let m = self.message_bound as f64;
+        m * m * 2.0 * input_variance + self.variance() + self.fresh_variance
+    }
+
+    //The probability that the gate output is decrypted incorrectly, i.e. that its noise exceeds 1/16 (see noise())
+    pub fn gate_failure_probability(&self, input_variance: f64) -> f64 {
+        erfc(1.0 / 16.0 / (2.0 * self.gate_variance(input_variance)).sqrt())
+    }
+
+    //The variance of the n-input gate output A(c1+...+cn)+d before the bootstrapping (see ob_gate_nop)
+    pub fn gate_variance_nop(&self, fan_in: usize, input_variance: f64) -> f64 {
+        let m = self.message_bound as f64;
+        m * m * fan_in as f64 * input_variance + self.variance() + self.fresh_variance
+    }
+
+    /*The probability that the n-input gate output is on the wrong side of the sign bootstrapping, i.e. that its noise
+    exceeds the input magnitude nop_input_magnitude(fan_in) (the distance between the closest level and the boundary)
+    The noise of the modulus switching of the bootstrapping is not included, it matters for large fan-ins
+    (the margin halves each time the fan-in doubles). The fan-ins that the gates reject (0, > 2^30) always fail
+    */
+    pub fn gate_failure_probability_nop(&self, fan_in: usize, input_variance: f64) -> f64 {
+        let Ok(magnitude) = nop_input_magnitude(fan_in) else {
+            return 1.0;
+        };
+        let margin = magnitude as f64 / 2f64.powi(u32::BITS as i32);
+        erfc(margin / (2.0 * self.gate_variance_nop(fan_in, input_variance)).sqrt())
+    }
+
+    /*The variance of each branch of the obfuscated multiplexer before its bootstrapping (see ob_mux), for message_bound 1:
+    the select and a data input weighted by +-1, and the third input weighted by 0 (only the digit noise remains)
+    */
+    pub fn mux_branch_variance(&self, input_variance: f64) -> f64 {
+        2.0 * input_variance + 2.0 * self.variance() + self.digit_variance + self.fresh_variance
+    }
+
+    /*The probability that the multiplexer output is incorrect, i.e. that either branch is on the wrong side of its
+    sign bootstrapping (the levels of a branch are +-1/8 and +-3/8, 1/8 away from the boundaries)
+    */
+    pub fn mux_failure_probability(&self, input_variance: f64) -> f64 {
+        2.0 * erfc(1.0 / 8.0 / (2.0 * self.mux_branch_variance(input_variance)).sqrt())
+    }
+}
+
+//The noise model of the external product TGSW(M) x LWE(m) for the messages M <= message_bound
+pub fn ext_product_noise(tgsw_params: &TgswParams, message_bound: u32) -> ExtProductNoise {
+    let n = tgsw_params.lwe_dimension().0 as f64;
+    let l = tgsw_params.decomp_level as f64;
+    let B = tgsw_params.decomp_base as f64;
+    let m = message_bound as f64;
+
+    //Rounding to a multiple of g = q/B^l: the error is uniform over the g integers of [-g/2, g/2)
+    let g = 2f64.powi(u32::BITS as i32) / B.powf(l);
+    let e_round_variance = (g * g - 1.0).max(0.0) / 12.0 / 2f64.powi(2 * u32::BITS as i32);
+
+    //Mean square of a digit uniform in [0, B) or [-B/2, B/2)
+    let digit_mean_square = match tgsw_params.decomp_kind {
+        DecompositionKind::Unsigned => (B - 1.0) * (2.0 * B - 1.0) / 6.0,
+        DecompositionKind::Balanced => (B * B + 2.0) / 12.0,
+    };
+
+    let fresh_variance = fresh_noise_variance(&tgsw_params.tfhe_params);
+
+    ExtProductNoise {
+        dropped_bits: tgsw_params.dropped_bits(),
+        rounding_error_bound: 0.5 / B.powf(l),
+        rounding_variance: m * m * (1.0 + n / 2.0) * e_round_variance,
+        digit_variance: l * (n + 1.0) * digit_mean_square * fresh_variance,
+        fresh_variance,
+        message_bound,
+    }
+}
+
+//The noise variance (on the torus) of a fresh boolean encryption, under the key chosen by the parameters
+pub fn fresh_noise_variance(tfhe_params: &BooleanParameters) -> f64 {
+    let noise_distribution = match tfhe_params.encryption_key_choice {
+        EncryptionKeyChoice::Big => tfhe_params.glwe_noise_distribution,
+        EncryptionKeyChoice::Small => tfhe_params.lwe_noise_distribution,
+    };
+    match noise_distribution {
+        DynamicDistribution::Gaussian(gaussian) => gaussian.std * gaussian.std,
+        DynamicDistribution::TUniform(t_uniform) => {
+            t_uniform.variance(2f64.powi(u32::BITS as i32)).0
+        }
+    }
+}
+
+// Complementary error function, with a relative error below 1.2e-7 (Numerical Recipes, erfcc)
+// The relative error matters here, the failure probabilities are tiny
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    //Coefficients of the polynomial in t, constant term first
+    const COEFFS: [f64; 10] = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ];
+    let poly = COEFFS.iter().rev().fold(0.0, |acc, &c| acc * t + c);
+    let r = t * (-z * z + poly).exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
@@ -0,0 +1,138 @@
+//A reusable evaluator of the obfuscated gates, owning the server key, the parameters and the scratch buffers
+use std::borrow::Borrow;
+use std::sync::Arc;
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::commons::traits::Container;
+
+use crate::gate_core::{GateScratch, ObGateError};
+use crate::tgsw::{TgswCiphertext, TgswParams};
+
+/*An evaluator of the obfuscated gates (see oblivious_gates) for one server key and parameter set
+It owns the bootstrapping buffers and the scratch space of the external product, so the gates work in place (*_assign,
+the first input is replaced with the output) without allocating. The server key is shared: cloning an evaluator
+is cheap and gives it fresh scratch buffers, e.g. one clone per worker thread.
+*/
+pub struct ObliviousEvaluator {
+    server_key: Arc<ServerKey>,
+    tgsw_params: TgswParams,
+    scratch: GateScratch,
+}
+
+impl Clone for ObliviousEvaluator {
+    fn clone(&self) -> Self {
+        Self {
+            server_key: Arc::clone(&self.server_key),
+            tgsw_params: self.tgsw_params,
+            scratch: GateScratch::new(),
+        }
+    }
+}
+
+impl ObliviousEvaluator {
+    pub fn new(server_key: impl Into<Arc<ServerKey>>, tgsw_params: TgswParams) -> Self {
+        Self {
+            server_key: server_key.into(),
+            tgsw_params,
+            scratch: GateScratch::new(),
+        }
+    }
+
+    pub fn server_key(&self) -> &ServerKey {
+        &self.server_key
+    }
+
+    pub fn tgsw_params(&self) -> &TgswParams {
+        &self.tgsw_params
+    }
+
+    //ct_1 = A(ct_1+ct_2)+d, bootstrapped (see oblivious_gates::ob_gate_2op)
+    pub fn ob_gate_2op_assign<C: Container<Element = u32>>(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        ct_2: &Ciphertext,
+        A: &TgswCiphertext<C>,
+        ct_d: &Ciphertext,
+    ) -> Result<(), ObGateError> {
+        self.scratch
+            .ob_gate_2op_assign(ct_1, ct_2, A, ct_d, &self.tgsw_params, &self.server_key)
+    }
+
+    //ct_1 = ct_1+ct_arg_1, bootstrapped (see oblivious_gates::ob_gate_1op)
+    pub fn ob_gate_1op_assign(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        ct_arg_1: &Ciphertext,
+    ) -> Result<(), ObGateError> {
+        self.scratch
+            .ob_gate_1op_assign(ct_1, ct_arg_1, &self.server_key)
+    }
+
+    //ct_1 = A*ct_1+d, bootstrapped (see oblivious_gates::ob_gate_1op_tgsw)
+    pub fn ob_gate_1op_tgsw_assign<C: Container<Element = u32>>(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        A: &TgswCiphertext<C>,
+        ct_d: &Ciphertext,
+    ) -> Result<(), ObGateError> {
+        self.scratch
+            .ob_gate_1op_tgsw_assign(ct_1, A, ct_d, &self.tgsw_params, &self.server_key)
+    }
+
+    /*ct_1 = A(ct_1+others[0]+...)+d, bootstrapped to +-output_magnitude (see oblivious_gates::ob_gate_nop_with_output)
+    The inputs are encoded as +-nop_input_magnitude(1 + others.len())
+    */
+    pub fn ob_gate_nop_assign<C: Container<Element = u32>, B: Borrow<Ciphertext>>(
+        &mut self,
+        ct_1: &mut Ciphertext,
+        others: &[B],
+        A: &TgswCiphertext<C>,
+        ct_d: &Ciphertext,
+        output_magnitude: u32,
+    ) -> Result<(), ObGateError> {
+        self.scratch.ob_gate_nop_assign(
+            ct_1,
+            others,
+            A,
+            ct_d,
+            &self.tgsw_params,
+            &self.server_key,
+            output_magnitude,
+        )
+    }
+
+    //ct = ct re-encoded as +-output_magnitude (see oblivious_gates::ob_reencode)
+    pub fn ob_reencode_assign(
+        &mut self,
+        ct: &mut Ciphertext,
+        output_magnitude: u32,
+    ) -> Result<(), ObGateError> {
+        self.scratch
+            .ob_reencode_assign(ct, output_magnitude, &self.tgsw_params, &self.server_key)
+    }
+
+    /*ct_0 = mux of (ct_0, ct_1, ct_2) (see oblivious_gates::ob_mux)
+    The parameters can be given by value or by reference (e.g. gathered from a GateParams)
+    */
+    pub fn ob_mux_assign<
+        C: Container<Element = u32>,
+        T: Borrow<TgswCiphertext<C>>,
+        D: Borrow<Ciphertext>,
+    >(
+        &mut self,
+        ct_0: &mut Ciphertext,
+        ct_1: &Ciphertext,
+        ct_2: &Ciphertext,
+        A: &[[T; 3]; 2],
+        ct_d: &[D; 2],
+    ) -> Result<(), ObGateError> {
+        self.scratch.ob_mux_assign(
+            ct_0,
+            ct_1,
+            ct_2,
+            A,
+            ct_d,
+            &self.tgsw_params,
+            &self.server_key,
+        )
+    }
+}
@@ -1,89 +1,174 @@
 use std::cell::RefCell;
-use tfhe::boolean::engine::BooleanEngine;
 use tfhe::boolean::prelude::*;
-use tfhe::core_crypto::algorithms::lwe_linear_algebra::*;
-use tfhe::core_crypto::entities::lwe_ciphertext::LweCiphertext;
+use tfhe::core_crypto::commons::traits::Container;
 
+use crate::gate_core::GateScratch;
+pub use crate::gate_core::{nop_input_magnitude, ObGateError};
+use crate::noise::EIGHTH;
 use crate::tgsw::{TgswCiphertext, TgswParams};
 
+//The scratch buffers of the gate functions below, see oblivious_evaluator::ObliviousEvaluator to own them instead
 thread_local! {
-    static OB_BOOLEAN_ENGINE: RefCell<BooleanEngine> = RefCell::new(BooleanEngine::new());
+    static OB_GATE_SCRATCH: RefCell<GateScratch> = RefCell::new(GateScratch::new());
 }
 
 /*
 An obfuscated logic function that performs A(c1+c2)+d, where
-A:TGSW encryption of a scalar value in Z/PZ where P = B^L
-c1,c2: The TLWE ciphertexts to be operated on
+A:TGSW encryption of a scalar value in Z/PZ where P = B^L (owned, or a view e.g. on a memory-mapped file)
+c1,c2: The TLWE ciphertexts to be operated on (a trivial input is encoded as a noiseless LWE)
 d: TLWE ciphertext that defines the logic function along with A
 */
-pub fn ob_gate_2op(
+pub fn ob_gate_2op<C: Container<Element = u32>>(
     ct_1: &Ciphertext,
     ct_2: &Ciphertext,
-    A: &TgswCiphertext,
+    A: &TgswCiphertext<C>,
     ct_d: &Ciphertext,
     tgsw_params: &TgswParams,
     server_key: &ServerKey,
-) -> Ciphertext {
-    let ct_1_lwe = match ct_1 {
-        Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-        _ => panic!("Expected encrypted ciphertext"),
-    };
-
-    let ct_2_lwe = match ct_2 {
-        Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-        _ => panic!("Expected encrypted ciphertext"),
-    };
-
-    let ct_d_lwe = match ct_d {
-        Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-        _ => panic!("Expected encrypted ciphertext"),
-    };
-
-    let mut buffer_lwe =
-        LweCiphertext::new(0u32, ct_1_lwe.lwe_size(), ct_1_lwe.ciphertext_modulus());
-
-    lwe_ciphertext_add(&mut buffer_lwe, &ct_1_lwe, &ct_2_lwe);
-
-    let mut buffer_ct = Ciphertext::Encrypted(buffer_lwe);
+) -> Result<Ciphertext, ObGateError> {
+    //The ciphertexts are encrypted under the key selected by encryption_key_choice: the small LWE key, or the big key
+    //of dimension glwe_dimension*polynomial_size with the KS->PBS order (e.g. DEFAULT_PARAMETERS_KS_PBS)
+    let mut ct_res = ct_1.clone();
+    OB_GATE_SCRATCH.with(|scratch| {
+        scratch
+            .borrow_mut()
+            .ob_gate_2op_assign(&mut ct_res, ct_2, A, ct_d, tgsw_params, server_key)
+    })?;
+    Ok(ct_res)
+}
 
-    buffer_ct = A.ext_product(&buffer_ct, &tgsw_params /*server_key*/);
+/*An n-input obfuscated logic function that performs A(c1+...+cn)+d, e.g. a threshold gate (at least k of n inputs true)
+or its negation, in a single bootstrapping (see gate_params::threshold_gate_plaintexts for the parameters)
+inputs: the TLWE ciphertexts, encoded as +-nop_input_magnitude(n) (+-1/8, i.e. regular boolean ciphertexts, for n <= 2)
+(a trivial input is encoded as a noiseless LWE of +-nop_input_magnitude(n))
+A, d: as in ob_gate_2op
+The output is a regular boolean ciphertext (+-1/8), use ob_gate_nop_with_output to feed another n-input gate.
+The inputs get closer to each other as n grows, so the noise margin is nop_input_magnitude(n) instead of 1/8
+(see noise::ExtProductNoise::gate_failure_probability_nop)
+*/
+pub fn ob_gate_nop<C: Container<Element = u32>>(
+    inputs: &[Ciphertext],
+    A: &TgswCiphertext<C>,
+    ct_d: &Ciphertext,
+    tgsw_params: &TgswParams,
+    server_key: &ServerKey,
+) -> Result<Ciphertext, ObGateError> {
+    ob_gate_nop_with_output(inputs, A, ct_d, tgsw_params, server_key, EIGHTH)
+}
 
-    buffer_lwe = match buffer_ct {
-        Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-        _ => panic!("Expected encrypted ciphertext"),
-    };
+//Same as ob_gate_nop, with the output encoded as +-output_magnitude, e.g. nop_input_magnitude(m) for an m-input gate
+pub fn ob_gate_nop_with_output<C: Container<Element = u32>>(
+    inputs: &[Ciphertext],
+    A: &TgswCiphertext<C>,
+    ct_d: &Ciphertext,
+    tgsw_params: &TgswParams,
+    server_key: &ServerKey,
+    output_magnitude: u32,
+) -> Result<Ciphertext, ObGateError> {
+    let (ct_1, others) = inputs.split_first().ok_or(ObGateError::NoInputs)?;
+    let mut ct_res = ct_1.clone();
+    OB_GATE_SCRATCH.with(|scratch| {
+        scratch.borrow_mut().ob_gate_nop_assign(
+            &mut ct_res,
+            others,
+            A,
+            ct_d,
+            tgsw_params,
+            server_key,
+            output_magnitude,
+        )
+    })?;
+    Ok(ct_res)
+}
 
-    lwe_ciphertext_add_assign(&mut buffer_lwe, &ct_d_lwe);
+/*An obfuscated multiplexer: out = c_s ? c_t : c_e, where any of the three inputs can be the select c_s
+A, d: two branches b_j = A_j0*c0 + A_j1*c1 + A_j2*c2 + d_j are bootstrapped, one computing AND(c_s, c_t) and the other
+AND(NOT c_s, c_e) (A = 1 for the data input, +1 or -1 for the select, 0 for the third input, d = -1/8),
+so at most one branch is true and out = b_0 + b_1 + 1/8 needs no further bootstrapping
+(see gate_params::mux_gate_plaintexts for the parameters)
+The server only sees 6 TGSW and 2 TLWE parameters, so which input is the select and which data input it selects
+when true stay hidden. Costs 6 external products and 2 bootstrappings.
+The output noise is the sum of the noises of the two bootstrappings.
+*/
+pub fn ob_mux<C: Container<Element = u32>>(
+    inputs: &[Ciphertext; 3],
+    A: &[[TgswCiphertext<C>; 3]; 2],
+    ct_d: &[Ciphertext; 2],
+    tgsw_params: &TgswParams,
+    server_key: &ServerKey,
+) -> Result<Ciphertext, ObGateError> {
+    let mut ct_res = inputs[0].clone();
+    OB_GATE_SCRATCH.with(|scratch| {
+        scratch.borrow_mut().ob_mux_assign(
+            &mut ct_res,
+            &inputs[1],
+            &inputs[2],
+            A,
+            ct_d,
+            tgsw_params,
+            server_key,
+        )
+    })?;
+    Ok(ct_res)
+}
 
-    OB_BOOLEAN_ENGINE.with(|engine_cell| {
-        let eng = &mut engine_cell.borrow_mut() as &mut BooleanEngine;
-        let bootstrapper = &mut eng.bootstrapper;
-        bootstrapper.apply_bootstrapping_pattern(buffer_lwe, &server_key)
-    })
+/*Re-encode a regular boolean ciphertext (+-1/8) as +-output_magnitude with one bootstrapping,
+e.g. to use the output of ob_gate_2op as an input of an n-input gate (see nop_input_magnitude)
+*/
+pub fn ob_reencode(
+    ct: &Ciphertext,
+    output_magnitude: u32,
+    tgsw_params: &TgswParams,
+    server_key: &ServerKey,
+) -> Result<Ciphertext, ObGateError> {
+    let mut ct_res = ct.clone();
+    OB_GATE_SCRATCH.with(|scratch| {
+        scratch.borrow_mut().ob_reencode_assign(
+            &mut ct_res,
+            output_magnitude,
+            tgsw_params,
+            server_key,
+        )
+    })?;
+    Ok(ct_res)
 }
 
 /*A 1-operan obfuscated logic gate (Buffer or NOT)
 Performs the operation ct_1 + ct_arg_1, where ct_1 is the input and ct_arg_1 is either 0(buffer) or 1/2(NOT)
+ct_arg_1 must be encrypted by the client, a trivial input ct_1 is encoded as a noiseless LWE of its size
+See ob_gate_1op_tgsw to also hide the constant gates
 */
-pub fn ob_gate_1op(ct_1: &Ciphertext, ct_arg_1: &Ciphertext, server_key: &ServerKey) -> Ciphertext {
-    let ct_1_lwe = match ct_1 {
-        Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-        _ => panic!("Expected encrypted ciphertext"),
-    };
-
-    let ct_arg_1_lwe = match ct_arg_1 {
-        Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-        _ => panic!("Expected encrypted ciphertext"),
-    };
-
-    let mut buffer_lwe =
-        LweCiphertext::new(0u32, ct_1_lwe.lwe_size(), ct_1_lwe.ciphertext_modulus());
-
-    lwe_ciphertext_add(&mut buffer_lwe, &ct_1_lwe, &ct_arg_1_lwe);
+pub fn ob_gate_1op(
+    ct_1: &Ciphertext,
+    ct_arg_1: &Ciphertext,
+    server_key: &ServerKey,
+) -> Result<Ciphertext, ObGateError> {
+    let mut ct_res = ct_1.clone();
+    OB_GATE_SCRATCH.with(|scratch| {
+        scratch
+            .borrow_mut()
+            .ob_gate_1op_assign(&mut ct_res, ct_arg_1, server_key)
+    })?;
+    Ok(ct_res)
+}
 
-    OB_BOOLEAN_ENGINE.with(|engine_cell| {
-        let eng = &mut engine_cell.borrow_mut() as &mut BooleanEngine;
-        let bootstrapper = &mut eng.bootstrapper;
-        bootstrapper.apply_bootstrapping_pattern(buffer_lwe, &server_key)
-    })
+/*A 1-operand obfuscated logic gate that performs A*c1+d, hiding BUF, NOT, CONST0 and CONST1
+A: TGSW encryption of 1 (BUF, NOT) or 0 (constants), as in ob_gate_2op
+d: TLWE ciphertext of 0 (BUF), 1/2 (NOT), -1/8 (CONST0) or 1/8 (CONST1)
+(see gate_compiler::UnaryGateKind for the parameters)
+*/
+pub fn ob_gate_1op_tgsw<C: Container<Element = u32>>(
+    ct_1: &Ciphertext,
+    A: &TgswCiphertext<C>,
+    ct_d: &Ciphertext,
+    tgsw_params: &TgswParams,
+    server_key: &ServerKey,
+) -> Result<Ciphertext, ObGateError> {
+    let mut ct_res = ct_1.clone();
+    OB_GATE_SCRATCH.with(|scratch| {
+        scratch
+            .borrow_mut()
+            .ob_gate_1op_tgsw_assign(&mut ct_res, A, ct_d, tgsw_params, server_key)
+    })?;
+    Ok(ct_res)
 }
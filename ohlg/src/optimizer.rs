@@ -0,0 +1,494 @@
+/*Client-side optimization of a circuit before its gate parameters are encrypted: every gate removed saves a bootstrapping
+on the server. The passes are run together, gate by gate in evaluation order:
+- inverter folding: the 1-input BUF and NOT gates are removed, and the negations are absorbed into the parameters
+  of their readers when the function stays symmetric (NOT on both inputs of a 2-input gate, or on an input of a XOR/XNOR),
+  or into the parameters of their driver (a 2-input gate computing the negated function). A NOT gate is only kept
+  when neither is possible (e.g. a AND NOT b).
+- constant propagation: the CONST0/CONST1 gates and the 2-input gates with a constant input or output are evaluated
+  away, the constants only need a gate where they are read by an output port or a gate that is not a known 2-input gate
+- duplicate merging: gates with the same operation, parameters and inputs are computed once
+- dead-gate removal: the gates no output depends on are removed
+The gates whose parameters are not the ones of GateKind or UnaryGateKind (threshold gates, multiplexers, ...)
+are kept as they are, only merged and removed when dead.
+The optimized circuit is checked against the original one by plaintext simulation (see check_equivalence).
+The optimization reveals nothing more than the netlist itself, it should be run before topology::hide_topology
+(which inserts dummy BUF gates that the optimizer would remove).
+*/
+use std::collections::HashMap;
+use tfhe::core_crypto::commons::math::random::{DefaultRandomGenerator, RandomGenerator, Seed};
+
+use crate::gate_compiler::{GateKind, TruthTable, UnaryGateKind};
+use crate::netlist::{Circuit, GateOp, NetlistError, WireId};
+
+//The number of input vectors simulated by optimize to check the optimized circuit (all of them for up to 12 input bits)
+pub const EQUIVALENCE_SAMPLES: usize = 1 << 12;
+
+//What the optimization removed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub gates_before: usize,
+    pub gates_after: usize,
+    pub bootstraps_before: usize,
+    pub bootstraps_after: usize,
+    //The 1-input BUF and NOT gates removed, folded into the gates around them (the kept NOT gates are not counted)
+    pub folded_inverters: usize,
+    //The gates with a constant input or a constant output
+    pub constant_gates: usize,
+    //The gates merged with an identical gate
+    pub merged_gates: usize,
+    //The gates removed because no output depends on them
+    pub dead_gates: usize,
+    //The number of input vectors on which the optimized circuit was checked against the original one
+    pub checked_inputs: usize,
+}
+
+impl OptimizationReport {
+    pub fn bootstraps_saved(&self) -> usize {
+        self.bootstraps_before - self.bootstraps_after
+    }
+}
+
+//The reasons why a circuit cannot be optimized
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptimizeError {
+    //The netlist of the circuit is invalid (see Netlist::validate), or cannot be simulated
+    Netlist(NetlistError),
+    //The circuits compute different outputs on these inputs
+    NotEquivalent { inputs: Vec<Vec<bool>> },
+}
+
+impl std::fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Netlist(error) => write!(f, "Invalid netlist: {}", error),
+            Self::NotEquivalent { inputs } => {
+                write!(f, "The circuits differ on the inputs {:?}", inputs)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OptimizeError {}
+
+impl From<NetlistError> for OptimizeError {
+    fn from(error: NetlistError) -> Self {
+        Self::Netlist(error)
+    }
+}
+
+/*Optimize the circuit (see above), returns the optimized circuit with the report of what was removed
+The optimized circuit is checked on EQUIVALENCE_SAMPLES input vectors, drawn from seed if there are more input vectors
+(see check_equivalence), so that the check can be reproduced. It never has more bootstrappings than
+the original circuit: if the optimization does not pay off, the original circuit is returned.
+*/
+pub fn optimize(
+    circuit: &Circuit,
+    seed: Seed,
+) -> Result<(Circuit, OptimizationReport), OptimizeError> {
+    circuit.validate()?;
+    let mut optimizer = Optimizer::new(circuit);
+    optimizer.run(circuit);
+    let (optimized, dead_gates) = optimizer.remove_dead_gates();
+
+    let mut report = optimizer.report;
+    report.gates_before = circuit.netlist.gates.len();
+    report.bootstraps_before = circuit.netlist.bootstrap_count();
+    report.dead_gates = dead_gates;
+    //The NOT gates kept on an input of an asymmetric gate were not folded
+    report.folded_inverters = inverter_count(circuit).saturating_sub(inverter_count(&optimized));
+    report.gates_after = optimized.netlist.gates.len();
+    report.bootstraps_after = optimized.netlist.bootstrap_count();
+    if report.bootstraps_after > report.bootstraps_before {
+        return Ok((
+            circuit.clone(),
+            OptimizationReport {
+                gates_before: report.gates_before,
+                gates_after: report.gates_before,
+                bootstraps_before: report.bootstraps_before,
+                bootstraps_after: report.bootstraps_before,
+                ..Default::default()
+            },
+        ));
+    }
+    report.checked_inputs = check_equivalence(circuit, &optimized, EQUIVALENCE_SAMPLES, seed)?;
+    Ok((optimized, report))
+}
+
+/*Check that two circuits with the same input ports compute the same outputs, by plaintext simulation:
+on every input vector if there are at most num_samples of them, else on num_samples random ones drawn from seed
+Returns the number of input vectors checked
+*/
+pub fn check_equivalence(
+    a: &Circuit,
+    b: &Circuit,
+    num_samples: usize,
+    seed: Seed,
+) -> Result<usize, OptimizeError> {
+    let widths: Vec<usize> = a
+        .netlist
+        .inputs
+        .iter()
+        .map(|port| port.wires.len())
+        .collect();
+    let num_bits: usize = widths.iter().sum();
+    let exhaustive = num_bits < usize::BITS as usize && 1 << num_bits <= num_samples;
+    let num_inputs = if exhaustive {
+        1 << num_bits
+    } else {
+        num_samples
+    };
+
+    let mut rng = RandomGenerator::<DefaultRandomGenerator>::new(seed);
+    for x in 0..num_inputs {
+        let mut next_bit = 0;
+        let mut bit = || {
+            next_bit += 1;
+            if exhaustive {
+                x >> (next_bit - 1) & 1 == 1
+            } else {
+                rng.random_uniform_binary::<u8>() == 1
+            }
+        };
+        let inputs: Vec<Vec<bool>> = widths
+            .iter()
+            .map(|&width| (0..width).map(|_| bit()).collect())
+            .collect();
+        if a.simulate(&inputs)? != b.simulate(&inputs)? {
+            return Err(OptimizeError::NotEquivalent { inputs });
+        }
+    }
+    Ok(num_inputs)
+}
+
+//The number of 1-input BUF and NOT gates of the circuit
+fn inverter_count(circuit: &Circuit) -> usize {
+    circuit
+        .netlist
+        .gates
+        .iter()
+        .filter(|gate| {
+            gate.op == GateOp::OneOp
+                && matches!(
+                    gate.param_slots()
+                        .and_then(|slots| circuit.gate_plaintexts.get(slots.start).copied())
+                        .and_then(UnaryGateKind::from_plaintexts),
+                    Some(UnaryGateKind::Buf | UnaryGateKind::Not)
+                )
+        })
+        .count()
+}
+
+//The value of a wire of the original circuit in the optimized one: a constant, or a wire possibly negated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Value {
+    Const(bool),
+    Wire { wire: WireId, negated: bool },
+}
+
+impl Value {
+    fn wire(wire: WireId) -> Self {
+        Self::Wire {
+            wire,
+            negated: false,
+        }
+    }
+
+    //The value of the 1-input function of this value
+    fn apply(self, kind: UnaryGateKind) -> Self {
+        match (kind, self) {
+            (UnaryGateKind::Buf, value) => value,
+            (UnaryGateKind::Not, Self::Const(m)) => Self::Const(!m),
+            (UnaryGateKind::Not, Self::Wire { wire, negated }) => Self::Wire {
+                wire,
+                negated: !negated,
+            },
+            (UnaryGateKind::Const0, _) => Self::Const(false),
+            (UnaryGateKind::Const1, _) => Self::Const(true),
+        }
+    }
+}
+
+//A gate of the optimized circuit: its operation, inputs and parameters
+type GateKey = (GateOp, Vec<WireId>, Vec<(u32, u32)>);
+
+//Builds the optimized circuit gate by gate, with the gates of the optimized circuit merged as they are added
+struct Optimizer {
+    optimized: Circuit,
+    //The gate of the optimized circuit driving each of its wires
+    drivers: HashMap<WireId, usize>,
+    //The output wire of each gate of the optimized circuit
+    gates: HashMap<GateKey, WireId>,
+    //The wire read by the constant gates (the first input wire)
+    constant_input: Option<WireId>,
+    //The value of each wire of the original circuit
+    values: Vec<Option<Value>>,
+    report: OptimizationReport,
+}
+
+impl Optimizer {
+    fn new(circuit: &Circuit) -> Self {
+        let mut optimized = Circuit::new();
+        let mut values = vec![None; circuit.netlist.num_wires];
+        for port in &circuit.netlist.inputs {
+            let wires = optimized
+                .netlist
+                .add_input_port(&port.name, port.wires.len());
+            for (&wire, new_wire) in port.wires.iter().zip(wires) {
+                values[wire] = Some(Value::wire(new_wire));
+            }
+        }
+        let constant_input = optimized
+            .netlist
+            .inputs
+            .iter()
+            .find_map(|port| port.wires.first().copied());
+        Self {
+            optimized,
+            drivers: HashMap::new(),
+            gates: HashMap::new(),
+            constant_input,
+            values,
+            report: OptimizationReport::default(),
+        }
+    }
+
+    fn run(&mut self, circuit: &Circuit) {
+        for gate in &circuit.netlist.gates {
+            //validate() guarantees that the inputs are computed and that the parameter slots are in gate_plaintexts
+            let inputs: Vec<Value> = gate
+                .inputs
+                .iter()
+                .map(|&wire| self.values[wire].unwrap())
+                .collect();
+            let plaintexts = &circuit.gate_plaintexts[gate.param_slots().unwrap()];
+            let first = plaintexts.first().copied();
+
+            let value = match (
+                gate.op,
+                first.and_then(UnaryGateKind::from_plaintexts),
+                first.and_then(GateKind::from_plaintexts),
+            ) {
+                (GateOp::OneOp, Some(kind), _) => {
+                    if matches!(kind, UnaryGateKind::Const0 | UnaryGateKind::Const1) {
+                        self.report.constant_gates += 1;
+                    }
+                    inputs[0].apply(kind)
+                }
+                (GateOp::TwoOp, _, Some(kind)) => self.add_gate_2op(kind, inputs[0], inputs[1]),
+                _ => {
+                    let wires = inputs.into_iter().map(|value| self.wire(value)).collect();
+                    Value::wire(self.add_gate(gate.op, wires, plaintexts))
+                }
+            };
+            self.values[gate.output] = Some(value);
+        }
+
+        for port in &circuit.netlist.outputs {
+            let wires = port
+                .wires
+                .iter()
+                .map(|&wire| self.wire(self.values[wire].unwrap()))
+                .collect();
+            self.optimized.netlist.add_output_port(&port.name, wires);
+        }
+    }
+
+    //A known 2-input gate on the values: no gate if the output is a constant or a function of a single wire
+    fn add_gate_2op(&mut self, kind: GateKind, a: Value, b: Value) -> Value {
+        let (wire_a, negated_a, wire_b, negated_b) = match (a, b) {
+            (Value::Const(m1), Value::Const(m2)) => {
+                self.report.constant_gates += 1;
+                return Value::Const(kind.evaluate(m1, m2));
+            }
+            (Value::Const(m), Value::Wire { wire, negated })
+            | (Value::Wire { wire, negated }, Value::Const(m)) => {
+                self.report.constant_gates += 1;
+                let unary = UnaryGateKind::from_truth_table(
+                    [false, true].map(|x| kind.evaluate(m, x != negated)),
+                );
+                return Value::wire(wire).apply(unary);
+            }
+            (
+                Value::Wire {
+                    wire: wire_a,
+                    negated: negated_a,
+                },
+                Value::Wire {
+                    wire: wire_b,
+                    negated: negated_b,
+                },
+            ) => (wire_a, negated_a, wire_b, negated_b),
+        };
+        //The function of the gate on the wires of its inputs, with their negations folded in
+        let t: TruthTable = [0, 1, 2, 3]
+            .map(|i| kind.evaluate((i >> 1 == 1) != negated_a, (i & 1 == 1) != negated_b));
+
+        if t[0] == t[1] && t[2] == t[3] || wire_a == wire_b {
+            //A function of the first wire only (for the same wire, f(x, x) = t[3x])
+            let outputs = if wire_a == wire_b {
+                [t[0], t[3]]
+            } else {
+                [t[0], t[2]]
+            };
+            if outputs[0] == outputs[1] {
+                self.report.constant_gates += 1;
+            }
+            return Value::wire(wire_a).apply(UnaryGateKind::from_truth_table(outputs));
+        }
+        if t[0] == t[2] && t[1] == t[3] {
+            return Value::wire(wire_b).apply(UnaryGateKind::from_truth_table([t[0], t[1]]));
+        }
+        match GateKind::from_truth_table(&t) {
+            Ok(folded) => Value::wire(self.add_gate(
+                GateOp::TwoOp,
+                vec![wire_a, wire_b],
+                &[folded.plaintexts()],
+            )),
+            //f(a, NOT b) is not symmetric: the negated input needs a gate
+            Err(_) => {
+                let inputs = vec![self.wire(a), self.wire(b)];
+                Value::wire(self.add_gate(GateOp::TwoOp, inputs, &[kind.plaintexts()]))
+            }
+        }
+    }
+
+    //The wire of the optimized circuit computing the value, adding a gate for the constants and the negations
+    fn wire(&mut self, value: Value) -> WireId {
+        match value {
+            Value::Wire {
+                wire,
+                negated: false,
+            } => wire,
+            Value::Const(m) => {
+                let kind = if m {
+                    UnaryGateKind::Const1
+                } else {
+                    UnaryGateKind::Const0
+                };
+                let input = self
+                    .constant_input
+                    .expect("a constant is computed by a gate, so the circuit has an input wire");
+                self.add_gate(GateOp::OneOp, vec![input], &[kind.plaintexts()])
+            }
+            Value::Wire {
+                wire,
+                negated: true,
+            } => {
+                /*Absorb the negation into the driver of the wire when it is a known gate:
+                a 2-input gate computing the negated function, or the input of a NOT gate
+                */
+                if let Some(&index) = self.drivers.get(&wire) {
+                    let gate = &self.optimized.netlist.gates[index];
+                    let plaintexts = self.optimized.gate_plaintexts.get(gate.param).copied();
+                    match (gate.op, plaintexts) {
+                        (GateOp::TwoOp, Some(plaintexts)) => {
+                            if let Some(kind) = GateKind::from_plaintexts(plaintexts) {
+                                let inputs = gate.inputs.clone();
+                                return self.add_gate(
+                                    GateOp::TwoOp,
+                                    inputs,
+                                    &[kind.negated().plaintexts()],
+                                );
+                            }
+                        }
+                        (GateOp::OneOp, Some(plaintexts))
+                            if plaintexts == UnaryGateKind::Not.plaintexts() =>
+                        {
+                            return gate.inputs[0];
+                        }
+                        _ => {}
+                    }
+                }
+                self.add_gate(
+                    GateOp::OneOp,
+                    vec![wire],
+                    &[UnaryGateKind::Not.plaintexts()],
+                )
+            }
+        }
+    }
+
+    //Add a gate to the optimized circuit, or return the output of the identical gate already added
+    fn add_gate(
+        &mut self,
+        op: GateOp,
+        mut inputs: Vec<WireId>,
+        plaintexts: &[(u32, u32)],
+    ) -> WireId {
+        //The 2-input and threshold gates only see the sum of their inputs
+        if matches!(op, GateOp::TwoOp | GateOp::Threshold { .. }) {
+            inputs.sort_unstable();
+        }
+        let key = (op, inputs, plaintexts.to_vec());
+        if let Some(&wire) = self.gates.get(&key) {
+            self.report.merged_gates += 1;
+            return wire;
+        }
+        let (op, inputs, plaintexts) = key.clone();
+        let param = self.optimized.gate_plaintexts.len();
+        self.optimized.gate_plaintexts.extend(plaintexts);
+        let wire = self.optimized.netlist.add_gate(op, inputs, param);
+        self.drivers
+            .insert(wire, self.optimized.netlist.gates.len() - 1);
+        self.gates.insert(key, wire);
+        wire
+    }
+
+    /*Remove the gates no output depends on, and renumber the wires and the gate parameter slots
+    Returns the optimized circuit and the number of gates removed
+    */
+    fn remove_dead_gates(&self) -> (Circuit, usize) {
+        let netlist = &self.optimized.netlist;
+        let mut live = vec![false; netlist.num_wires];
+        for port in &netlist.outputs {
+            for &wire in &port.wires {
+                live[wire] = true;
+            }
+        }
+        for gate in netlist.gates.iter().rev() {
+            if live[gate.output] {
+                for &wire in &gate.inputs {
+                    live[wire] = true;
+                }
+            }
+        }
+
+        let mut circuit = Circuit::new();
+        let mut wires = vec![None; netlist.num_wires];
+        for port in &netlist.inputs {
+            let new_wires = circuit.netlist.add_input_port(&port.name, port.wires.len());
+            for (&wire, new_wire) in port.wires.iter().zip(new_wires) {
+                wires[wire] = Some(new_wire);
+            }
+        }
+        let mut dead_gates = 0;
+        for gate in &netlist.gates {
+            if !live[gate.output] {
+                dead_gates += 1;
+                continue;
+            }
+            let count = gate.op.param_count();
+            let param = circuit.gate_plaintexts.len();
+            //The slots of the optimized gates were allocated by the optimizer in self.optimized.gate_plaintexts
+            circuit
+                .gate_plaintexts
+                .extend_from_slice(&self.optimized.gate_plaintexts[gate.param_slots().unwrap()]);
+            let inputs = gate
+                .inputs
+                .iter()
+                .map(|&wire| wires[wire].unwrap())
+                .collect();
+            let param = if count > 0 { param } else { 0 };
+            wires[gate.output] = Some(circuit.netlist.add_gate(gate.op, inputs, param));
+        }
+        for port in &netlist.outputs {
+            let port_wires = port
+                .wires
+                .iter()
+                .map(|&wire| wires[wire].unwrap())
+                .collect();
+            circuit.netlist.add_output_port(&port.name, port_wires);
+        }
+        (circuit, dead_gates)
+    }
+}
@@ -2,237 +2,1398 @@
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use tfhe::boolean::engine::BooleanEngine;
-use tfhe::{
-    boolean::prelude::{BooleanParameters, Ciphertext, ClientKey},
-    core_crypto::prelude::{CiphertextModulus, LweCiphertext},
+use tfhe::boolean::prelude::{
+    BooleanParameters, Ciphertext, ClientKey, CompressedPublicKey, EncryptionKeyChoice, PublicKey,
 };
+use tfhe::conformance::ParameterSetConformant;
+use tfhe::core_crypto::algorithms::misc::check_encrypted_content_respects_mod;
+use tfhe::core_crypto::commons::math::random::{Distribution, Uniform};
+use tfhe::core_crypto::prelude::*;
+use tfhe::core_crypto::seeders::new_seeder;
+use tfhe::named::Named;
+use tfhe_versionable::Versionize;
+
+use crate::backward_compatibility::{
+    DecompositionKindVersions, SeededTgswCiphertextVersions, TgswCiphertextVersions,
+    TgswParamsVersions,
+};
+use crate::noise::ext_product_noise;
 
 thread_local! {
     static OB_BOOLEAN_ENGINE: RefCell<BooleanEngine> = RefCell::new(BooleanEngine::new());
 }
 
-pub struct GadgetMatrix {
-    pub matrix: Vec<Vec<u32>>,
-}
+/*The digits used to decompose the LWE coefficients (GT^-1) in the external product
+Unsigned: digits in [0, B), as in the original prototype
+Balanced: signed digits in [-B/2, B/2), computed with tfhe's SignedDecomposer when B is a power of two
 
-impl GadgetMatrix {
-    // Method to create the gadget matrix based on the parameters n, B, l, and q
-    pub fn new(n: usize, B: u32, l: usize, q: u64) -> Self {
-        // Define the dimensions of the matrix: (n+1)*l rows and (n+1) columns
-        let rows = (n + 1) * l;
-        let cols = n + 1;
+Noise analysis: the external product of TGSW(M) (row noise variance s_tgsw^2) with an LWE ciphertext of dimension n adds
+    Var = l(n+1) * E[d^2] * s_tgsw^2 + M^2 * (1 + n/2) * e_round^2
+where E[d^2] is the mean square of a digit and e_round^2 = (q/B^l)^2/12 is the variance of the rounding to B^l/q (binary key)
+- Unsigned digits (uniform in [0, B)): E[d^2] = (B-1)(2B-1)/6 ~ B^2/3
+- Balanced digits (uniform in [-B/2, B/2)): E[d^2] = (B^2+2)/12 ~ B^2/12
+So the balanced decomposition divides the digit part of the variance by about 4 (half the standard deviation).
+With DEFAULT_PARAMETERS (n = 805, s_tgsw = 5.86e-6 ~ 2^-17.4 on the torus) and B = 64, l = 2, the digit part has a
+standard deviation of ~8.7e-3 (unsigned) or ~4.4e-3 (balanced) against ~1.4e-3 * M for the rounding part,
+which allows a smaller l (or a lower failure probability) for the same gate.
+See noise::ext_product_noise for these values with any parameter set, and the test decomposition_noise (tests/tgsw.rs)
+for the measured noise of both decompositions against the model.
+*/
+#[derive(Serialize, Deserialize, Versionize, Clone, Copy, Debug, PartialEq, Eq)]
+#[versionize(DecompositionKindVersions)]
+pub enum DecompositionKind {
+    Unsigned,
+    Balanced,
+}
 
-        // Create a matrix initialized with zeros
-        let mut matrix: Vec<Vec<u32>> = vec![vec![0; cols]; rows];
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Versionize)]
+#[versionize(TgswParamsVersions)]
+pub struct TgswParams {
+    pub tfhe_params: BooleanParameters,
+    pub decomp_base: u32,
+    pub decomp_level: usize,
+    pub decomp_kind: DecompositionKind,
+}
 
-        // Fill in the matrix GT as a decomposition matrix based on B and l
-        for i in 0..rows {
-            for j in 0..cols {
-                if i >= j * l && i < (j + 1) * l {
-                    // Set the element based on the given formula
-                    let exponent = (i - j * l + 1) as u32;
-                    //let value = ((q as f64 / B.pow(exponent) as f64) as u32);  //This line was working 16-jan-2025
-                    let value = (q as u64 / B.pow(exponent) as u64) as u32; //This line is working 16-jan-2025
-                    matrix[i][j] = value;
-                }
-            }
-        }
+/*The largest failure probability of an obfuscated gate (multiplier 1, fresh inputs) accepted by TgswParams::try_new,
+as given by the noise model (see noise::ext_product_noise)
+Larger multipliers and inputs coming from previous gates only increase it
+*/
+pub const MAX_GATE_FAILURE_PROBABILITY: f64 = 1.0 / (1u64 << 20) as f64;
 
-        GadgetMatrix { matrix }
-    }
+//The reasons why TgswParams::try_new rejects a decomposition or a boolean parameter set
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TgswParamsError {
+    //B < 2, the decomposition has no digits
+    BaseTooSmall {
+        decomp_base: u32,
+    },
+    //B is not a power of two, the rounding to B^l/q is not exact on the u32 torus
+    BaseNotPowerOfTwo {
+        decomp_base: u32,
+    },
+    //l = 0, the TGSW ciphertext has no rows
+    ZeroLevel,
+    //B^l > q = 2^32, the gadget values q/B^(i+1) of the last levels are zero
+    PrecisionExceedsModulus {
+        decomp_base: u32,
+        decomp_level: usize,
+    },
+    //No level l in base B drops at most `dropped_bits` bits without exceeding q (see TgswParams::new_approximate)
+    DroppedBitsOutOfRange {
+        decomp_base: u32,
+        dropped_bits: u32,
+    },
+    //The encryption key of the boolean parameter set (see TgswParams::lwe_dimension) has dimension 0
+    EmptyEncryptionKey,
+    //The noise model of the gate (see noise::ext_product_noise) exceeds MAX_GATE_FAILURE_PROBABILITY:
+    //the rounding error is too large (l too small) or the digit noise is (l, B or the parameter set noise too large)
+    GateTooNoisy {
+        failure_probability: f64,
+    },
+}
 
-    // Method to display the gadget matrix (optional for debugging)
-    pub fn display(&self) {
-        for row in &self.matrix {
-            println!("{:?}", row);
+impl std::fmt::Display for TgswParamsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BaseTooSmall { decomp_base } => {
+                write!(f, "The decomposition base {decomp_base} is smaller than 2")
+            }
+            Self::BaseNotPowerOfTwo { decomp_base } => {
+                write!(
+                    f,
+                    "The decomposition base {decomp_base} is not a power of two"
+                )
+            }
+            Self::ZeroLevel => write!(f, "The decomposition level is 0"),
+            Self::PrecisionExceedsModulus {
+                decomp_base,
+                decomp_level,
+            } => write!(
+                f,
+                "The decomposition precision {decomp_base}^{decomp_level} exceeds the modulus 2^32"
+            ),
+            Self::DroppedBitsOutOfRange {
+                decomp_base,
+                dropped_bits,
+            } => write!(
+                f,
+                "No decomposition level in base {decomp_base} drops at most {dropped_bits} bits \
+                 without exceeding the modulus 2^32"
+            ),
+            Self::EmptyEncryptionKey => {
+                write!(
+                    f,
+                    "The encryption key of the boolean parameter set is empty"
+                )
+            }
+            Self::GateTooNoisy {
+                failure_probability,
+            } => write!(
+                f,
+                "The obfuscated gate failure probability {failure_probability:.2e} exceeds \
+                 {MAX_GATE_FAILURE_PROBABILITY:.2e}"
+            ),
         }
     }
 }
 
-// Use a static initialization to make the gadget matrix globally available
-static mut GADGET_MATRIX: Option<GadgetMatrix> = None;
-use std::sync::Once;
-static INIT: Once = Once::new();
+impl std::error::Error for TgswParamsError {}
 
-// Function to initialize the gadget matrix globally
-pub fn get_gadget_matrix(n: usize, B: u32, l: usize, q: u64) -> &'static GadgetMatrix {
-    unsafe {
-        INIT.call_once(|| {
-            // Create the gadget matrix with the provided parameters
-            let gadget = GadgetMatrix::new(n, B, l, q);
-            GADGET_MATRIX = Some(gadget);
-        });
-        GADGET_MATRIX.as_ref().unwrap()
-    }
+//The reasons why a TGSW ciphertext cannot be encrypted with a key or a modulus
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TgswError {
+    //The seeded ciphertexts only support the native and the power of two ciphertext moduli, as the tfhe seeded entities
+    UnsupportedCiphertextModulus {
+        modulus: u128,
+    },
+    //The public key does not encrypt under the key of the parameter set (see TgswParams::lwe_dimension)
+    KeyDimensionMismatch {
+        expected: LweDimension,
+        found: LweDimension,
+    },
+    //The public-key encryption of a parameter set with EncryptionKeyChoice::Small is too noisy for the gates
+    //(see TgswCiphertext::new_encrypt_with_public_key)
+    SmallEncryptionKey,
+    //The public key returned a trivial encryption of zero, which cannot hide the message of the TGSW ciphertext
+    TrivialPublicKeyEncryption,
+    //A container that is not l(n+1) rows of lwe_size values (e.g. a corrupted or truncated serialized ciphertext)
+    InvalidContainerLength {
+        container_len: usize,
+        lwe_size: LweSize,
+    },
+    //A V0 serialized ciphertext whose row `row` does not have the length of the first row
+    RaggedRows {
+        row: usize,
+        row_len: usize,
+        lwe_size: LweSize,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct TgswParams {
-    pub tfhe_params: BooleanParameters,
-    pub decomp_base: u32,
-    pub decomp_level: usize,
+impl std::fmt::Display for TgswError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedCiphertextModulus { modulus } => write!(
+                f,
+                "The ciphertext modulus {modulus} is not a power of two, it cannot be seeded"
+            ),
+            Self::KeyDimensionMismatch { expected, found } => write!(
+                f,
+                "The public key encrypts under a key of dimension {}, the parameter set under a key of dimension {}",
+                found.0, expected.0
+            ),
+            Self::SmallEncryptionKey => write!(
+                f,
+                "The public-key gate parameters need a parameter set with EncryptionKeyChoice::Big"
+            ),
+            Self::TrivialPublicKeyEncryption => write!(
+                f,
+                "The public key returned a trivial ciphertext instead of an encryption of zero"
+            ),
+            Self::InvalidContainerLength {
+                container_len,
+                lwe_size,
+            } => write!(
+                f,
+                "The container length ({container_len}) is not a non-zero multiple of lwe_size^2 (lwe_size = {})",
+                lwe_size.0
+            ),
+            Self::RaggedRows {
+                row,
+                row_len,
+                lwe_size,
+            } => write!(
+                f,
+                "The TGSW row {row} has {row_len} values, expected lwe_size = {}",
+                lwe_size.0
+            ),
+        }
+    }
 }
 
+impl std::error::Error for TgswError {}
+
 impl TgswParams {
+    //Parameters with the unsigned decomposition of the original prototype
+    //The parameters are not checked, see try_new
     pub fn new(tfhe_params: BooleanParameters, decomp_base: u32, decomp_level: usize) -> Self {
+        Self::new_with_decomposition(
+            tfhe_params,
+            decomp_base,
+            decomp_level,
+            DecompositionKind::Unsigned,
+        )
+    }
+
+    pub fn new_with_decomposition(
+        tfhe_params: BooleanParameters,
+        decomp_base: u32,
+        decomp_level: usize,
+        decomp_kind: DecompositionKind,
+    ) -> Self {
         Self {
             tfhe_params,
             decomp_base,
             decomp_level,
+            decomp_kind,
+        }
+    }
+
+    /*Parameters checked by validate, so that an invalid decomposition fails here rather than producing wrong gate outputs
+    (the gadget values and the rounding of the external product silently overflow or truncate otherwise)
+    */
+    pub fn try_new(
+        tfhe_params: BooleanParameters,
+        decomp_base: u32,
+        decomp_level: usize,
+        decomp_kind: DecompositionKind,
+    ) -> Result<Self, TgswParamsError> {
+        let tgsw_params =
+            Self::new_with_decomposition(tfhe_params, decomp_base, decomp_level, decomp_kind);
+        tgsw_params.validate()?;
+        Ok(tgsw_params)
+    }
+
+    /*Parameters with an approximate decomposition that drops at most `dropped_bits` low bits of each coefficient
+    The external product rounds each coefficient of the u32 torus to a multiple of q/B^l before decomposing it,
+    so the dropped precision and the TGSW size are the same setting: this picks the smallest level l with
+    B^l >= 2^(32 - dropped_bits), i.e. l = ceil((32 - dropped_bits)/log2(B)).
+    Dropping more bits gives a smaller l (the TGSW ciphertext has l(n+1)^2 values and the external product costs
+    l(n+1)^2 multiply-adds) at the price of a larger rounding error, see noise::ext_product_noise.
+    The parameters are checked as in try_new
+    */
+    pub fn new_approximate(
+        tfhe_params: BooleanParameters,
+        decomp_base: u32,
+        dropped_bits: u32,
+        decomp_kind: DecompositionKind,
+    ) -> Result<Self, TgswParamsError> {
+        if decomp_base < 2 {
+            return Err(TgswParamsError::BaseTooSmall { decomp_base });
+        }
+        let out_of_range = TgswParamsError::DroppedBitsOutOfRange {
+            decomp_base,
+            dropped_bits,
+        };
+        if dropped_bits >= u32::BITS {
+            return Err(out_of_range);
         }
+
+        let precision = 1u128 << (u32::BITS - dropped_bits);
+        let mut decomp_level = 1;
+        while (decomp_base as u128).pow(decomp_level as u32) < precision {
+            decomp_level += 1;
+        }
+        if (decomp_base as u128).pow(decomp_level as u32) > 1u128 << u32::BITS {
+            return Err(out_of_range);
+        }
+
+        Self::try_new(tfhe_params, decomp_base, decomp_level, decomp_kind)
     }
+
+    /*Check that the decomposition and the boolean parameter set can be used by the TGSW layer:
+    - 2 <= B, B a power of two, l >= 1 and B^l <= q = 2^32, so that the gadget values q/B^(i+1) are exact
+    - the encryption key of the parameter set is not empty
+    - l against the parameter set: the gate failure probability of the noise model (multiplier 1, fresh inputs)
+      is at most MAX_GATE_FAILURE_PROBABILITY
+    Also useful on deserialized parameters, which do not go through try_new
+    */
+    pub fn validate(&self) -> Result<(), TgswParamsError> {
+        let decomp_base = self.decomp_base;
+        let decomp_level = self.decomp_level;
+        if decomp_base < 2 {
+            return Err(TgswParamsError::BaseTooSmall { decomp_base });
+        }
+        if !decomp_base.is_power_of_two() {
+            return Err(TgswParamsError::BaseNotPowerOfTwo { decomp_base });
+        }
+        if decomp_level == 0 {
+            return Err(TgswParamsError::ZeroLevel);
+        }
+        if decomp_base.ilog2() as usize * decomp_level > u32::BITS as usize {
+            return Err(TgswParamsError::PrecisionExceedsModulus {
+                decomp_base,
+                decomp_level,
+            });
+        }
+        if self.lwe_dimension().0 == 0 {
+            return Err(TgswParamsError::EmptyEncryptionKey);
+        }
+
+        let model = ext_product_noise(self, 1);
+        let failure_probability = model.gate_failure_probability(model.fresh_variance);
+        if failure_probability.is_nan() || failure_probability > MAX_GATE_FAILURE_PROBABILITY {
+            return Err(TgswParamsError::GateTooNoisy {
+                failure_probability,
+            });
+        }
+        Ok(())
+    }
+
+    //The number of low bits log2(q/B^l) of each coefficient dropped by the rounding of the external product
+    //It is 0 for an exact decomposition (B^l = q) and is not an integer when B is not a power of two
+    pub fn dropped_bits(&self) -> f64 {
+        u32::BITS as f64 - self.decomp_level as f64 * (self.decomp_base as f64).log2()
+    }
+
+    //The dimension n of the LWE key the boolean ciphertexts (and the TGSW rows) are encrypted under
+    pub fn lwe_dimension(&self) -> LweDimension {
+        match self.tfhe_params.encryption_key_choice {
+            EncryptionKeyChoice::Big => self
+                .tfhe_params
+                .glwe_dimension
+                .to_equivalent_lwe_dimension(self.tfhe_params.polynomial_size),
+            EncryptionKeyChoice::Small => self.tfhe_params.lwe_dimension,
+        }
+    }
+}
+
+impl Named for TgswParams {
+    const NAME: &'static str = "ohlg::TgswParams";
 }
 
 /*A TGSW ciphertext that encryptes a message M in Z/PZ (P = B^L) with dimension l(n+1)*(n+1)
-Note that the implementation is done with type u32, which assumes a ciphertext modulus of 2^32
-This is a LIMITATION of the current implementation, and needs to be updated with more generic approach for other q values
+The implementation is generic over the torus type (u32, u64) and the ciphertext modulus q:
+- Native moduli (2^32, 2^64) and non-native powers of two (stored in the MSBs, as tfhe does) use wrapping arithmetic
+- Other (custom) moduli use arithmetic mod q
+The boolean API (new_encrypt, and ext_product in gate_core) works with the u32 native torus of the tfhe boolean parameters
+
+As for the tfhe entities, the l(n+1) rows of n+1 values are stored row after row in a single container C:
+TgswCiphertextOwned owns a Vec (the default, TgswCiphertext), TgswCiphertextView and TgswCiphertextMutView borrow a slice.
+from_container wraps any slice of l(n+1)^2 values without copying (e.g. a memory-mapped gate-parameter file),
+and each row is an LWE ciphertext under the same key (see as_lwe_ciphertext_list).
 */
-#[derive(Serialize, Deserialize)]
-pub struct TgswCiphertext {
-    ct_matrix: Vec<Vec<u32>>,
-    //params: BooleanParameters,
-    //dec_base: u32,
-    //dec_level: usize,
+#[derive(Serialize, Deserialize, Versionize, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "SerializableTgswCiphertext<C>")]
+#[versionize(try_convert = "SerializableTgswCiphertext<C>")]
+pub struct TgswCiphertext<C: Container = Vec<u32>>
+where
+    C::Element: UnsignedInteger,
+{
+    data: C,
+    lwe_size: LweSize,
+    ciphertext_modulus: CiphertextModulus<C::Element>,
 }
 
-impl TgswCiphertext {
-    //Create a new TGSW ciphertext that encrypts a message m in Z/PZ
-    //The implementation assumes a q value of 2^32
+/*The serialized fields of a TgswCiphertext, unchecked
+A TgswCiphertext is only deserialized through try_from_container, so that a malformed ciphertext
+(e.g. lwe_size = 0, or a truncated container) is an error rather than a panic in decomp_level or the external product
+*/
+#[derive(Serialize, Deserialize, Versionize, Clone)]
+#[versionize(TgswCiphertextVersions)]
+pub struct SerializableTgswCiphertext<C: Container>
+where
+    C::Element: UnsignedInteger,
+{
+    pub(crate) data: C,
+    pub(crate) lwe_size: LweSize,
+    pub(crate) ciphertext_modulus: CiphertextModulus<C::Element>,
+}
+
+impl<Scalar: UnsignedInteger, C: Container<Element = Scalar>> From<TgswCiphertext<C>>
+    for SerializableTgswCiphertext<C>
+{
+    fn from(tgsw_ct: TgswCiphertext<C>) -> Self {
+        Self {
+            data: tgsw_ct.data,
+            lwe_size: tgsw_ct.lwe_size,
+            ciphertext_modulus: tgsw_ct.ciphertext_modulus,
+        }
+    }
+}
+
+impl<Scalar: UnsignedInteger, C: Container<Element = Scalar>> TryFrom<SerializableTgswCiphertext<C>>
+    for TgswCiphertext<C>
+{
+    type Error = TgswError;
+
+    fn try_from(serialized: SerializableTgswCiphertext<C>) -> Result<Self, TgswError> {
+        Self::try_from_container(
+            serialized.data,
+            serialized.lwe_size,
+            serialized.ciphertext_modulus,
+        )
+    }
+}
+
+pub type TgswCiphertextOwned<Scalar> = TgswCiphertext<Vec<Scalar>>;
+pub type TgswCiphertextView<'data, Scalar> = TgswCiphertext<&'data [Scalar]>;
+pub type TgswCiphertextMutView<'data, Scalar> = TgswCiphertext<&'data mut [Scalar]>;
+
+impl<Scalar: UnsignedInteger, C: Container<Element = Scalar>> AsRef<[Scalar]>
+    for TgswCiphertext<C>
+{
+    fn as_ref(&self) -> &[Scalar] {
+        self.data.as_ref()
+    }
+}
+
+impl<Scalar: UnsignedInteger, C: ContainerMut<Element = Scalar>> AsMut<[Scalar]>
+    for TgswCiphertext<C>
+{
+    fn as_mut(&mut self) -> &mut [Scalar] {
+        self.data.as_mut()
+    }
+}
+
+impl<Scalar: UnsignedInteger, C: Container<Element = Scalar>> TgswCiphertext<C> {
+    //Wrap a container of l(n+1) rows of lwe_size = n+1 values, without copying
+    //Panics if the container length is not a non-zero multiple of lwe_size^2, see try_from_container
+    pub fn from_container(
+        container: C,
+        lwe_size: LweSize,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+    ) -> Self {
+        Self::try_from_container(container, lwe_size, ciphertext_modulus)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    //Same as from_container, for containers that come from outside (e.g. deserialized)
+    pub fn try_from_container(
+        container: C,
+        lwe_size: LweSize,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+    ) -> Result<Self, TgswError> {
+        let container_len = container.container_len();
+        match lwe_size.0.checked_mul(lwe_size.0) {
+            Some(matrix_len) if container_len > 0 && container_len.is_multiple_of(matrix_len) => {
+                Ok(Self {
+                    data: container,
+                    lwe_size,
+                    ciphertext_modulus,
+                })
+            }
+            _ => Err(TgswError::InvalidContainerLength {
+                container_len,
+                lwe_size,
+            }),
+        }
+    }
+
+    pub fn into_container(self) -> C {
+        self.data
+    }
+
+    pub fn as_view(&self) -> TgswCiphertextView<'_, Scalar> {
+        TgswCiphertext::from_container(self.as_ref(), self.lwe_size, self.ciphertext_modulus)
+    }
+
+    //The TGSW rows, as a list of l(n+1) LWE ciphertexts
+    pub fn as_lwe_ciphertext_list(&self) -> LweCiphertextListView<'_, Scalar> {
+        LweCiphertextList::from_container(self.as_ref(), self.lwe_size, self.ciphertext_modulus)
+    }
+
+    pub fn lwe_size(&self) -> LweSize {
+        self.lwe_size
+    }
+
+    //The decomposition level l, the ciphertext has l(n+1) rows
+    pub fn decomp_level(&self) -> usize {
+        self.data.container_len() / (self.lwe_size.0 * self.lwe_size.0)
+    }
+
+    pub fn ciphertext_modulus(&self) -> CiphertextModulus<Scalar> {
+        self.ciphertext_modulus
+    }
+
+    fn rows(&self) -> std::slice::ChunksExact<'_, Scalar> {
+        self.as_ref().chunks_exact(self.lwe_size.0)
+    }
+}
+
+impl<Scalar: UnsignedInteger, C: ContainerMut<Element = Scalar>> TgswCiphertext<C> {
+    pub fn as_mut_view(&mut self) -> TgswCiphertextMutView<'_, Scalar> {
+        let lwe_size = self.lwe_size;
+        let ciphertext_modulus = self.ciphertext_modulus;
+        TgswCiphertext::from_container(self.as_mut(), lwe_size, ciphertext_modulus)
+    }
+}
+
+impl TgswCiphertext<Vec<u32>> {
+    //Create a new TGSW ciphertext that encrypts a message m in Z/PZ under the boolean client key
     pub fn new_encrypt(m: u32, tgsw_params: &TgswParams, client_key: &ClientKey) -> Self {
-        let n = tgsw_params.tfhe_params.lwe_dimension.0;
-        //let B = 1 << params.pbs_base_log.0;
-        //let l = params.pbs_level.0;
-        let decomp_level = tgsw_params.decomp_level;
-        let decomp_base = tgsw_params.decomp_base;
+        let (lwe_sk, encryption_noise) = client_key.encryption_key_and_noise();
 
-        let q: u64 = 1 << 32;
+        OB_BOOLEAN_ENGINE.with(|engine_cell| {
+            let eng = &mut engine_cell.borrow_mut() as &mut BooleanEngine;
+            Self::new_encrypt_with_key(
+                m,
+                tgsw_params.decomp_base,
+                tgsw_params.decomp_level,
+                &lwe_sk,
+                encryption_noise,
+                CiphertextModulus::new_native(),
+                &mut eng.encryption_generator,
+            )
+        })
+    }
 
-        //Create a matrix of size (l*(n+1))*(n+1)
-        let mut ct_matrix = vec![vec![0u32; n + 1]; decomp_level * (n + 1)];
-        let gadget_matrix = get_gadget_matrix(n, decomp_base, decomp_level, q);
-        //gadget_matrix.display();
+    /*Create a new TGSW ciphertext that encrypts a message m in Z/PZ under the boolean public key
+    This lets a party that does not hold the client key (e.g. the circuit author) create the gate parameters
 
-        //TGSW(m) = Z + mGT where Z is matrix of (n+1)*l rows and (n+1) columns with each row being an ecryption of zero and GT is the gadget matrix
-        for i in 0..decomp_level * (n + 1) {
-            let temp_0 = client_key.encrypt_abs(0u32);
+    Noise analysis: the rows of Z are public-key encryptions of zero, i.e. sums of random subsets (bits b_rj)
+    of the same k = (n+1)*32+128 encryptions of zero e_j in the public key. The rows are therefore correlated,
+    and the digit part of the external product noise (see DecompositionKind) becomes
+        sum_j (sum_r d_r * b_rj) * e_j,   Var ~ k/4 * s^2 * (sum_r d_r^2 + (sum_r d_r)^2)
+    - Unsigned digits have a mean of (B-1)/2, so the (sum_r d_r)^2 term dominates
+    - Balanced digits are centered, which gives ~ k/2 * s^2 * sum_r d_r^2, i.e. k/2 times the secret key case
+    Measured external product noise (standard deviation on the torus, M = 1):
+    - DEFAULT_PARAMETERS (small LWE key, s = 5.86e-6): the public-key rows have ~1.3e-3,
+      B = 64, l = 2 gives ~0.3 and B = 4, l = 4 balanced ~9.3e-2, so the obfuscated gates would fail often
+    - DEFAULT_PARAMETERS_KS_PBS (EncryptionKeyChoice::Big, s = 9.3e-10): the public-key rows have ~1.6e-7,
+      B = 64, l = 2 gives ~1.2e-2 (unsigned) and ~1.9e-3 (balanced, same as with the client key)
+    So the parameter sets with EncryptionKeyChoice::Small are rejected (SmallEncryptionKey), the public-key gate parameters
+    should use a parameter set with EncryptionKeyChoice::Big and the balanced decomposition.
+    The public key must encrypt under the key of tgsw_params (KeyDimensionMismatch otherwise).
+    Each row costs one public-key encryption (O(k*n)), so this takes minutes per TGSW ciphertext.
+    */
+    pub fn new_encrypt_with_public_key(
+        m: u32,
+        tgsw_params: &TgswParams,
+        public_key: &PublicKey,
+    ) -> Result<Self, TgswError> {
+        if matches!(
+            tgsw_params.tfhe_params.encryption_key_choice,
+            EncryptionKeyChoice::Small
+        ) {
+            return Err(TgswError::SmallEncryptionKey);
+        }
+        let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+        let mut z_rows: Vec<u32> =
+            Vec::with_capacity(tgsw_params.decomp_level * lwe_size.0 * lwe_size.0);
+        for _ in 0..tgsw_params.decomp_level * lwe_size.0 {
+            let temp_0 = public_key.encrypt_abs(0u32);
 
             //Extract the LWE vector from the LWE ciphertext
             let temp_0_lwe = match temp_0 {
                 Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-                _ => panic!("Expected encrypted ciphertext"),
+                Ciphertext::Trivial(_) => return Err(TgswError::TrivialPublicKeyEncryption),
             };
+            if temp_0_lwe.lwe_size() != lwe_size {
+                return Err(TgswError::KeyDimensionMismatch {
+                    expected: lwe_size.to_lwe_dimension(),
+                    found: temp_0_lwe.lwe_size().to_lwe_dimension(),
+                });
+            }
+            z_rows.extend_from_slice(temp_0_lwe.as_ref());
+        }
 
-            //Further extract the raw data vector from the LWE ciphertext object
-            let temp_0_vec = temp_0_lwe.into_container();
+        Ok(Self::from_encryptions_of_zero(
+            m,
+            tgsw_params.decomp_base,
+            tgsw_params.decomp_level,
+            z_rows,
+            lwe_size,
+            CiphertextModulus::new_native(),
+        ))
+    }
 
-            //TGSW(m) = Z + mGT
-            for j in 0..n + 1 {
-                ct_matrix[i][j] =
-                    temp_0_vec[j].wrapping_add(m.wrapping_mul(gadget_matrix.matrix[i][j]));
-            }
+    //Same as new_encrypt_with_public_key, with the compressed public key
+    //The key is decompressed once, rather than regenerating its masks for each of the l(n+1) rows
+    pub fn new_encrypt_with_compressed_public_key(
+        m: u32,
+        tgsw_params: &TgswParams,
+        compressed_public_key: &CompressedPublicKey,
+    ) -> Result<Self, TgswError> {
+        Self::new_encrypt_with_public_key(m, tgsw_params, &compressed_public_key.decompress())
+    }
+}
+
+impl<C: Container<Element = u32>> TgswCiphertext<C> {
+    /*Decrypt the TGSW ciphertext under the boolean client key (client side, for debugging)
+    The decomposition parameters are not stored in the ciphertext, so they are taken from tgsw_params
+    See decrypt_with_key for how the message is recovered
+    */
+    pub fn decrypt(&self, tgsw_params: &TgswParams, client_key: &ClientKey) -> u32 {
+        let (lwe_sk, _) = client_key.encryption_key_and_noise();
+        self.decrypt_with_key(tgsw_params.decomp_base, tgsw_params.decomp_level, &lwe_sk)
+    }
+
+    /*Internal product TGSW(A1) x TGSW(A2) = TGSW(A1*A2), where tgsw_params are the decomposition parameters of self
+    See internal_product_with_decomposition for the noise, self usually needs a finer decomposition than other
+    */
+    pub fn internal_product<OtherCont: Container<Element = u32>>(
+        &self,
+        other: &TgswCiphertext<OtherCont>,
+        tgsw_params: &TgswParams,
+    ) -> TgswCiphertextOwned<u32> {
+        self.internal_product_with_decomposition(
+            other,
+            tgsw_params.decomp_base,
+            tgsw_params.decomp_level,
+            tgsw_params.decomp_kind,
+        )
+    }
+}
+
+impl<Scalar: UnsignedTorus> TgswCiphertextOwned<Scalar> {
+    //Create a new TGSW ciphertext that encrypts a message m in Z/PZ under an LWE secret key of dimension n
+    //with the decomposition base B and level l, for any torus type and ciphertext modulus
+    pub fn new_encrypt_with_key<KeyCont, NoiseDistribution, Gen>(
+        m: Scalar,
+        decomp_base: u32,
+        decomp_level: usize,
+        lwe_secret_key: &LweSecretKey<KeyCont>,
+        noise_distribution: NoiseDistribution,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+        generator: &mut EncryptionRandomGenerator<Gen>,
+    ) -> Self
+    where
+        Scalar: Encryptable<Uniform, NoiseDistribution>,
+        NoiseDistribution: Distribution,
+        KeyCont: Container<Element = Scalar>,
+        Gen: ByteRandomGenerator,
+    {
+        let n = lwe_secret_key.lwe_dimension().0;
+
+        //Z is a list of (n+1)*l encryptions of zero
+        let mut z_list = LweCiphertextList::new(
+            Scalar::ZERO,
+            lwe_secret_key.lwe_dimension().to_lwe_size(),
+            LweCiphertextCount(decomp_level * (n + 1)),
+            ciphertext_modulus,
+        );
+        encrypt_lwe_ciphertext_list(
+            lwe_secret_key,
+            &mut z_list,
+            &PlaintextList::new(Scalar::ZERO, PlaintextCount(decomp_level * (n + 1))),
+            noise_distribution,
+            generator,
+        );
+
+        Self::from_encryptions_of_zero(
+            m,
+            decomp_base,
+            decomp_level,
+            z_list.into_container(),
+            lwe_secret_key.lwe_dimension().to_lwe_size(),
+            ciphertext_modulus,
+        )
+    }
+
+    //Create a new TGSW ciphertext that encrypts a message m in Z/PZ with an LWE public key, for any torus type
+    //The rows of Z are public-key encryptions of zero (see new_encrypt_with_public_key for the noise)
+    pub fn new_encrypt_with_lwe_public_key<KeyCont, Gen>(
+        m: Scalar,
+        decomp_base: u32,
+        decomp_level: usize,
+        lwe_public_key: &LwePublicKey<KeyCont>,
+        generator: &mut SecretRandomGenerator<Gen>,
+    ) -> Self
+    where
+        KeyCont: Container<Element = Scalar>,
+        Gen: ByteRandomGenerator,
+    {
+        let lwe_size = lwe_public_key.lwe_size();
+        let ciphertext_modulus = lwe_public_key.ciphertext_modulus();
+
+        let z_rows = (0..decomp_level * lwe_size.0)
+            .flat_map(|_| {
+                let mut z_row = LweCiphertext::new(Scalar::ZERO, lwe_size, ciphertext_modulus);
+                encrypt_lwe_ciphertext_with_public_key(
+                    lwe_public_key,
+                    &mut z_row,
+                    Plaintext(Scalar::ZERO),
+                    generator,
+                );
+                z_row.into_container()
+            })
+            .collect();
+
+        Self::from_encryptions_of_zero(
+            m,
+            decomp_base,
+            decomp_level,
+            z_rows,
+            lwe_size,
+            ciphertext_modulus,
+        )
+    }
+
+    //TGSW(m) = Z + mGT, where Z is a list of (n+1)*l encryptions of zero
+    //and the row i of GT has the single non-zero value g_(i mod l) at column i/l
+    fn from_encryptions_of_zero(
+        m: Scalar,
+        decomp_base: u32,
+        decomp_level: usize,
+        mut z_rows: Vec<Scalar>,
+        lwe_size: LweSize,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+    ) -> Self {
+        let gadget = gadget_vector(decomp_base, decomp_level, ciphertext_modulus);
+
+        for (i, ct_row) in z_rows.chunks_exact_mut(lwe_size.0).enumerate() {
+            let j = i / decomp_level;
+            let m_g = mod_mul(m, gadget[i % decomp_level], ciphertext_modulus);
+            ct_row[j] = mod_add(ct_row[j], m_g, ciphertext_modulus);
         }
-        Self {
-            ct_matrix, /*, dec_base, dec_level , params*/
+
+        Self::from_container(z_rows, lwe_size, ciphertext_modulus)
+    }
+}
+
+impl<Scalar: UnsignedTorus, C: Container<Element = Scalar>> TgswCiphertext<C> {
+    //External product (see ext_product) with an LWE ciphertext of any torus type and ciphertext modulus
+    pub fn ext_product_lwe<LweCont: Container<Element = Scalar>>(
+        &self,
+        ct_lwe: &LweCiphertext<LweCont>,
+        decomp_base: u32,
+        decomp_level: usize,
+        decomp_kind: DecompositionKind,
+    ) -> LweCiphertextOwned<Scalar> {
+        let mut result =
+            LweCiphertext::new(Scalar::ZERO, ct_lwe.lwe_size(), self.ciphertext_modulus);
+        let mut digits = vec![Scalar::ZERO; decomp_level];
+        self.ext_product_lwe_add_assign(
+            &mut result,
+            ct_lwe,
+            decomp_base,
+            decomp_level,
+            decomp_kind,
+            &mut digits,
+        );
+        result
+    }
+
+    /*Same as ext_product_lwe, accumulated into output (output += TGSW x ct_lwe) without allocating,
+    digits is the scratch space for the l digits of a coefficient (see oblivious_evaluator::ObliviousEvaluator)
+    */
+    pub fn ext_product_lwe_add_assign<
+        LweCont: Container<Element = Scalar>,
+        OutputCont: ContainerMut<Element = Scalar>,
+    >(
+        &self,
+        output: &mut LweCiphertext<OutputCont>,
+        ct_lwe: &LweCiphertext<LweCont>,
+        decomp_base: u32,
+        decomp_level: usize,
+        decomp_kind: DecompositionKind,
+        digits: &mut [Scalar],
+    ) {
+        let l = decomp_level;
+        let lwe_size = ct_lwe.lwe_size().0;
+        assert_eq!(
+            self.lwe_size,
+            ct_lwe.lwe_size(),
+            "Mismatch between the TGSW and LWE ciphertext sizes"
+        );
+        assert_eq!(
+            self.lwe_size,
+            output.lwe_size(),
+            "Mismatch between the TGSW and output LWE ciphertext sizes"
+        );
+        assert_eq!(
+            self.decomp_level(),
+            l,
+            "Mismatch between the TGSW ciphertext rows and the LWE size times the decomposition level"
+        );
+        assert_eq!(
+            self.ciphertext_modulus,
+            ct_lwe.ciphertext_modulus(),
+            "Mismatch between the TGSW and LWE ciphertext moduli"
+        );
+        assert_eq!(digits.len(), l, "Expected a scratch space of l digits");
+
+        //scale each coefficient by the factor of B^l/q, decompose it into l digits, and accumulate
+        //the matching l rows of the TGSW matrix weighted by those digits.
+        //This computes decomposed_lwe_ct * TGSW matrix row by row, so each row is streamed once from memory.
+        //The rows with a non-zero digit are gathered in blocks of ROW_BLOCK rows that are accumulated in a single
+        //pass over the output (see mul_add_rows), a vector kernel that loads and stores the output once per block.
+        //All the arithmetic is exact (mod q), so the result is bit-identical to the column-wise product.
+        let decomposer =
+            GadgetDecomposer::new(decomp_base, l, decomp_kind, self.ciphertext_modulus);
+        let result = output.as_mut();
+        let mut block: [(Scalar, &[Scalar]); ROW_BLOCK] = [(Scalar::ZERO, &[]); ROW_BLOCK];
+        let mut block_len = 0;
+        let row_blocks = self.as_ref().chunks_exact(l * lwe_size);
+        for (&coef, row_block) in ct_lwe.as_ref().iter().zip(row_blocks) {
+            decomposer.decompose_into(coef, digits);
+
+            for (row, &digit) in row_block.chunks_exact(lwe_size).zip(digits.iter()) {
+                if digit != Scalar::ZERO {
+                    block[block_len] = (digit, row);
+                    block_len += 1;
+                    if block_len == ROW_BLOCK {
+                        mul_add_rows(result, &block, self.ciphertext_modulus);
+                        block_len = 0;
+                    }
+                }
+            }
         }
+        mul_add_rows(result, &block[..block_len], self.ciphertext_modulus);
     }
 
-    /*Perform an external product between TGSW and TLWE ciphertexts
-    for C1 = TGSW(M1) and c2 = TLWE(m2), the external product is defined as
-    cr = GT^-1(c2) * C1 = GT^-1(c2) * (Z+M1.GT) =  GT^-1(c2) * Z + M1 GT^-1(c2) * GT = LWE(0) + M1c2
-    Which means cr is an LWE encryption of M1*m2, given a small "limited" round and multiplicative noise
-    See: Marc Joye, "Guide to Fully Homomorphic Encryption over the [Discretized] Torus" : https://eprint.iacr.org/2021/1402
+    /*Homomorphic addition TGSW(A1) + TGSW(A2) = TGSW(A1+A2)
+    Both ciphertexts must use the same key, decomposition parameters and ciphertext modulus.
+    The row noises add up, so the external product noise of the digit part doubles in variance
     */
-    pub fn ext_product(
+    pub fn add<OtherCont: Container<Element = Scalar>>(
         &self,
-        ct_lwe: &Ciphertext,
-        tgsw_params: &TgswParams, /*, server_key:&ServerKey*/
-    ) -> Ciphertext {
-        let n = tgsw_params.tfhe_params.lwe_dimension.0;
+        other: &TgswCiphertext<OtherCont>,
+    ) -> TgswCiphertextOwned<Scalar> {
+        let mut result = self.to_owned();
+        result.add_assign(other);
+        result
+    }
 
-        //let B = 1 << self.params.pbs_base_log.0;
-        let B = tgsw_params.decomp_base;
+    /*Multiplication by a clear integer k*TGSW(A) = TGSW(k*A)
+    The row noises are multiplied by k, so the digit part of the external product noise is multiplied by k
+    (k^2 in variance), as is the rounding part since the message is now k*A
+    */
+    pub fn scalar_mul(&self, k: Scalar) -> TgswCiphertextOwned<Scalar> {
+        let mut result = self.to_owned();
+        result.scalar_mul_assign(k);
+        result
+    }
 
-        //let l = self.params.pbs_level.0;
-        let l = tgsw_params.decomp_level;
+    /*Internal product TGSW(A1) x TGSW(A2) = TGSW(A1*A2)
+    Each row of TGSW(A2) = Z2 + A2*GT is an LWE ciphertext of phase e_i + A2*<GT_i, (-s, 1)>, so its external product
+    with TGSW(A1) is an LWE ciphertext of phase A1*e_i + A1*A2*<GT_i, (-s, 1)> + e_ext, i.e. row i of TGSW(A1*A2).
+    decomp_base, decomp_level and decomp_kind are the decomposition parameters of self, the result has the
+    decomposition parameters of other.
 
-        let q: u64 = 1u64 << 32;
+    Noise analysis: row i of the result carries A1*e_i + e_ext, where e_ext is the external product noise of self
+    (see DecompositionKind), i.e. Var = A1^2*s^2 + l1(n+1)*E[d^2]*s^2 + A1^2*(1+n/2)*e_round^2.
+    The rounding error e_round ~ q/(2*B1^l1) lands on rows whose gadget entry is only q/B2^l2, and those rows are later
+    multiplied by digits up to B2 in the external product. So B1^l1 should be much larger than B2^l2 * n, ideally
+    B1^l1 = q (e.g. B1 = 16, l1 = 8 on the u32 torus), which makes the rounding exact.
+    The digit part is then l1(n+1)*E[d^2] times the fresh row noise, which is only usable with a small noise
+    standard deviation, e.g. DEFAULT_PARAMETERS_KS_PBS (EncryptionKeyChoice::Big), not with DEFAULT_PARAMETERS.
+    Measured external product noise (standard deviation on the torus) of TGSW(3) (B1 = 16, l1 = 8) x TGSW(2)
+    (B2 = 64, l2 = 2), both balanced: ~1.0e-2 with DEFAULT_PARAMETERS_KS_PBS, the same as for 3*TGSW(2),
+    and ~0.3 with DEFAULT_PARAMETERS
+    */
+    pub fn internal_product_with_decomposition<OtherCont: Container<Element = Scalar>>(
+        &self,
+        other: &TgswCiphertext<OtherCont>,
+        decomp_base: u32,
+        decomp_level: usize,
+        decomp_kind: DecompositionKind,
+    ) -> TgswCiphertextOwned<Scalar> {
+        let data = other
+            .as_lwe_ciphertext_list()
+            .iter()
+            .flat_map(|row_lwe| {
+                self.ext_product_lwe(&row_lwe, decomp_base, decomp_level, decomp_kind)
+                    .into_container()
+            })
+            .collect();
 
-        //Create a vector of size l(n+1) to stored the decomposed LWE ciphertext
+        TgswCiphertext::from_container(data, other.lwe_size, other.ciphertext_modulus)
+    }
 
-        let ct_lwe_obj = match ct_lwe.clone() {
-            Ciphertext::Encrypted(ct_lwe) => ct_lwe,
-            _ => panic!("Expected encrypted ciphertext"),
-        };
-        let mut ct_lwe_vec = ct_lwe_obj.into_container();
+    pub fn to_owned(&self) -> TgswCiphertextOwned<Scalar> {
+        TgswCiphertext::from_container(
+            self.as_ref().to_vec(),
+            self.lwe_size,
+            self.ciphertext_modulus,
+        )
+    }
 
-        //scale the vector by the factor of B^l/q
-        let bl = B.pow(l as u32) as u64;
-        let q_2 = q / 2;
-        for i in 0..ct_lwe_vec.len() {
-            //ct_lwe_vec[i] = (ct_lwe_vec[i] as f64 * (B as f64).powi(l as i32) / q as f64).round() as u32; //This line was working 16-jan-2025
-            ct_lwe_vec[i] = ((ct_lwe_vec[i] as u64 * bl + q_2) / q) as u32; //This line is working 16-jan-2025 // It rescales to B^l/q while avoding floating point arithmetic
+    /*Decrypt the TGSW ciphertext with the LWE secret key it was encrypted under
+    The last row of GT has its gadget entry g_(l-1) = q/B^l on the body, so the last row of the TGSW ciphertext
+    is an LWE encryption of m*q/B^l, and m = round(phase/(q/B^l)) mod B^l.
+    This is the finest gadget level, so any m < B^l is recovered as long as the noise of that row is below q/(2B^l)
+    */
+    pub fn decrypt_with_key<KeyCont>(
+        &self,
+        decomp_base: u32,
+        decomp_level: usize,
+        lwe_secret_key: &LweSecretKey<KeyCont>,
+    ) -> Scalar
+    where
+        KeyCont: Container<Element = Scalar>,
+    {
+        let n = lwe_secret_key.lwe_dimension().0;
+        assert_eq!(
+            self.lwe_size,
+            lwe_secret_key.lwe_dimension().to_lwe_size(),
+            "Mismatch between the TGSW ciphertext and LWE secret key sizes"
+        );
+        assert_eq!(
+            self.decomp_level(),
+            decomp_level,
+            "Mismatch between the TGSW ciphertext rows and the LWE size times the decomposition level"
+        );
+
+        let phase = lwe_phase(
+            self.rows()
+                .nth(n * decomp_level + decomp_level - 1)
+                .unwrap(),
+            lwe_secret_key.as_ref(),
+            self.ciphertext_modulus,
+        );
+
+        let q = effective_modulus(self.ciphertext_modulus);
+        let bl = decomposition_precision(decomp_base, decomp_level, q);
+        let g = q / bl;
+        let phase: u128 = phase.cast_into();
+        Scalar::cast_from(((phase + g / 2) / g) % bl)
+    }
+}
+
+impl<Scalar: UnsignedTorus, C: ContainerMut<Element = Scalar>> TgswCiphertext<C> {
+    pub fn add_assign<OtherCont: Container<Element = Scalar>>(
+        &mut self,
+        other: &TgswCiphertext<OtherCont>,
+    ) {
+        assert_eq!(
+            (self.lwe_size, self.decomp_level()),
+            (other.lwe_size, other.decomp_level()),
+            "Mismatch between the TGSW ciphertext sizes"
+        );
+        assert_eq!(
+            self.ciphertext_modulus, other.ciphertext_modulus,
+            "Mismatch between the TGSW ciphertext moduli"
+        );
+        let ciphertext_modulus = self.ciphertext_modulus;
+        mul_add_row(
+            self.as_mut(),
+            other.as_ref(),
+            Scalar::ONE,
+            ciphertext_modulus,
+        );
+    }
+
+    pub fn scalar_mul_assign(&mut self, k: Scalar) {
+        let ciphertext_modulus = self.ciphertext_modulus;
+        self.as_mut()
+            .iter_mut()
+            .for_each(|c| *c = mod_mul(*c, k, ciphertext_modulus));
+    }
+}
+
+/*A seeded (compressed) TGSW ciphertext
+Only the l(n+1) LWE bodies are stored, the masks are regenerated from a CSPRNG seed when decompressing.
+Row i of TGSW(m) = Z + mGT has a single non-zero gadget entry g_i = q/B^(i-jl+1) at column j = i/l:
+- For j < n, adding m*g_i to the mask coefficient j is the same as an LWE encryption of -m*g_i*s_j under a fresh uniform mask
+- For j = n, the gadget entry sits on the body, so the row is an LWE encryption of m*g_i
+Each row is therefore a regular (seeded) LWE encryption, and the decompressed ciphertext has the same distribution as TgswCiphertext::new_encrypt
+As for the tfhe seeded entities, only native and power of two ciphertext moduli are supported
+*/
+#[derive(Serialize, Deserialize, Versionize)]
+#[versionize(SeededTgswCiphertextVersions)]
+pub struct SeededTgswCiphertext<Scalar: UnsignedInteger = u32> {
+    ct_list: SeededLweCiphertextListOwned<Scalar>,
+}
+
+impl SeededTgswCiphertext<u32> {
+    //Create a new seeded TGSW ciphertext that encrypts a message m in Z/PZ under the boolean client key
+    pub fn new_encrypt(m: u32, tgsw_params: &TgswParams, client_key: &ClientKey) -> Self {
+        let (lwe_sk, encryption_noise) = client_key.encryption_key_and_noise();
+
+        let mut seeder = new_seeder();
+        Self::new_encrypt_with_key(
+            m,
+            tgsw_params.decomp_base,
+            tgsw_params.decomp_level,
+            &lwe_sk,
+            encryption_noise,
+            CiphertextModulus::new_native(),
+            seeder.as_mut(),
+        )
+        .expect("The native modulus can be seeded")
+    }
+}
+
+impl<Scalar: UnsignedTorus> SeededTgswCiphertext<Scalar> {
+    //Create a new seeded TGSW ciphertext that encrypts a message m in Z/PZ under an LWE secret key,
+    //for any torus type and a native or power of two ciphertext modulus (other moduli are rejected)
+    pub fn new_encrypt_with_key<KeyCont, NoiseDistribution, NoiseSeeder>(
+        m: Scalar,
+        decomp_base: u32,
+        decomp_level: usize,
+        lwe_secret_key: &LweSecretKey<KeyCont>,
+        noise_distribution: NoiseDistribution,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+        noise_seeder: &mut NoiseSeeder,
+    ) -> Result<Self, TgswError>
+    where
+        Scalar: Encryptable<Uniform, NoiseDistribution>,
+        NoiseDistribution: Distribution,
+        KeyCont: Container<Element = Scalar>,
+        NoiseSeeder: Seeder + ?Sized,
+    {
+        if !ciphertext_modulus.is_compatible_with_native_modulus()
+            && !ciphertext_modulus.is_power_of_two()
+        {
+            return Err(TgswError::UnsupportedCiphertextModulus {
+                modulus: ciphertext_modulus.get_custom_modulus(),
+            });
         }
+        let n = lwe_secret_key.lwe_dimension().0;
+        let gadget = gadget_vector(decomp_base, decomp_level, ciphertext_modulus);
 
-        let decomposed_lwe_ct = vec_decompose(&ct_lwe_vec, B, l);
+        //tfhe takes the plaintexts of non-native power of two moduli in Z/qZ and moves them to the MSBs itself
+        let torus_scaling = ciphertext_modulus.get_power_of_two_scaling_to_native_torus();
 
-        //The external product is provided by performing decomposed_lwe_ct * ct_matrix
-        let mut result = vec![0u32; n + 1];
-        for i in 0..n + 1 {
-            for j in 0..l * (n + 1) {
-                result[i] =
-                    result[i].wrapping_add(decomposed_lwe_ct[j].wrapping_mul(self.ct_matrix[j][i]));
-            }
+        //The plaintext of each row, as described above
+        let plaintexts: Vec<Scalar> = (0..decomp_level * (n + 1))
+            .map(|i| {
+                let j = i / decomp_level;
+                let m_g = m.wrapping_mul(gadget[i % decomp_level]);
+                let plaintext = if j < n {
+                    m_g.wrapping_mul(lwe_secret_key.as_ref()[j]).wrapping_neg()
+                } else {
+                    m_g
+                };
+                plaintext / torus_scaling
+            })
+            .collect();
+
+        let mut ct_list = SeededLweCiphertextList::new(
+            Scalar::ZERO,
+            lwe_secret_key.lwe_dimension().to_lwe_size(),
+            LweCiphertextCount(decomp_level * (n + 1)),
+            noise_seeder.seed().into(),
+            ciphertext_modulus,
+        );
+
+        encrypt_seeded_lwe_ciphertext_list(
+            lwe_secret_key,
+            &mut ct_list,
+            &PlaintextList::from_container(plaintexts),
+            noise_distribution,
+            noise_seeder,
+        );
+
+        Ok(Self { ct_list })
+    }
+
+    //Regenerate the masks from the seed and return the full TGSW ciphertext, to be used by the server
+    pub fn decompress(self) -> TgswCiphertextOwned<Scalar> {
+        let lwe_size = self.ct_list.lwe_size();
+        let ciphertext_modulus = self.ct_list.ciphertext_modulus();
+        let ct_list = self.ct_list.decompress_into_lwe_ciphertext_list();
+
+        TgswCiphertext::from_container(ct_list.into_container(), lwe_size, ciphertext_modulus)
+    }
+}
+
+impl<C: Container> Named for TgswCiphertext<C>
+where
+    C::Element: UnsignedInteger,
+{
+    const NAME: &'static str = "ohlg::TgswCiphertext";
+}
+
+impl<Scalar: UnsignedInteger> Named for SeededTgswCiphertext<Scalar> {
+    const NAME: &'static str = "ohlg::SeededTgswCiphertext";
+}
+
+//The expected shape of a TGSW ciphertext, to check the deserialized ones (see tfhe::safe_serialization)
+#[derive(Copy, Clone)]
+pub struct TgswCiphertextConformanceParams<Scalar: UnsignedInteger> {
+    pub lwe_dimension: LweDimension,
+    pub decomp_level: usize,
+    pub ciphertext_modulus: CiphertextModulus<Scalar>,
+}
+
+impl From<&TgswParams> for TgswCiphertextConformanceParams<u32> {
+    fn from(tgsw_params: &TgswParams) -> Self {
+        Self {
+            lwe_dimension: tgsw_params.lwe_dimension(),
+            decomp_level: tgsw_params.decomp_level,
+            ciphertext_modulus: CiphertextModulus::new_native(),
         }
-        let ciphertext_modulus = CiphertextModulus::new_native();
-        let x = LweCiphertext::from_container(result, ciphertext_modulus);
+    }
+}
 
-        //CAUTION: Bootstrapping here can lead to unintended results (i.e. reducess -3/8 to -1/8).
-        //The bootstrapping should be done at the end of the computation.
+impl<Scalar: UnsignedInteger, C: Container<Element = Scalar>> ParameterSetConformant
+    for TgswCiphertext<C>
+{
+    type ParameterSet = TgswCiphertextConformanceParams<Scalar>;
 
-        /*OB_BOOLEAN_ENGINE.with(|engine_cell| {
-             let eng = & mut engine_cell.borrow_mut() as &mut BooleanEngine;
-             let bootstrapper = &mut eng.bootstrapper;
-             bootstrapper.apply_bootstrapping_pattern(x, &server_key)
+    fn is_conformant(&self, parameter_set: &Self::ParameterSet) -> bool {
+        let lwe_size = parameter_set.lwe_dimension.to_lwe_size();
 
-        })*/
-        Ciphertext::Encrypted(x)
+        self.lwe_size == lwe_size
+            && self.data.container_len() == parameter_set.decomp_level * lwe_size.0 * lwe_size.0
+            && check_encrypted_content_respects_mod(&self.data, parameter_set.ciphertext_modulus)
+            && self.ciphertext_modulus == parameter_set.ciphertext_modulus
     }
 }
 
-// Function to decompose a single integer `n` into `l` digits in base `B`
-pub fn decompose(n: u32, B: u32, l: usize) -> Vec<u32> {
-    let mut n = n; // Create a mutable copy of n
-    let mut decomposed: Vec<u32> = Vec::with_capacity(l);
+impl<Scalar: UnsignedInteger> ParameterSetConformant for SeededTgswCiphertext<Scalar> {
+    type ParameterSet = TgswCiphertextConformanceParams<Scalar>;
+
+    fn is_conformant(&self, parameter_set: &Self::ParameterSet) -> bool {
+        let lwe_size = parameter_set.lwe_dimension.to_lwe_size();
 
-    // Perform the decomposition
-    for _ in 0..l {
-        decomposed.push(n % B); // Get the remainder
-        n = n / B; // Integer division
+        self.ct_list.lwe_size() == lwe_size
+            && self.ct_list.lwe_ciphertext_count().0 == parameter_set.decomp_level * lwe_size.0
+            && check_encrypted_content_respects_mod(&self.ct_list, parameter_set.ciphertext_modulus)
+            && self.ct_list.ciphertext_modulus() == parameter_set.ciphertext_modulus
     }
+}
+
+// The l non-zero gadget values g_t = q/B^(t+1), t = 0..l
+// Native-compatible moduli store the values in the MSBs, so q is taken as 2^(bits of Scalar) in that case
+// Panics if B^l exceeds q (see decomposition_precision)
+pub fn gadget_vector<Scalar: UnsignedInteger>(
+    B: u32,
+    l: usize,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) -> Vec<Scalar> {
+    let q = effective_modulus(ciphertext_modulus);
+    decomposition_precision(B, l, q);
+    let mut g = q;
+    (0..l)
+        .map(|_| {
+            g /= B as u128;
+            Scalar::cast_from(g)
+        })
+        .collect()
+}
 
-    // Reverse the list to match SageMath's behavior
-    decomposed.reverse();
+// The precision B^l of the decomposition, checked against the modulus q
+// B^l > q (e.g. TgswParams::new with l too large for a u32 torus) would make the last gadget values zero
+// and overflow the rounding of the external product, so it panics instead (see TgswParams::validate)
+fn decomposition_precision(B: u32, l: usize, q: u128) -> u128 {
+    u32::try_from(l)
+        .ok()
+        .and_then(|l| (B as u128).checked_pow(l))
+        .filter(|&bl| bl <= q)
+        .unwrap_or_else(|| panic!("The decomposition precision {B}^{l} exceeds the modulus {q}"))
+}
 
+// The modulus the raw Scalar values live in: 2^(bits of Scalar) for native-compatible moduli, q otherwise
+fn effective_modulus<Scalar: UnsignedInteger>(
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) -> u128 {
+    if ciphertext_modulus.is_compatible_with_native_modulus() {
+        1u128 << Scalar::BITS
+    } else {
+        ciphertext_modulus.get_custom_modulus()
+    }
+}
+
+// The phase b - <a, s> of an LWE ciphertext given as (a, b), in the raw Scalar representation
+fn lwe_phase<Scalar: UnsignedInteger>(
+    lwe: &[Scalar],
+    lwe_secret_key: &[Scalar],
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) -> Scalar {
+    let (mask, body) = lwe.split_at(lwe.len() - 1);
+    mask.iter()
+        .zip(lwe_secret_key.iter())
+        .fold(body[0], |acc, (&a, &s)| {
+            mod_sub(acc, mod_mul(a, s, ciphertext_modulus), ciphertext_modulus)
+        })
+}
+
+// Modular arithmetic helpers: wrapping for native-compatible moduli, mod q for custom moduli
+#[inline]
+fn mod_sub<Scalar: UnsignedInteger>(
+    a: Scalar,
+    b: Scalar,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) -> Scalar {
+    if ciphertext_modulus.is_compatible_with_native_modulus() {
+        a.wrapping_sub(b)
+    } else {
+        a.wrapping_sub_custom_mod(b, ciphertext_modulus.get_custom_modulus().cast_into())
+    }
+}
+
+#[inline]
+fn mod_add<Scalar: UnsignedInteger>(
+    a: Scalar,
+    b: Scalar,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) -> Scalar {
+    if ciphertext_modulus.is_compatible_with_native_modulus() {
+        a.wrapping_add(b)
+    } else {
+        a.wrapping_add_custom_mod(b, ciphertext_modulus.get_custom_modulus().cast_into())
+    }
+}
+
+#[inline]
+fn mod_mul<Scalar: UnsignedInteger>(
+    a: Scalar,
+    b: Scalar,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) -> Scalar {
+    if ciphertext_modulus.is_compatible_with_native_modulus() {
+        a.wrapping_mul(b)
+    } else {
+        a.wrapping_mul_custom_mod(b, ciphertext_modulus.get_custom_modulus().cast_into())
+    }
+}
+
+// Gadget decomposition (GT^-1) of the LWE coefficients, as used by the external product
+struct GadgetDecomposer<Scalar: UnsignedInteger> {
+    decomp_base: u32,
+    decomp_level: usize,
+    bl: u128,
+    decomp_kind: DecompositionKind,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+    //tfhe's signed decomposer, usable when B is a power of two and q is native-compatible
+    signed_decomposer: Option<SignedDecomposer<Scalar>>,
+}
+
+impl<Scalar: UnsignedInteger> GadgetDecomposer<Scalar> {
+    fn new(
+        decomp_base: u32,
+        decomp_level: usize,
+        decomp_kind: DecompositionKind,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+    ) -> Self {
+        let base_log = decomp_base.ilog2() as usize;
+        let signed_decomposer = (decomp_kind == DecompositionKind::Balanced
+            && decomp_base.is_power_of_two()
+            && ciphertext_modulus.is_compatible_with_native_modulus()
+            && base_log * decomp_level < Scalar::BITS)
+            .then(|| {
+                SignedDecomposer::new(
+                    DecompositionBaseLog(base_log),
+                    DecompositionLevelCount(decomp_level),
+                )
+            });
+
+        Self {
+            decomp_base,
+            decomp_level,
+            bl: decomposition_precision(
+                decomp_base,
+                decomp_level,
+                effective_modulus(ciphertext_modulus),
+            ),
+            decomp_kind,
+            ciphertext_modulus,
+            signed_decomposer,
+        }
+    }
+
+    // Write the l digits of the coefficient c to `digits`, most significant digit first
+    // Negative (balanced) digits are written as their representative mod q
+    #[inline]
+    fn decompose_into(&self, c: Scalar, digits: &mut [Scalar]) {
+        //tfhe rounds c to the closest multiple of q/B^l and yields the levels l..1, level i going with g = q/B^i
+        if let Some(signed_decomposer) = &self.signed_decomposer {
+            for term in signed_decomposer.decompose(c) {
+                digits[term.level().0 - 1] = term.value();
+            }
+            return;
+        }
+
+        // Rescale c in Z/qZ to round(c * B^l/q) in Z/(B^l)Z, avoiding floating point arithmetic
+        // The product c * B^l fits in a u128 as long as B^l <= 2^64
+        let q = effective_modulus(self.ciphertext_modulus);
+        let B = self.decomp_base as u128;
+        let c: u128 = c.cast_into();
+        let mut scaled = ((c * self.bl + q / 2) / q) % self.bl;
+
+        match self.decomp_kind {
+            DecompositionKind::Unsigned => {
+                for digit in digits.iter_mut().rev() {
+                    *digit = Scalar::cast_from(scaled % B); // Get the remainder
+                    scaled /= B; // Integer division
+                }
+            }
+            DecompositionKind::Balanced => {
+                //Digits >= B/2 are replaced by digit - B, carrying 1 to the next digit
+                //The carry out of the most significant digit is a multiple of B^l, i.e. of q, and is dropped
+                let mut carry = 0u128;
+                for digit in digits.iter_mut().rev() {
+                    let d = scaled % B + carry;
+                    scaled /= B;
+                    if 2 * d >= B {
+                        *digit = mod_neg_small(Scalar::cast_from(B - d), self.ciphertext_modulus);
+                        carry = 1;
+                    } else {
+                        *digit = Scalar::cast_from(d);
+                        carry = 0;
+                    }
+                }
+            }
+        }
+        debug_assert_eq!(digits.len(), self.decomp_level);
+    }
+}
+
+// -a mod q for a small a (a < q)
+#[inline]
+fn mod_neg_small<Scalar: UnsignedInteger>(
+    a: Scalar,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) -> Scalar {
+    if ciphertext_modulus.is_compatible_with_native_modulus() {
+        a.wrapping_neg()
+    } else {
+        a.wrapping_neg_custom_mod(ciphertext_modulus.get_custom_modulus().cast_into())
+    }
+}
+
+// Function to decompose a single integer `n` into `l` digits in base `B`
+pub fn decompose(n: u32, B: u32, l: usize) -> Vec<u32> {
+    let mut decomposed = vec![0u32; l];
+    decompose_into(n, B, &mut decomposed);
     decomposed
 }
 
-// Function to decompose each element of a vector `v` into base `B` with `l` digits
-pub fn vec_decompose(v: &[u32], B: u32, l: usize) -> Vec<u32> {
-    let mut decomposed_vec: Vec<u32> = Vec::with_capacity(v.len() * l);
+// Decompose `n` in base `B` into the pre-allocated `digits` buffer (one digit per entry)
+// The most significant digit comes first, to match SageMath's behavior
+pub fn decompose_into(n: u32, B: u32, digits: &mut [u32]) {
+    let mut n = n; // Create a mutable copy of n
+    for digit in digits.iter_mut().rev() {
+        *digit = n % B; // Get the remainder
+        n /= B; // Integer division
+    }
+}
 
-    // Iterate over each element in the input vector `v`
-    v.iter().for_each(|&val| {
-        let mut decomposed_val = decompose(val, B, l);
-        decomposed_vec.append(&mut decomposed_val); // Extend the result with the decomposed value
-    });
+// The number of TGSW rows accumulated in one pass over the output of the external product (see mul_add_rows)
+const ROW_BLOCK: usize = 4;
 
-    decomposed_vec
+// Accumulate acc += sum of digit * row over the (digit, row) pairs (mod q), the blocked kernel of the external product
+// A full block of native rows is accumulated in a single pass, each output value is loaded and stored once
+#[inline]
+fn mul_add_rows<Scalar: UnsignedInteger>(
+    acc: &mut [Scalar],
+    rows: &[(Scalar, &[Scalar])],
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) {
+    match rows {
+        [(d0, r0), (d1, r1), (d2, r2), (d3, r3)]
+            if ciphertext_modulus.is_compatible_with_native_modulus() =>
+        {
+            //Slicing the rows to the output length lets the compiler drop the bounds checks and vectorize
+            let len = acc.len();
+            let (r0, r1, r2, r3) = (&r0[..len], &r1[..len], &r2[..len], &r3[..len]);
+            for j in 0..len {
+                acc[j] = acc[j]
+                    .wrapping_add(d0.wrapping_mul(r0[j]))
+                    .wrapping_add(d1.wrapping_mul(r1[j]))
+                    .wrapping_add(d2.wrapping_mul(r2[j]))
+                    .wrapping_add(d3.wrapping_mul(r3[j]));
+            }
+        }
+        _ => {
+            for &(digit, row) in rows {
+                mul_add_row(acc, row, digit, ciphertext_modulus);
+            }
+        }
+    }
 }
 
-pub fn vec_scale(mut v: Vec<u32>, B: u32, l: usize, q: u64) -> Vec<u32> {
-    // Iterate over each element in the input vector `v`
-    for i in 0..v.len() {
-        v[i] = (v[i] as f64 * (B as f64).powi(l as i32) / q as f64).round() as u32;
+// Accumulate acc += digit * row (mod q), the inner kernel of the external product
+// The modulus check is hoisted out of the loop so the native case stays a plain vectorizable loop
+#[inline]
+fn mul_add_row<Scalar: UnsignedInteger>(
+    acc: &mut [Scalar],
+    row: &[Scalar],
+    digit: Scalar,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+) {
+    if ciphertext_modulus.is_compatible_with_native_modulus() {
+        for (a, &r) in acc.iter_mut().zip(row.iter()) {
+            *a = a.wrapping_add(digit.wrapping_mul(r));
+        }
+    } else {
+        let q: Scalar = ciphertext_modulus.get_custom_modulus().cast_into();
+        for (a, &r) in acc.iter_mut().zip(row.iter()) {
+            *a = a.wrapping_add_custom_mod(digit.wrapping_mul_custom_mod(r, q), q);
+        }
     }
-    v
 }
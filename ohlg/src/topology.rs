@@ -0,0 +1,544 @@
+/*Client-side transformations hiding the topology of a circuit from the server
+The obfuscated gates hide the function of each gate, but the netlist sent to the server still reveals the gate count,
+the depth, the fan-in/fan-out of every wire and the gate order, which leak a lot about the function computed.
+All the transformations preserve the plaintext function of the circuit:
+- pad_topology: pads the circuit to the gate count and depth of a TopologyClass, so that all the circuits of the class
+  look the same size
+- insert_dummy_gates: splices dummy gates computing the identity on the wires, either a 1-input gate computing BUF,
+  or a 2-input gate combining the wire with a constant (XOR/OR with FALSE, AND/XNOR with TRUE) computed by a 2-input
+  gate on two distinct random wires. Their encrypted parameters cannot be told apart from the ones of the real gates.
+  The dummy gates are only spliced where there is slack (including on the edges of the dummy gates already spliced),
+  so the depth of the circuit does not change.
+- shuffle_gates: a random evaluation order (among the topological orders), with the gate parameter slots renumbered
+  in that order
+- shuffle_wires: a random numbering of the wires
+The randomness is derived from a seed: CAUTION, anyone who knows the seed can undo the shuffles and find the dummy gates,
+so it must be kept as secret as the client key (e.g. drawn with tfhe::core_crypto::seeders::new_seeder)
+*/
+use tfhe::core_crypto::commons::math::random::{DefaultRandomGenerator, RandomGenerator, Seed};
+
+use crate::gate_compiler::{GateKind, UnaryGateKind};
+use crate::netlist::{Circuit, Gate, GateOp, NetlistError, WireId};
+use crate::noise::EIGHTH;
+
+//The gate count and depth (see Netlist::depth) shared by all the circuits padded to the class
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TopologyClass {
+    pub gate_count: usize,
+    pub depth: usize,
+}
+
+impl TopologyClass {
+    //The class of the circuit as it is
+    pub fn of(circuit: &Circuit) -> Result<Self, TopologyError> {
+        circuit.validate()?;
+        Ok(Self::of_valid(circuit))
+    }
+
+    /*The smallest class with a power of two depth and gate count that the circuit can be padded to,
+    so that only the order of magnitude of its size is revealed
+    The depth is doubled until there is enough slack for the dummy gates (see padding_capacity), e.g. a balanced tree
+    has none at its own depth, and gets it from the chain padding a larger depth
+    */
+    pub fn covering(circuit: &Circuit) -> Result<Self, TopologyError> {
+        circuit.validate()?;
+        let class = Self::of_valid(circuit);
+        let mut depth = class.depth.next_power_of_two();
+        loop {
+            let covering = Self {
+                gate_count: (class.gate_count + depth - class.depth).next_power_of_two(),
+                depth,
+            };
+            match padding_capacity(circuit, depth) {
+                Some(capacity) if covering.gate_count - class.gate_count > capacity => depth *= 2,
+                _ => return Ok(covering),
+            }
+        }
+    }
+
+    //The class of a circuit already validated (Netlist::depth indexes the wires without checking them)
+    fn of_valid(circuit: &Circuit) -> Self {
+        Self {
+            gate_count: circuit.netlist.gates.len(),
+            depth: circuit.netlist.depth(),
+        }
+    }
+}
+
+//The reasons why the topology of a circuit cannot be transformed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TopologyError {
+    //The netlist of the circuit is invalid (see Netlist::validate)
+    Netlist(NetlistError),
+    /*The circuit is deeper than the class, or has more gates than the class
+    (padding the depth takes at least one gate per missing layer)
+    */
+    TooLarge {
+        class: TopologyClass,
+        gate_count: usize,
+        depth: usize,
+    },
+    //No wire left with enough slack to splice the remaining dummy gates without increasing the depth
+    NoSlack {
+        missing: usize,
+    },
+    //Padding the depth needs an output wire with the boolean encoding (+-1/8) to splice the dummy gates on
+    NoBooleanOutput,
+}
+
+impl std::fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Netlist(error) => write!(f, "Invalid netlist: {}", error),
+            Self::TooLarge {
+                class,
+                gate_count,
+                depth,
+            } => write!(
+                f,
+                "The circuit ({} gates, depth {}) does not fit in the class ({} gates, depth {})",
+                gate_count, depth, class.gate_count, class.depth
+            ),
+            Self::NoSlack { missing } => write!(
+                f,
+                "No slack left to insert the last {} dummy gates without increasing the depth",
+                missing
+            ),
+            Self::NoBooleanOutput => write!(
+                f,
+                "No output wire with the boolean encoding to pad the depth of the circuit"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+impl From<NetlistError> for TopologyError {
+    fn from(error: NetlistError) -> Self {
+        Self::Netlist(error)
+    }
+}
+
+//Pad the circuit to the class, then shuffle its gates and wires (see the transformations below)
+pub fn hide_topology(
+    circuit: &mut Circuit,
+    class: TopologyClass,
+    seed: Seed,
+) -> Result<(), TopologyError> {
+    circuit.validate()?;
+    let mut rng = RandomGenerator::<DefaultRandomGenerator>::new(seed);
+    pad(circuit, class, &mut rng)?;
+    shuffle_gate_order(circuit, &mut rng);
+    shuffle_wire_ids(circuit, &mut rng);
+    Ok(())
+}
+
+/*Pad the circuit to exactly the gate count and depth of the class: a chain of dummy gates is spliced on the deepest
+output wire up to the depth of the class (2-input dummy gates as long as the gate count allows, so that the chain does
+not stand out as a run of BUF gates), then dummy gates are inserted up to its gate count
+The gates are reordered by depth and the dummy gates are appended to gate_plaintexts (see shuffle_gates to renumber the slots)
+*/
+pub fn pad_topology(
+    circuit: &mut Circuit,
+    class: TopologyClass,
+    seed: Seed,
+) -> Result<(), TopologyError> {
+    circuit.validate()?;
+    pad(circuit, class, &mut RandomGenerator::new(seed))
+}
+
+//Insert count dummy gates without changing the depth of the circuit
+pub fn insert_dummy_gates(
+    circuit: &mut Circuit,
+    count: usize,
+    seed: Seed,
+) -> Result<(), TopologyError> {
+    circuit.validate()?;
+    let mut class = TopologyClass::of_valid(circuit);
+    class.gate_count += count;
+    pad(circuit, class, &mut RandomGenerator::new(seed))
+}
+
+/*Evaluate the gates in a random topological order, and renumber the gate parameter slots in that order
+(the slots not used by any gate are dropped), so that neither reveals the order in which the circuit was built
+*/
+pub fn shuffle_gates(circuit: &mut Circuit, seed: Seed) -> Result<(), TopologyError> {
+    circuit.validate()?;
+    shuffle_gate_order(circuit, &mut RandomGenerator::new(seed));
+    Ok(())
+}
+
+//Renumber the wires with a random permutation
+pub fn shuffle_wires(circuit: &mut Circuit, seed: Seed) -> Result<(), TopologyError> {
+    circuit.validate()?;
+    shuffle_wire_ids(circuit, &mut RandomGenerator::new(seed));
+    Ok(())
+}
+
+//A uniform index below n (the modulo bias of a 64-bit draw is negligible)
+fn random_index(rng: &mut RandomGenerator<DefaultRandomGenerator>, n: usize) -> usize {
+    (rng.random_uniform::<u64>() % n as u64) as usize
+}
+
+fn pad(
+    circuit: &mut Circuit,
+    class: TopologyClass,
+    rng: &mut RandomGenerator<DefaultRandomGenerator>,
+) -> Result<(), TopologyError> {
+    let TopologyClass { gate_count, depth } = TopologyClass::of_valid(circuit);
+    if depth > class.depth || gate_count + (class.depth - depth) > class.gate_count {
+        return Err(TopologyError::TooLarge {
+            class,
+            gate_count,
+            depth,
+        });
+    }
+
+    let mut splicer = Splicer::new(circuit, class.depth, rng);
+    let mut remaining = class.gate_count - gate_count;
+
+    if depth < class.depth {
+        let edge = splicer
+            .output_edges()
+            .filter(|&edge| splicer.magnitudes[splicer.wire(edge)] == EIGHTH)
+            .max_by_key(|&edge| splicer.depths[splicer.wire(edge)])
+            .ok_or(TopologyError::NoBooleanOutput)?;
+        loop {
+            let missing = class.depth - splicer.depths[splicer.wire(edge)];
+            if missing == 0 {
+                break;
+            }
+            //A dummy gate adds one layer, keep one gate per missing layer
+            remaining -= splicer.splice(edge, remaining - missing + 1, true);
+        }
+    }
+
+    let mut edges: Vec<Edge> = splicer
+        .gate_edges(0)
+        .chain(splicer.output_edges())
+        .filter(|&edge| splicer.has_slack(edge))
+        .collect();
+    while remaining > 0 {
+        if edges.is_empty() {
+            return Err(TopologyError::NoSlack { missing: remaining });
+        }
+        let index = random_index(splicer.rng, edges.len());
+        let first_new = splicer.circuit.netlist.gates.len();
+        remaining -= splicer.splice(edges[index], remaining, false);
+        if !splicer.has_slack(edges[index]) {
+            edges.swap_remove(index);
+        }
+        //The edges of the new dummy gates, e.g. the constant of a 2-input dummy, can take dummy gates too
+        let new_edges: Vec<Edge> = splicer
+            .gate_edges(first_new)
+            .filter(|&edge| splicer.has_slack(edge))
+            .collect();
+        edges.extend(new_edges);
+    }
+    splicer.finish();
+    Ok(())
+}
+
+/*A lower bound on the number of dummy gates that pad can add to reach the depth (at least the depth of the circuit),
+None if it does not grow with the depth: without a boolean output to pad the depth on, or without 2 input wires
+for the constants of the 2-input dummy gates.
+- the chain padding the depth takes 2 gates per layer (1 on a wire of depth 0), and the constant of each 2-input dummy
+  reads wires at most half as deep as the chained wire w, which leaves ceil(w/2)-1 layers of slack below the chain
+- every other edge takes one gate per layer of slack
+*/
+fn padding_capacity(circuit: &Circuit, depth: usize) -> Option<usize> {
+    let netlist = &circuit.netlist;
+    let depths = netlist.wire_depths();
+    let mut boolean = vec![true; netlist.num_wires];
+    for gate in &netlist.gates {
+        boolean[gate.output] = gate.op.output_magnitude() == EIGHTH;
+    }
+    let chained = netlist
+        .outputs
+        .iter()
+        .flat_map(|port| &port.wires)
+        .filter(|&&wire| boolean[wire])
+        .map(|&wire| depths[wire])
+        .max()?;
+    let input_wires: usize = netlist.inputs.iter().map(|port| port.wires.len()).sum();
+    if input_wires < 2 {
+        return None;
+    }
+
+    let slack = |wire: WireId, reader_depth: usize| {
+        if boolean[wire] {
+            reader_depth.saturating_sub(depths[wire] + 1)
+        } else {
+            0
+        }
+    };
+    let gate_slack: usize = netlist
+        .gates
+        .iter()
+        .flat_map(|gate| {
+            gate.inputs
+                .iter()
+                .map(|&wire| slack(wire, depths[gate.output]))
+        })
+        .sum();
+    let output_slack: usize = netlist
+        .outputs
+        .iter()
+        .flat_map(|port| &port.wires)
+        .map(|&wire| slack(wire, depth + 1))
+        .sum();
+    let chain: usize = (chained..depth)
+        .map(|w| if w == 0 { 1 } else { 1 + w.div_ceil(2) })
+        .sum();
+    //The slack of the chained output edge is taken by the chain
+    Some(gate_slack + output_slack - (depth - chained) + chain)
+}
+
+//A wire read by a gate input or by an output port bit
+#[derive(Clone, Copy)]
+enum Edge {
+    Gate { gate: usize, slot: usize },
+    Output { port: usize, bit: usize },
+}
+
+/*Splices the dummy gates on the edges of a circuit. The dummy gates are appended to the gates,
+finish() puts all of them back in a topological order.
+*/
+struct Splicer<'a> {
+    circuit: &'a mut Circuit,
+    rng: &'a mut RandomGenerator<DefaultRandomGenerator>,
+    //The depth given to the output ports, so that they can be read up to this depth
+    output_depth: usize,
+    //The depth and the encoding of each wire
+    depths: Vec<usize>,
+    magnitudes: Vec<u32>,
+    //The wires driven by an input port or a gate (validate() accepts wires that nothing drives or reads)
+    driven: Vec<bool>,
+}
+
+impl<'a> Splicer<'a> {
+    fn new(
+        circuit: &'a mut Circuit,
+        output_depth: usize,
+        rng: &'a mut RandomGenerator<DefaultRandomGenerator>,
+    ) -> Self {
+        let netlist = &circuit.netlist;
+        let mut magnitudes = vec![EIGHTH; netlist.num_wires];
+        let mut driven = vec![false; netlist.num_wires];
+        for gate in &netlist.gates {
+            magnitudes[gate.output] = gate.op.output_magnitude();
+            driven[gate.output] = true;
+        }
+        for &wire in netlist.inputs.iter().flat_map(|port| &port.wires) {
+            driven[wire] = true;
+        }
+        Self {
+            depths: netlist.wire_depths(),
+            magnitudes,
+            driven,
+            output_depth,
+            circuit,
+            rng,
+        }
+    }
+
+    //The edges of the gates from the index first on
+    fn gate_edges(&self, first: usize) -> impl Iterator<Item = Edge> + '_ {
+        self.circuit.netlist.gates[first..]
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, g)| {
+                (0..g.inputs.len()).map(move |slot| Edge::Gate {
+                    gate: first + index,
+                    slot,
+                })
+            })
+    }
+
+    fn output_edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.circuit
+            .netlist
+            .outputs
+            .iter()
+            .enumerate()
+            .flat_map(|(port, p)| (0..p.wires.len()).map(move |bit| Edge::Output { port, bit }))
+    }
+
+    fn wire(&self, edge: Edge) -> WireId {
+        match edge {
+            Edge::Gate { gate, slot } => self.circuit.netlist.gates[gate].inputs[slot],
+            Edge::Output { port, bit } => self.circuit.netlist.outputs[port].wires[bit],
+        }
+    }
+
+    fn set_wire(&mut self, edge: Edge, wire: WireId) {
+        match edge {
+            Edge::Gate { gate, slot } => self.circuit.netlist.gates[gate].inputs[slot] = wire,
+            Edge::Output { port, bit } => self.circuit.netlist.outputs[port].wires[bit] = wire,
+        }
+    }
+
+    fn reader_depth(&self, edge: Edge) -> usize {
+        match edge {
+            Edge::Gate { gate, .. } => self.depths[self.circuit.netlist.gates[gate].output],
+            Edge::Output { .. } => self.output_depth + 1,
+        }
+    }
+
+    //A dummy gate can be spliced on the edge without making the reader deeper
+    fn has_slack(&self, edge: Edge) -> bool {
+        let wire = self.wire(edge);
+        self.magnitudes[wire] == EIGHTH && self.depths[wire] + 2 <= self.reader_depth(edge)
+    }
+
+    /*Splice a dummy gate one layer above the wire of the edge: a BUF gate, or if max_gates >= 2,
+    a 2-input gate combining the wire with a constant computed from two distinct shallower wires.
+    On the chain padding the depth, the 2-input gate is always preferred and its constant reads wires at most half as
+    deep as the wire (see padding_capacity), elsewhere either gate is drawn.
+    Returns the number of gates added
+    */
+    fn splice(&mut self, edge: Edge, max_gates: usize, on_chain: bool) -> usize {
+        let wire = self.wire(edge);
+        let wire_depth = self.depths[wire];
+        if max_gates >= 2
+            && wire_depth > 0
+            && (on_chain || self.rng.random_uniform::<u64>() & 1 == 1)
+        {
+            //The constant must not be deeper than the wire, so that the dummy gate only adds one layer
+            let max_depth = if on_chain {
+                wire_depth / 2
+            } else {
+                wire_depth - 1
+            };
+            let candidates: Vec<WireId> = (0..self.circuit.netlist.num_wires)
+                .filter(|&w| {
+                    self.driven[w] && self.magnitudes[w] == EIGHTH && self.depths[w] <= max_depth
+                })
+                .collect();
+            if candidates.len() >= 2 {
+                let a = random_index(self.rng, candidates.len());
+                let b = (a + 1 + random_index(self.rng, candidates.len() - 1)) % candidates.len();
+                let (constant, kind) = match random_index(self.rng, 4) {
+                    0 => (GateKind::False, GateKind::Xor),
+                    1 => (GateKind::False, GateKind::Or),
+                    2 => (GateKind::True, GateKind::And),
+                    _ => (GateKind::True, GateKind::Xnor),
+                };
+                let c = self.add_gate(
+                    GateOp::TwoOp,
+                    vec![candidates[a], candidates[b]],
+                    constant.plaintexts(),
+                );
+                let inputs = if self.rng.random_uniform::<u64>() & 1 == 1 {
+                    vec![wire, c]
+                } else {
+                    vec![c, wire]
+                };
+                let output = self.add_gate(GateOp::TwoOp, inputs, kind.plaintexts());
+                self.set_wire(edge, output);
+                return 2;
+            }
+        }
+        let output = self.add_gate(GateOp::OneOp, vec![wire], UnaryGateKind::Buf.plaintexts());
+        self.set_wire(edge, output);
+        1
+    }
+
+    //Add a dummy gate with the boolean encoding, with a new parameter slot
+    fn add_gate(&mut self, op: GateOp, inputs: Vec<WireId>, plaintexts: (u32, u32)) -> WireId {
+        let output = self.circuit.netlist.new_wire();
+        self.depths
+            .push(1 + inputs.iter().map(|&w| self.depths[w]).max().unwrap_or(0));
+        self.magnitudes.push(EIGHTH);
+        self.driven.push(true);
+        self.circuit.gate_plaintexts.push(plaintexts);
+        let param = self.circuit.gate_plaintexts.len() - 1;
+        self.circuit.netlist.gates.push(Gate {
+            op,
+            inputs,
+            output,
+            param,
+        });
+        output
+    }
+
+    //Order the gates by depth, a topological order since a gate is deeper than its inputs
+    fn finish(self) {
+        let depths = self.depths;
+        self.circuit
+            .netlist
+            .gates
+            .sort_by_key(|gate| depths[gate.output]);
+    }
+}
+
+fn shuffle_gate_order(circuit: &mut Circuit, rng: &mut RandomGenerator<DefaultRandomGenerator>) {
+    let netlist = &mut circuit.netlist;
+    let mut drivers = vec![None; netlist.num_wires];
+    for (index, gate) in netlist.gates.iter().enumerate() {
+        drivers[gate.output] = Some(index);
+    }
+    //Kahn's algorithm, taking a random gate among the ones whose inputs are all computed
+    let mut readers = vec![Vec::new(); netlist.gates.len()];
+    let mut missing_inputs = vec![0; netlist.gates.len()];
+    for (index, gate) in netlist.gates.iter().enumerate() {
+        for &wire in &gate.inputs {
+            if let Some(driver) = drivers[wire] {
+                readers[driver].push(index);
+                missing_inputs[index] += 1;
+            }
+        }
+    }
+    let mut ready: Vec<usize> = (0..netlist.gates.len())
+        .filter(|&index| missing_inputs[index] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(netlist.gates.len());
+    while !ready.is_empty() {
+        let index = ready.swap_remove(random_index(rng, ready.len()));
+        order.push(index);
+        for &reader in &readers[index] {
+            missing_inputs[reader] -= 1;
+            if missing_inputs[reader] == 0 {
+                ready.push(reader);
+            }
+        }
+    }
+
+    let mut gates: Vec<Option<Gate>> = std::mem::take(&mut netlist.gates)
+        .into_iter()
+        .map(Some)
+        .collect();
+    let mut gate_plaintexts = Vec::with_capacity(circuit.gate_plaintexts.len());
+    for index in order {
+        let mut gate = gates[index].take().unwrap();
+        //validate() guarantees that the parameter slots are in gate_plaintexts
+        let slots = gate.param_slots().unwrap();
+        gate.param = if slots.is_empty() {
+            0
+        } else {
+            gate_plaintexts.len()
+        };
+        gate_plaintexts.extend_from_slice(&circuit.gate_plaintexts[slots]);
+        netlist.gates.push(gate);
+    }
+    circuit.gate_plaintexts = gate_plaintexts;
+}
+
+fn shuffle_wire_ids(circuit: &mut Circuit, rng: &mut RandomGenerator<DefaultRandomGenerator>) {
+    let netlist = &mut circuit.netlist;
+    //Fisher-Yates shuffle
+    let mut permutation: Vec<WireId> = (0..netlist.num_wires).collect();
+    for i in (1..permutation.len()).rev() {
+        permutation.swap(i, random_index(rng, i + 1));
+    }
+    let ports = netlist.inputs.iter_mut().chain(netlist.outputs.iter_mut());
+    for wire in ports.flat_map(|port| port.wires.iter_mut()) {
+        *wire = permutation[*wire];
+    }
+    for gate in &mut netlist.gates {
+        for wire in gate.inputs.iter_mut().chain([&mut gate.output]) {
+            *wire = permutation[*wire];
+        }
+    }
+}
@@ -0,0 +1,595 @@
+/*Client-side import of the gate-level netlists synthesized by Yosys, restricted to 1- and 2-input cells:
+- the JSON netlist of write_json, with the fine-grained cells of synth/techmap ($_AND_, $_OR_, $_XOR_, $_NAND_, $_NOR_,
+  $_XNOR_, $_ANDNOT_, $_ORNOT_, $_NOT_, $_BUF_)
+- the BLIF netlist of write_blif: .names covers of at most 2 inputs (the default output of Yosys for its internal cells),
+  and .subckt/.gate lines of the cells above (write_blif -icells)
+Each cell becomes an obfuscated gate (see Circuit::add_truth_table_gate: $_ANDNOT_ and $_ORNOT_ take a NOT gate and
+a 2-input gate), the constant drivers a 1-input gate computing CONST0 or CONST1 on the first input wire.
+The cells can be in any order, they are sorted in evaluation order. The multi-bit ports keep their bits in order
+(bit 0 first), e.g. the BLIF nets a[0], a[1], ... become the port a.
+*/
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+
+use crate::gate_compiler::UnaryGateKind;
+use crate::netlist::{Circuit, WireId};
+
+//The reasons why a Yosys netlist cannot be imported
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum YosysError {
+    //The JSON text is not a Yosys netlist
+    Format { message: String },
+    //A BLIF line does not follow the format (the line numbers start at 1)
+    Syntax { line: usize, message: String },
+    //The requested module does not exist, or there is no top module to choose by default
+    MissingModule { name: Option<String> },
+    //A cell with more than 2 inputs, or a sequential or unknown cell
+    UnsupportedCell { cell: String, cell_type: String },
+    //A net with an undefined value (x or z)
+    UndefinedNet { net: String },
+    //A net read by a cell or an output port, but not driven by an input port or a cell
+    UndrivenNet { net: String },
+    //A net driven by more than one input port or cell
+    MultipleDrivers { net: String },
+    //A cycle of cells, which cannot be evaluated gate by gate
+    CombinationalLoop { cell: String },
+    //A constant in a netlist without any input wire to attach it to
+    ConstantWithoutInputs,
+}
+
+impl std::fmt::Display for YosysError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Format { message } => write!(f, "Invalid Yosys JSON netlist: {}", message),
+            Self::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+            Self::MissingModule { name: Some(name) } => {
+                write!(f, "The module {} does not exist", name)
+            }
+            Self::MissingModule { name: None } => write!(
+                f,
+                "The netlist has no top module, the module to import must be named"
+            ),
+            Self::UnsupportedCell { cell, cell_type } => write!(
+                f,
+                "The cell {} ({}) is not a 1- or 2-input combinational cell",
+                cell, cell_type
+            ),
+            Self::UndefinedNet { net } => write!(f, "The net {} has an undefined value", net),
+            Self::UndrivenNet { net } => write!(f, "The net {} is not driven", net),
+            Self::MultipleDrivers { net } => write!(f, "The net {} is driven more than once", net),
+            Self::CombinationalLoop { cell } => {
+                write!(f, "The cell {} is part of a combinational loop", cell)
+            }
+            Self::ConstantWithoutInputs => {
+                write!(f, "A constant needs at least one input wire in the netlist")
+            }
+        }
+    }
+}
+
+impl std::error::Error for YosysError {}
+
+//A net of the imported netlist, named as in the file (the bit number in JSON)
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Net {
+    Signal(String),
+    Const(bool),
+}
+
+//A 1- or 2-input cell: truth_table[i] is the output for the inputs given by the bits of i (input 0 is bit 0)
+struct Cell {
+    name: String,
+    inputs: Vec<Net>,
+    output: String,
+    truth_table: Vec<bool>,
+}
+
+//The module to import, independently of the file format
+#[derive(Default)]
+struct Module {
+    inputs: Vec<(String, Vec<Net>)>,
+    outputs: Vec<(String, Vec<Net>)>,
+    cells: Vec<Cell>,
+}
+
+//The truth table of the fine-grained Yosys cells (inputs A, then B)
+fn cell_truth_table(cell_type: &str) -> Option<Vec<bool>> {
+    let table: &[u8] = match cell_type {
+        "$_BUF_" => &[0, 1],
+        "$_NOT_" => &[1, 0],
+        "$_AND_" => &[0, 0, 0, 1],
+        "$_NAND_" => &[1, 1, 1, 0],
+        "$_OR_" => &[0, 1, 1, 1],
+        "$_NOR_" => &[1, 0, 0, 0],
+        "$_XOR_" => &[0, 1, 1, 0],
+        "$_XNOR_" => &[1, 0, 0, 1],
+        //A AND NOT B, A OR NOT B
+        "$_ANDNOT_" => &[0, 1, 0, 0],
+        "$_ORNOT_" => &[1, 1, 0, 1],
+        _ => return None,
+    };
+    Some(table.iter().map(|&bit| bit == 1).collect())
+}
+
+//The input pins of a cell with the given number of inputs
+fn cell_input_pins(num_inputs: usize) -> &'static [&'static str] {
+    &["A", "B"][..num_inputs]
+}
+
+#[derive(Deserialize)]
+struct JsonNetlist {
+    #[serde(deserialize_with = "ordered_map")]
+    modules: Vec<(String, JsonModule)>,
+}
+
+#[derive(Deserialize)]
+struct JsonModule {
+    #[serde(default)]
+    attributes: HashMap<String, serde_json::Value>,
+    #[serde(default, deserialize_with = "ordered_map")]
+    ports: Vec<(String, JsonPort)>,
+    #[serde(default, deserialize_with = "ordered_map")]
+    cells: Vec<(String, JsonCell)>,
+}
+
+#[derive(Deserialize)]
+struct JsonPort {
+    direction: String,
+    bits: Vec<JsonBit>,
+}
+
+#[derive(Deserialize)]
+struct JsonCell {
+    #[serde(rename = "type")]
+    cell_type: String,
+    connections: HashMap<String, Vec<JsonBit>>,
+}
+
+//A bit of a port or a connection: a net number, or a constant "0", "1", "x" or "z"
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonBit {
+    Net(u64),
+    Const(String),
+}
+
+impl JsonBit {
+    fn to_net(&self) -> Result<Net, YosysError> {
+        match self {
+            Self::Net(net) => Ok(Net::Signal(net.to_string())),
+            Self::Const(value) => match value.as_str() {
+                "0" => Ok(Net::Const(false)),
+                "1" => Ok(Net::Const(true)),
+                _ => Err(YosysError::UndefinedNet { net: value.clone() }),
+            },
+        }
+    }
+}
+
+//A JSON object as a list of (key, value), in the order of the file (the declaration order of the ports)
+fn ordered_map<'de, D, T>(deserializer: D) -> Result<Vec<(String, T)>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OrderedMap<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for OrderedMap<T> {
+        type Value = Vec<(String, T)>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "a JSON object")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(OrderedMap(std::marker::PhantomData))
+}
+
+/*Import a module of the JSON netlist written by Yosys (write_json): the module named top,
+or by default the one with the top attribute (or the only module of the netlist)
+The input and output ports become the ports of the netlist, in the order of declaration
+*/
+pub fn parse_yosys_json(text: &str, top: Option<&str>) -> Result<Circuit, YosysError> {
+    let netlist: JsonNetlist = serde_json::from_str(text).map_err(|error| YosysError::Format {
+        message: error.to_string(),
+    })?;
+
+    let is_top = |module: &JsonModule| match module.attributes.get("top") {
+        Some(serde_json::Value::String(value)) => value.contains('1'),
+        Some(serde_json::Value::Number(value)) => value.as_u64() != Some(0),
+        _ => false,
+    };
+    let (_, json_module) = match top {
+        Some(name) => netlist
+            .modules
+            .iter()
+            .find(|(module_name, _)| module_name == name),
+        None => netlist
+            .modules
+            .iter()
+            .find(|(_, module)| is_top(module))
+            .or(match &netlist.modules[..] {
+                [module] => Some(module),
+                _ => None,
+            }),
+    }
+    .ok_or(YosysError::MissingModule {
+        name: top.map(str::to_string),
+    })?;
+
+    let mut module = Module::default();
+    for (name, port) in &json_module.ports {
+        let bits = port
+            .bits
+            .iter()
+            .map(JsonBit::to_net)
+            .collect::<Result<Vec<Net>, YosysError>>()?;
+        match port.direction.as_str() {
+            "input" => module.inputs.push((name.clone(), bits)),
+            "output" => module.outputs.push((name.clone(), bits)),
+            direction => {
+                return Err(YosysError::Format {
+                    message: format!("the port {} is {}", name, direction),
+                })
+            }
+        }
+    }
+
+    for (name, cell) in &json_module.cells {
+        let unsupported = || YosysError::UnsupportedCell {
+            cell: name.clone(),
+            cell_type: cell.cell_type.clone(),
+        };
+        let truth_table = cell_truth_table(&cell.cell_type).ok_or_else(unsupported)?;
+        let num_inputs = truth_table.len().ilog2() as usize;
+        let pin = |pin: &str| match cell.connections.get(pin).map(Vec::as_slice) {
+            Some([bit]) => bit.to_net(),
+            _ => Err(YosysError::Format {
+                message: format!("the pin {} of the cell {} is not 1 bit wide", pin, name),
+            }),
+        };
+        let inputs = cell_input_pins(num_inputs)
+            .iter()
+            .map(|name| pin(name))
+            .collect::<Result<Vec<Net>, YosysError>>()?;
+        let output = match pin("Y")? {
+            Net::Signal(net) => net,
+            Net::Const(_) => {
+                return Err(YosysError::Format {
+                    message: format!("the output of the cell {} is a constant", name),
+                })
+            }
+        };
+        module.cells.push(Cell {
+            name: name.clone(),
+            inputs,
+            output,
+            truth_table,
+        });
+    }
+
+    build_circuit(module)
+}
+
+/*Import the first model of the BLIF netlist written by Yosys (write_blif, see the module documentation)
+The nets name[0], name[1], ... of .inputs and .outputs are gathered in the port name
+*/
+pub fn parse_blif(text: &str) -> Result<Circuit, YosysError> {
+    //The logical lines (comments removed, continuations joined) with the number of their first line
+    let mut lines: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut continued = false;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let (line, continues) = match line.trim_end().strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let tokens = line.split_whitespace().map(str::to_string);
+        match lines.last_mut() {
+            Some((_, last)) if continued => last.extend(tokens),
+            _ => lines.push((index + 1, tokens.collect())),
+        }
+        continued = continues;
+    }
+    lines.retain(|(_, tokens)| !tokens.is_empty());
+
+    let mut inputs: Vec<String> = Vec::new();
+    let mut outputs: Vec<String> = Vec::new();
+    let mut module = Module::default();
+    let mut lines = lines.into_iter().peekable();
+    while let Some((line, tokens)) = lines.next() {
+        let syntax = |message: &str| YosysError::Syntax {
+            line,
+            message: message.to_string(),
+        };
+        match tokens[0].as_str() {
+            ".model" | ".attr" | ".param" | ".cname" => {}
+            ".end" => break,
+            ".inputs" => inputs.extend(tokens[1..].iter().cloned()),
+            ".outputs" => outputs.extend(tokens[1..].iter().cloned()),
+            ".names" => {
+                let (output, input_names) = tokens[1..]
+                    .split_last()
+                    .ok_or_else(|| syntax(".names without an output net"))?;
+                let mut rows = Vec::new();
+                while let Some((_, row)) = lines.next_if(|(_, row)| !row[0].starts_with('.')) {
+                    rows.push(row);
+                }
+                //The definitions of the constant nets
+                if blif_net(output) != Net::Signal(output.clone()) {
+                    continue;
+                }
+                if input_names.len() > 2 {
+                    return Err(YosysError::UnsupportedCell {
+                        cell: output.clone(),
+                        cell_type: format!(".names with {} inputs", input_names.len()),
+                    });
+                }
+                let truth_table = cover_truth_table(input_names.len(), &rows)
+                    .map_err(|message| syntax(&message))?;
+                module.cells.push(Cell {
+                    name: output.clone(),
+                    inputs: input_names.iter().map(|name| blif_net(name)).collect(),
+                    output: output.clone(),
+                    truth_table,
+                });
+            }
+            ".subckt" | ".gate" => {
+                let cell_type = tokens
+                    .get(1)
+                    .ok_or_else(|| syntax("missing the cell type"))?;
+                let pins: HashMap<&str, &str> = tokens[2..]
+                    .iter()
+                    .map(|pin| {
+                        pin.split_once('=')
+                            .ok_or_else(|| syntax("expected pin=net"))
+                    })
+                    .collect::<Result<_, YosysError>>()?;
+                let output = pins.get("Y").ok_or_else(|| syntax("missing the pin Y"))?;
+                let truth_table =
+                    cell_truth_table(cell_type).ok_or_else(|| YosysError::UnsupportedCell {
+                        cell: output.to_string(),
+                        cell_type: cell_type.clone(),
+                    })?;
+                let num_inputs = truth_table.len().ilog2() as usize;
+                let inputs = cell_input_pins(num_inputs)
+                    .iter()
+                    .map(|pin| {
+                        pins.get(pin)
+                            .map(|net| blif_net(net))
+                            .ok_or_else(|| syntax(&format!("missing the pin {}", pin)))
+                    })
+                    .collect::<Result<Vec<Net>, YosysError>>()?;
+                module.cells.push(Cell {
+                    name: output.to_string(),
+                    inputs,
+                    output: output.to_string(),
+                    truth_table,
+                });
+            }
+            //A connection (write_blif -conn), i.e. a buffer
+            ".conn" => {
+                let [_, input, output] = &tokens[..] else {
+                    return Err(syntax(".conn takes 2 nets"));
+                };
+                module.cells.push(Cell {
+                    name: output.clone(),
+                    inputs: vec![blif_net(input)],
+                    output: output.clone(),
+                    truth_table: vec![false, true],
+                });
+            }
+            directive if directive.starts_with('.') => {
+                return Err(YosysError::UnsupportedCell {
+                    cell: tokens[1..].join(" "),
+                    cell_type: directive.to_string(),
+                })
+            }
+            _ => return Err(syntax("expected a directive")),
+        }
+    }
+
+    module.inputs = blif_ports(&inputs);
+    module.outputs = blif_ports(&outputs);
+    build_circuit(module)
+}
+
+/*The constant nets of write_blif, defined by .names covers (except with write_blif -impltf),
+the undefined value is written as the constant 0
+*/
+fn blif_net(name: &str) -> Net {
+    match name {
+        "$false" | "$undef" => Net::Const(false),
+        "$true" => Net::Const(true),
+        _ => Net::Signal(name.to_string()),
+    }
+}
+
+//Gather the nets name[i] in the port name (sorted by i), in the order of the first net of each port
+fn blif_ports(nets: &[String]) -> Vec<(String, Vec<Net>)> {
+    let mut ports: Vec<(String, Vec<(usize, Net)>)> = Vec::new();
+    for net in nets {
+        let (name, index) = match net.strip_suffix(']').and_then(|net| net.rsplit_once('[')) {
+            Some((name, index)) if index.parse::<usize>().is_ok() => {
+                (name.to_string(), index.parse().unwrap())
+            }
+            _ => (net.clone(), 0),
+        };
+        let bit = (index, Net::Signal(net.clone()));
+        match ports.iter_mut().find(|(port_name, _)| *port_name == name) {
+            Some((_, bits)) => bits.push(bit),
+            None => ports.push((name, vec![bit])),
+        }
+    }
+    ports
+        .into_iter()
+        .map(|(name, mut bits)| {
+            bits.sort_by_key(|(index, _)| *index);
+            (name, bits.into_iter().map(|(_, net)| net).collect())
+        })
+        .collect()
+}
+
+/*The truth table of a .names cover: each row is an input pattern (0, 1 or - for any value) and an output value,
+the rows with the output 1 give the ON-set of the function, the rows with 0 its OFF-set (and no row the constant 0)
+*/
+fn cover_truth_table(num_inputs: usize, rows: &[Vec<String>]) -> Result<Vec<bool>, String> {
+    let mut truth_table = vec![false; 1 << num_inputs];
+    let mut on_set = None;
+    for row in rows {
+        let (pattern, value) = match &row[..] {
+            [value] if num_inputs == 0 => ("", value),
+            [pattern, value] if pattern.len() == num_inputs => (pattern.as_str(), value),
+            _ => return Err(format!("invalid cover row {}", row.join(" "))),
+        };
+        let value = match value.as_str() {
+            "1" => true,
+            "0" => false,
+            _ => return Err(format!("invalid cover output {}", value)),
+        };
+        if *on_set.get_or_insert(value) != value {
+            return Err("a cover mixes ON-set and OFF-set rows".to_string());
+        }
+        for (index, entry) in truth_table.iter_mut().enumerate() {
+            let matches = pattern.bytes().enumerate().all(|(i, bit)| match bit {
+                b'-' => true,
+                b'0' | b'1' => (index >> i & 1 == 1) == (bit == b'1'),
+                _ => false,
+            });
+            *entry |= matches;
+        }
+    }
+    //The rows of an OFF-set give the inputs where the function is 0
+    if on_set == Some(false) {
+        truth_table.iter_mut().for_each(|entry| *entry = !*entry);
+    }
+    Ok(truth_table)
+}
+
+//Build the circuit of a module: the ports, then the cells in evaluation order
+fn build_circuit(module: Module) -> Result<Circuit, YosysError> {
+    let mut circuit = Circuit::new();
+    let mut wires: HashMap<String, WireId> = HashMap::new();
+
+    let drive = |wires: &mut HashMap<String, WireId>, net: &str, wire: WireId| match wires
+        .insert(net.to_string(), wire)
+    {
+        Some(_) => Err(YosysError::MultipleDrivers {
+            net: net.to_string(),
+        }),
+        None => Ok(()),
+    };
+    for (name, bits) in &module.inputs {
+        let port = circuit.netlist.add_input_port(name, bits.len());
+        for (bit, wire) in bits.iter().zip(port) {
+            match bit {
+                Net::Signal(net) => drive(&mut wires, net, wire)?,
+                Net::Const(_) => {
+                    return Err(YosysError::Format {
+                        message: format!("the input port {} has a constant bit", name),
+                    })
+                }
+            }
+        }
+    }
+
+    //The cells reading each net, and the number of inputs of each cell driven by a cell not evaluated yet
+    let mut drivers: HashMap<&str, usize> = HashMap::new();
+    for (index, cell) in module.cells.iter().enumerate() {
+        if wires.contains_key(&cell.output) || drivers.insert(&cell.output, index).is_some() {
+            return Err(YosysError::MultipleDrivers {
+                net: cell.output.clone(),
+            });
+        }
+    }
+    let mut readers: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut pending: Vec<usize> = vec![0; module.cells.len()];
+    for (index, cell) in module.cells.iter().enumerate() {
+        for input in &cell.inputs {
+            if let Net::Signal(net) = input {
+                if drivers.contains_key(net.as_str()) {
+                    readers.entry(net).or_default().push(index);
+                    pending[index] += 1;
+                }
+            }
+        }
+    }
+
+    //The wires of the constants, created on first use
+    let mut constants: [Option<WireId>; 2] = [None; 2];
+    let mut wire = |circuit: &mut Circuit, wires: &HashMap<String, WireId>, net: &Net| match net {
+        Net::Signal(net) => wires
+            .get(net)
+            .copied()
+            .ok_or_else(|| YosysError::UndrivenNet { net: net.clone() }),
+        Net::Const(value) => match constants[*value as usize] {
+            Some(wire) => Ok(wire),
+            None => {
+                let input = *circuit
+                    .netlist
+                    .inputs
+                    .iter()
+                    .flat_map(|port| &port.wires)
+                    .next()
+                    .ok_or(YosysError::ConstantWithoutInputs)?;
+                let kind = UnaryGateKind::from_truth_table([*value, *value]);
+                let wire = circuit.add_gate_1op(kind, input);
+                constants[*value as usize] = Some(wire);
+                Ok(wire)
+            }
+        },
+    };
+
+    let mut ready: VecDeque<usize> = (0..module.cells.len())
+        .filter(|&index| pending[index] == 0)
+        .collect();
+    while let Some(index) = ready.pop_front() {
+        let cell = &module.cells[index];
+        let inputs = cell
+            .inputs
+            .iter()
+            .map(|net| wire(&mut circuit, &wires, net))
+            .collect::<Result<Vec<WireId>, YosysError>>()?;
+        let output = match (&inputs[..], &cell.truth_table[..]) {
+            (&[], &[value]) => wire(&mut circuit, &wires, &Net::Const(value))?,
+            (&[input], &[f_0, f_1]) => {
+                circuit.add_gate_1op(UnaryGateKind::from_truth_table([f_0, f_1]), input)
+            }
+            //The truth tables of the gate compiler are indexed by 2*m1 + m2
+            (&[a, b], &[f_00, f_10, f_01, f_11]) => {
+                circuit.add_truth_table_gate([a, b], &[f_00, f_01, f_10, f_11])
+            }
+            _ => unreachable!("the cells have at most 2 inputs"),
+        };
+        drive(&mut wires, &cell.output, output)?;
+        for &reader in readers.get(cell.output.as_str()).into_iter().flatten() {
+            pending[reader] -= 1;
+            if pending[reader] == 0 {
+                ready.push_back(reader);
+            }
+        }
+    }
+    if let Some(index) = pending.iter().position(|&count| count > 0) {
+        return Err(YosysError::CombinationalLoop {
+            cell: module.cells[index].name.clone(),
+        });
+    }
+
+    for (name, bits) in &module.outputs {
+        let port = bits
+            .iter()
+            .map(|net| wire(&mut circuit, &wires, net))
+            .collect::<Result<Vec<WireId>, YosysError>>()?;
+        circuit.netlist.add_output_port(name, port);
+    }
+    Ok(circuit)
+}
@@ -0,0 +1,266 @@
+#![allow(non_snake_case)]
+/*Backward compatibility of the serialized OHLG types
+The fixtures in tests/backward_compatibility/data/<version> were written by a previous release of OHLG with
+tfhe::safe_serialization, and must keep loading (and decrypting to the same messages) with the current one.
+When the version of a serialized type changes (see src/backward_compatibility.rs), set FIXTURES_VERSION to the new
+version, generate its fixtures with `cargo test --test backward_compatibility -- --ignored`, and keep the old ones.
+The keys are not OHLG types, they are stored with their tfhe versioning in plain bincode.
+*/
+use ohlg::gate_params::{new_encrypt_gate_params, GateParams, GateParamsConformanceParams};
+use ohlg::noise::decrypt_eighths;
+use ohlg::tgsw::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::prelude::*;
+use tfhe::core_crypto::seeders::new_seeder;
+use tfhe::named::Named;
+use tfhe::safe_serialization::{safe_deserialize, safe_deserialize_conformant, safe_serialize};
+use tfhe_versionable::{Unversionize, Versionize, VersionizeOwned};
+
+const VERSIONS: [&str; 2] = ["v0", "v1"];
+const FIXTURES_VERSION: &str = "v1";
+const SIZE_LIMIT: u64 = 1 << 26;
+
+//Fixture messages
+const GATE_PLAINTEXTS: [(u32, u32); 3] = [(2, 6 << 29), (1, 7 << 29), (1, 1 << 29)];
+const TGSW_MESSAGE: u32 = 5;
+const TGSW_DECOMP_BASE: u32 = 16;
+const TGSW_DECOMP_LEVEL: usize = 3;
+
+fn data_dir(version: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/backward_compatibility/data")
+        .join(version)
+}
+
+fn fixture_tgsw_params() -> TgswParams {
+    TgswParams::new_with_decomposition(DEFAULT_PARAMETERS, 64, 2, DecompositionKind::Balanced)
+}
+
+fn load<T: serde::de::DeserializeOwned + Unversionize + Named>(version: &str, name: &str) -> T {
+    let file = std::fs::File::open(data_dir(version).join(name)).unwrap();
+    safe_deserialize(file, SIZE_LIMIT).unwrap()
+}
+
+fn load_key<T: Unversionize>(version: &str, name: &str) -> T {
+    let file = std::fs::File::open(data_dir(version).join(name)).unwrap();
+    T::unversionize(bincode::deserialize_from(file).unwrap()).unwrap()
+}
+
+#[test]
+fn tgsw_params() {
+    for version in VERSIONS {
+        let tgsw_params: TgswParams = load(version, "tgsw_params.bcode");
+        let expected = fixture_tgsw_params();
+
+        assert_eq!(tgsw_params.tfhe_params, expected.tfhe_params);
+        assert_eq!(tgsw_params.decomp_base, expected.decomp_base);
+        assert_eq!(tgsw_params.decomp_level, expected.decomp_level);
+        assert_eq!(tgsw_params.decomp_kind, expected.decomp_kind);
+    }
+}
+
+#[test]
+fn gate_params() {
+    for version in VERSIONS {
+        let tgsw_params: TgswParams = load(version, "tgsw_params.bcode");
+        let client_key: ClientKey = load_key(version, "client_key.bcode");
+
+        let file = std::fs::File::open(data_dir(version).join("gate_params.bcode")).unwrap();
+        let gate_params: GateParams = safe_deserialize_conformant(
+            file,
+            SIZE_LIMIT,
+            &GateParamsConformanceParams::new(GATE_PLAINTEXTS.len(), &tgsw_params),
+        )
+        .unwrap();
+
+        //The conformance check rejects a file with a different number of gates
+        let file = std::fs::File::open(data_dir(version).join("gate_params.bcode")).unwrap();
+        assert!(safe_deserialize_conformant::<GateParams>(
+            file,
+            SIZE_LIMIT,
+            &GateParamsConformanceParams::new(GATE_PLAINTEXTS.len() + 1, &tgsw_params),
+        )
+        .is_err());
+
+        for ((C_gate, cd_gate), &(A, d)) in gate_params
+            .C_gate_params
+            .into_iter()
+            .zip(gate_params.cd_gate_params.iter())
+            .zip(GATE_PLAINTEXTS.iter())
+        {
+            assert_eq!(C_gate.decompress().decrypt(&tgsw_params, &client_key), A);
+            assert_eq!(decrypt_eighths(cd_gate, &client_key), d >> 29);
+        }
+    }
+}
+
+#[test]
+fn tgsw_ciphertexts() {
+    for version in VERSIONS {
+        let lwe_sk: LweSecretKeyOwned<u32> = load_key(version, "lwe_secret_key.bcode");
+        let conformance_params = TgswCiphertextConformanceParams {
+            lwe_dimension: lwe_sk.lwe_dimension(),
+            decomp_level: TGSW_DECOMP_LEVEL,
+            ciphertext_modulus: CiphertextModulus::new_native(),
+        };
+
+        let file = std::fs::File::open(data_dir(version).join("tgsw_ciphertext.bcode")).unwrap();
+        let tgsw: TgswCiphertext =
+            safe_deserialize_conformant(file, SIZE_LIMIT, &conformance_params).unwrap();
+        assert_eq!(
+            tgsw.decrypt_with_key(TGSW_DECOMP_BASE, TGSW_DECOMP_LEVEL, &lwe_sk),
+            TGSW_MESSAGE
+        );
+
+        let file =
+            std::fs::File::open(data_dir(version).join("seeded_tgsw_ciphertext.bcode")).unwrap();
+        let seeded_tgsw: SeededTgswCiphertext =
+            safe_deserialize_conformant(file, SIZE_LIMIT, &conformance_params).unwrap();
+        assert_eq!(
+            seeded_tgsw
+                .decompress()
+                .decrypt_with_key(TGSW_DECOMP_BASE, TGSW_DECOMP_LEVEL, &lwe_sk),
+            TGSW_MESSAGE
+        );
+    }
+}
+
+fn store<T: Serialize + Versionize + Named>(object: &T, name: &str) {
+    let file = std::fs::File::create(data_dir(FIXTURES_VERSION).join(name)).unwrap();
+    safe_serialize(object, file, SIZE_LIMIT).unwrap();
+}
+
+fn store_key<T: VersionizeOwned>(key: T, name: &str) {
+    let file = std::fs::File::create(data_dir(FIXTURES_VERSION).join(name)).unwrap();
+    bincode::serialize_into(file, &key.versionize_owned()).unwrap();
+}
+
+#[test]
+#[ignore]
+fn generate_fixtures() {
+    std::fs::create_dir_all(data_dir(FIXTURES_VERSION)).unwrap();
+
+    //Gate parameters under a boolean client key
+    let tgsw_params = fixture_tgsw_params();
+    let client_key = ClientKey::new(&tgsw_params.tfhe_params);
+    let gate_params = new_encrypt_gate_params(&GATE_PLAINTEXTS, &tgsw_params, &client_key);
+    store(&tgsw_params, "tgsw_params.bcode");
+    store(&gate_params, "gate_params.bcode");
+    store_key(client_key, "client_key.bcode");
+
+    //TGSW ciphertexts under a small LWE key, so the uncompressed ciphertext stays small
+    let mut seeder = new_seeder();
+    let seeder = seeder.as_mut();
+    let mut secret_generator = SecretRandomGenerator::<DefaultRandomGenerator>::new(seeder.seed());
+    let mut encryption_generator =
+        EncryptionRandomGenerator::<DefaultRandomGenerator>::new(seeder.seed(), seeder);
+    let lwe_sk: LweSecretKeyOwned<u32> =
+        allocate_and_generate_new_binary_lwe_secret_key(LweDimension(16), &mut secret_generator);
+    let noise = Gaussian::from_dispersion_parameter(StandardDev(2f64.powi(-25)), 0.0);
+
+    let tgsw = TgswCiphertext::new_encrypt_with_key(
+        TGSW_MESSAGE,
+        TGSW_DECOMP_BASE,
+        TGSW_DECOMP_LEVEL,
+        &lwe_sk,
+        noise,
+        CiphertextModulus::new_native(),
+        &mut encryption_generator,
+    );
+    let seeded_tgsw = SeededTgswCiphertext::new_encrypt_with_key(
+        TGSW_MESSAGE,
+        TGSW_DECOMP_BASE,
+        TGSW_DECOMP_LEVEL,
+        &lwe_sk,
+        noise,
+        CiphertextModulus::new_native(),
+        seeder,
+    )
+    .unwrap();
+    store(&tgsw, "tgsw_ciphertext.bcode");
+    store(&seeded_tgsw, "seeded_tgsw_ciphertext.bcode");
+    store_key(lwe_sk, "lwe_secret_key.bcode");
+}
+
+//The fields of a serialized ciphertext, after the safe_serialization header and the version tag
+fn versioned_payload(tgsw: &TgswCiphertext) -> (Vec<u8>, Vec<u8>) {
+    let mut serialized = Vec::new();
+    safe_serialize(tgsw, &mut serialized, SIZE_LIMIT).unwrap();
+    let payload_len = bincode::serialized_size(&tgsw.versionize()).unwrap() as usize;
+    let header_len = serialized.len() - payload_len;
+    let payload = serialized.split_off(header_len);
+    (serialized, payload[4..].to_vec())
+}
+
+fn deserialize_tgsw(header: &[u8], version: u32, fields: &[u8]) -> Result<TgswCiphertext, String> {
+    let serialized = [header, &version.to_le_bytes(), fields].concat();
+    safe_deserialize(serialized.as_slice(), SIZE_LIMIT)
+}
+
+/*Malformed serialized ciphertexts are rejected with a TgswError rather than a panic: a V0 ciphertext without rows or
+with ragged rows, and a V1 (or plain serde) ciphertext whose container is not a multiple of lwe_size^2
+*/
+#[test]
+fn malformed_tgsw_ciphertexts() {
+    let lwe_size = LweSize(3);
+    let modulus = CiphertextModulus::new_native();
+    let tgsw = TgswCiphertext::from_container(vec![7u32; 2 * 3 * 3], lwe_size, modulus);
+    let (header, fields) = versioned_payload(&tgsw);
+    assert_eq!(deserialize_tgsw(&header, 1, &fields), Ok(tgsw.clone()));
+
+    //V1: data (u64 length + values), lwe_size (version tag + u64), ciphertext_modulus
+    let data_len = 8 + 4 * tgsw.as_ref().len();
+    let modulus_bytes = &fields[data_len + 12..];
+    for bad_lwe_size in [0u64, 4, u64::MAX] {
+        let fields = [
+            &fields[..data_len + 4],
+            &bad_lwe_size.to_le_bytes(),
+            modulus_bytes,
+        ]
+        .concat();
+        let err = deserialize_tgsw(&header, 1, &fields).unwrap_err();
+        let expected = TgswError::InvalidContainerLength {
+            container_len: 18,
+            lwe_size: LweSize(bad_lwe_size as usize),
+        };
+        assert!(err.contains(&expected.to_string()), "{err}");
+
+        //The same check without the versioning, where the fields are data, lwe_size and the unversioned modulus
+        let mut plain = bincode::serialize(&tgsw).unwrap();
+        plain[data_len..data_len + 8].copy_from_slice(&bad_lwe_size.to_le_bytes());
+        let err = bincode::deserialize::<TgswCiphertext>(&plain).unwrap_err();
+        assert!(err.to_string().contains(&expected.to_string()), "{err}");
+    }
+
+    //V0: the rows (u64 count, then u64 length + values for each row), ciphertext_modulus
+    let v0_fields = |rows: &[Vec<u32>]| {
+        let mut fields = (rows.len() as u64).to_le_bytes().to_vec();
+        for row in rows {
+            fields.extend((row.len() as u64).to_le_bytes());
+            fields.extend(row.iter().flat_map(|c| c.to_le_bytes()));
+        }
+        fields.extend(modulus_bytes);
+        fields
+    };
+    let rows: Vec<Vec<u32>> = tgsw.as_ref().chunks(3).map(<[u32]>::to_vec).collect();
+    assert_eq!(deserialize_tgsw(&header, 0, &v0_fields(&rows)), Ok(tgsw));
+
+    let err = deserialize_tgsw(&header, 0, &v0_fields(&[])).unwrap_err();
+    let expected = TgswError::InvalidContainerLength {
+        container_len: 0,
+        lwe_size: LweSize(0),
+    };
+    assert!(err.contains(&expected.to_string()), "{err}");
+
+    let mut ragged = rows;
+    ragged[4].pop();
+    let err = deserialize_tgsw(&header, 0, &v0_fields(&ragged)).unwrap_err();
+    let expected = TgswError::RaggedRows {
+        row: 4,
+        row_len: 2,
+        lwe_size,
+    };
+    assert!(err.contains(&expected.to_string()), "{err}");
+}
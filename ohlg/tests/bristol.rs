@@ -0,0 +1,156 @@
+/*Import of the bundled Bristol Fashion circuits (tests/bristol, the values are written bit 0 first),
+checked on every input with the plaintext simulation of the netlist, and the import errors
+*/
+use ohlg::bristol::*;
+use ohlg::gate_compiler::UnaryGateKind;
+use ohlg::netlist::{Circuit, GateOp};
+
+mod common;
+use common::{bits, value};
+
+fn import(text: &str) -> Circuit {
+    let circuit = parse_bristol_fashion(text).unwrap();
+    circuit.netlist.validate().unwrap();
+    assert_eq!(
+        circuit.netlist.num_params().unwrap(),
+        circuit.gate_plaintexts.len()
+    );
+    circuit
+}
+
+#[test]
+fn adder() {
+    let circuit = import(include_str!("bristol/adder4.txt"));
+    assert_eq!(circuit.netlist.gates.len(), 17);
+    for a in 0..16 {
+        for b in 0..16 {
+            let outputs = circuit.simulate(&[bits(a, 4), bits(b, 4)]).unwrap();
+            assert_eq!(value(&outputs[0]), a + b, "{a} + {b}");
+        }
+    }
+}
+
+#[test]
+fn comparator() {
+    let circuit = import(include_str!("bristol/lt4.txt"));
+    for a in 0..16 {
+        for b in 0..16 {
+            let outputs = circuit.simulate(&[bits(a, 4), bits(b, 4)]).unwrap();
+            assert_eq!(outputs, vec![vec![a < b]], "{a} < {b}");
+        }
+    }
+}
+
+//MAND, EQW and EQ
+#[test]
+fn multiple_and_copy_and_constant() {
+    let circuit = import(include_str!("bristol/misc.txt"));
+    let ports: Vec<&str> = circuit
+        .netlist
+        .outputs
+        .iter()
+        .map(|port| port.name.as_str())
+        .collect();
+    assert_eq!(ports, ["output0", "output1"]);
+
+    //The constant is a 1-input gate like the copy, only its parameters differ
+    assert!(circuit.netlist.gates[2..]
+        .iter()
+        .all(|gate| gate.op == GateOp::OneOp));
+    assert_eq!(
+        circuit.gate_plaintexts[3],
+        UnaryGateKind::Const1.plaintexts()
+    );
+
+    for x in 0..16 {
+        let m = bits(x, 4);
+        let outputs = circuit.simulate(std::slice::from_ref(&m)).unwrap();
+        assert_eq!(
+            outputs,
+            vec![vec![m[0] && m[1], m[2] && m[3]], vec![m[0], true]],
+            "{m:?}"
+        );
+    }
+}
+
+#[test]
+fn invalid_circuits() {
+    let header = "1 3\n2 1 1\n1 1\n";
+    assert_eq!(
+        parse_bristol_fashion(&format!("{header}2 1 0 1 2 OR\n")).unwrap_err(),
+        BristolError::UnsupportedGate {
+            line: 4,
+            op: "OR".to_string()
+        }
+    );
+    assert_eq!(
+        parse_bristol_fashion(&format!("{header}2 1 0 1 1 AND\n")).unwrap_err(),
+        BristolError::MultipleDrivers { line: 4, wire: 1 }
+    );
+    assert_eq!(
+        parse_bristol_fashion(&format!("{header}2 1 0 5 2 XOR\n")).unwrap_err(),
+        BristolError::WireOutOfRange {
+            line: 4,
+            wire: 5,
+            num_wires: 3
+        }
+    );
+    assert_eq!(
+        parse_bristol_fashion(&format!("{header}\n2 1 0 2 2 XOR\n")).unwrap_err(),
+        BristolError::UndrivenWire { line: 5, wire: 2 }
+    );
+    assert_eq!(
+        parse_bristol_fashion(&format!("{header}1 1 0 2 XOR\n")).unwrap_err(),
+        BristolError::Syntax {
+            line: 4,
+            message: "XOR takes 2 inputs and 1 outputs, found 1 and 1".to_string()
+        }
+    );
+    assert_eq!(
+        parse_bristol_fashion(header).unwrap_err(),
+        BristolError::GateCount {
+            expected: 1,
+            found: 0
+        }
+    );
+    assert_eq!(
+        parse_bristol_fashion("1 1\n0\n1 1\n1 1 1 0 EQ\n").unwrap_err(),
+        BristolError::ConstantWithoutInputs { line: 4 }
+    );
+
+    //Sizes that overflow usize, or that would allocate more than MAX_WIRES wires
+    let max = usize::MAX;
+    assert_eq!(
+        parse_bristol_fashion(&format!("1 {max}\n2 1 1\n1 1\n")).unwrap_err(),
+        BristolError::TooManyWires {
+            line: 1,
+            num_wires: max
+        }
+    );
+    assert_eq!(
+        parse_bristol_fashion(&format!("0 {}\n0\n0\n", MAX_WIRES + 1)).unwrap_err(),
+        BristolError::TooManyWires {
+            line: 1,
+            num_wires: MAX_WIRES + 1
+        }
+    );
+    let too_many_wires = |line: usize| BristolError::Syntax {
+        line,
+        message: "the input and output values have more wires than the circuit".to_string(),
+    };
+    assert_eq!(
+        parse_bristol_fashion(&format!("1 3\n2 {max} 2\n1 1\n")).unwrap_err(),
+        too_many_wires(2)
+    );
+    assert_eq!(
+        parse_bristol_fashion(&format!("1 3\n1 {max}\n1 1\n")).unwrap_err(),
+        too_many_wires(3)
+    );
+    assert_eq!(
+        parse_bristol_fashion(&format!("{header}{max} 1 0 1 2 XOR\n")).unwrap_err(),
+        BristolError::Syntax {
+            line: 4,
+            message: format!("expected {max} input and 1 output wires, found 3 wires")
+        }
+    );
+}
@@ -0,0 +1,31 @@
+/*The reference external product of the original implementation, shared by tests/tgsw.rs and benches/ext_product.rs
+(included with #[path]) to check and time the blocked kernel of TgswCiphertext::ext_product_lwe against it
+*/
+#![allow(non_snake_case)]
+use ohlg::tgsw::{decompose, TgswCiphertext};
+
+//Decompose each element of v into l digits in base B, most significant digit first
+pub fn vec_decompose(v: &[u32], B: u32, l: usize) -> Vec<u32> {
+    v.iter().flat_map(|&val| decompose(val, B, l)).collect()
+}
+
+//The original external product: scale and decompose the LWE coefficients, then one dot product per output column
+pub fn column_product(tgsw_ct: &TgswCiphertext, ct_lwe: &[u32], B: u32, l: usize) -> Vec<u32> {
+    let n_1 = ct_lwe.len();
+    let q: u64 = 1u64 << 32;
+    let bl = (B as u64).pow(l as u32);
+    let scaled: Vec<u32> = ct_lwe
+        .iter()
+        .map(|&c| ((c as u64 * bl + q / 2) / q) as u32)
+        .collect();
+    let decomposed_lwe_ct = vec_decompose(&scaled, B, l);
+
+    let matrix = tgsw_ct.as_ref();
+    let mut result = vec![0u32; n_1];
+    for (i, result_i) in result.iter_mut().enumerate() {
+        for (j, &digit) in decomposed_lwe_ct.iter().enumerate() {
+            *result_i = result_i.wrapping_add(digit.wrapping_mul(matrix[j * n_1 + i]));
+        }
+    }
+    result
+}
@@ -0,0 +1,23 @@
+//The helpers shared by the integration tests, each test crate uses a part of them
+#![allow(dead_code)]
+use ohlg::tgsw::{DecompositionKind, TgswParams};
+use tfhe::boolean::prelude::DEFAULT_PARAMETERS;
+
+pub mod column_product;
+
+//The width low bits of value, bit 0 first (the order of the netlist ports)
+pub fn bits(value: usize, width: usize) -> Vec<bool> {
+    (0..width).map(|i| value >> i & 1 == 1).collect()
+}
+
+//The value of bits, bit 0 first (the inverse of bits)
+pub fn value(bits: &[bool]) -> usize {
+    bits.iter()
+        .rev()
+        .fold(0, |value, &bit| 2 * value + bit as usize)
+}
+
+//The TGSW parameters of the gate tests: DEFAULT_PARAMETERS with B = 64, l = 2 and the balanced decomposition
+pub fn tgsw_params() -> TgswParams {
+    TgswParams::try_new(DEFAULT_PARAMETERS, 64, 2, DecompositionKind::Balanced).unwrap()
+}
@@ -0,0 +1,229 @@
+#![allow(non_snake_case)]
+/*The obfuscated gates end to end: the client encrypts the inputs and the gate parameters, the server evaluates the
+gate with its bootstrapping, and the client decrypts the output
+A server key takes about a minute to generate in a debug build and a bootstrapping a few seconds, so the keys are
+generated once per parameter set and shared by the tests, which keep their number of bootstrappings small
+*/
+use ohlg::gate_compiler::{new_encrypt_gate, GateKind};
+use ohlg::gate_params::{encrypt_nop_input, new_encrypt_mux_params, threshold_gate_plaintexts};
+use ohlg::noise::{decrypt_phase, EIGHTH};
+use ohlg::oblivious_evaluator::ObliviousEvaluator;
+use ohlg::oblivious_gates::*;
+use ohlg::tgsw::*;
+use once_cell::sync::Lazy;
+use tfhe::boolean::engine::bootstrapping::Bootstrapper;
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::seeders::new_seeder;
+
+mod common;
+use common::tgsw_params;
+
+const INPUTS: [(bool, bool); 4] = [(false, false), (false, true), (true, false), (true, true)];
+
+//The keys of DEFAULT_PARAMETERS, whose ciphertexts are encrypted under the small LWE key
+static KEYS: Lazy<(ClientKey, ServerKey)> = Lazy::new(|| {
+    let client_key = ClientKey::new(&DEFAULT_PARAMETERS);
+    let server_key = ServerKey::new(&client_key);
+    (client_key, server_key)
+});
+
+//The signed distance between the phase of ct and the expected phase, in units of 2^-32
+fn phase_error(ct: &Ciphertext, expected: u32, client_key: &ClientKey) -> i32 {
+    decrypt_phase(ct, client_key).wrapping_sub(expected) as i32
+}
+
+//The keys of DEFAULT_PARAMETERS_KS_PBS, whose ciphertexts are encrypted under the big key (EncryptionKeyChoice::Big)
+static KS_PBS_KEYS: Lazy<(ClientKey, ServerKey)> = Lazy::new(|| {
+    let client_key = ClientKey::new(&DEFAULT_PARAMETERS_KS_PBS);
+    let server_key = ServerKey::new(&client_key);
+    (client_key, server_key)
+});
+
+/*ob_gate_2op with the KS->PBS order: the TGSW parameter, the inputs and d are all under the big key of dimension
+glwe_dimension*polynomial_size, and ciphertexts under another key are rejected with an ObGateError
+*/
+#[test]
+fn ks_pbs_gate() {
+    let (client_key, server_key) = &*KS_PBS_KEYS;
+    let tgsw_params = TgswParams::try_new(
+        DEFAULT_PARAMETERS_KS_PBS,
+        64,
+        2,
+        DecompositionKind::Balanced,
+    )
+    .unwrap();
+    let big_lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+    assert_eq!(
+        tgsw_params.lwe_dimension(),
+        DEFAULT_PARAMETERS_KS_PBS
+            .glwe_dimension
+            .to_equivalent_lwe_dimension(DEFAULT_PARAMETERS_KS_PBS.polynomial_size)
+    );
+
+    let kind = GateKind::Xor;
+    let (A, ct_d) = new_encrypt_gate(kind, &tgsw_params, client_key);
+    for (m1, m2) in INPUTS {
+        let ct_res = ob_gate_2op(
+            &client_key.encrypt(m1),
+            &client_key.encrypt(m2),
+            &A,
+            &ct_d,
+            &tgsw_params,
+            server_key,
+        )
+        .unwrap();
+        assert_eq!(
+            client_key.decrypt(&ct_res),
+            kind.evaluate(m1, m2),
+            "{kind:?}({m1}, {m2})"
+        );
+    }
+
+    //An input under the small key of DEFAULT_PARAMETERS
+    let small_client_key = ClientKey::new(&DEFAULT_PARAMETERS);
+    let small_lwe_size = DEFAULT_PARAMETERS.lwe_dimension.to_lwe_size();
+    assert_eq!(
+        ob_gate_2op(
+            &client_key.encrypt(true),
+            &small_client_key.encrypt(true),
+            &A,
+            &ct_d,
+            &tgsw_params,
+            server_key,
+        )
+        .err(),
+        Some(ObGateError::LweSizeMismatch {
+            expected: big_lwe_size,
+            found: small_lwe_size,
+        })
+    );
+}
+
+/*The 3-input majority (the carry of a full adder) on the 8 input combinations, with the output encoded for another
+3-input gate (+-1/16 instead of +-1/8), and the custom output magnitude of the tfhe bootstrapping it relies on
+*/
+#[test]
+fn majority_with_output() {
+    let (client_key, server_key) = &*KEYS;
+    let tgsw_params = tgsw_params();
+    let (A, d) = threshold_gate_plaintexts(3, 2, false).unwrap();
+    let A = TgswCiphertext::new_encrypt(A, &tgsw_params, client_key);
+    let ct_d = client_key.encrypt_abs(d);
+    let output_magnitude = nop_input_magnitude(3).unwrap();
+    assert_eq!(output_magnitude, EIGHTH / 2);
+
+    for inputs in 0..8u32 {
+        let m = [inputs & 1 != 0, inputs & 2 != 0, inputs & 4 != 0];
+        let cts = m.map(|m_i| encrypt_nop_input(m_i, 3, client_key).unwrap());
+        let ct_res =
+            ob_gate_nop_with_output(&cts, &A, &ct_d, &tgsw_params, server_key, output_magnitude)
+                .unwrap();
+
+        let majority = inputs.count_ones() >= 2;
+        let expected = if majority {
+            output_magnitude
+        } else {
+            output_magnitude.wrapping_neg()
+        };
+        let error = phase_error(&ct_res, expected, client_key);
+        assert!(error.abs() < 1 << 25, "{m:?}: {error}");
+    }
+    assert_eq!(
+        ob_gate_nop_with_output(&[], &A, &ct_d, &tgsw_params, server_key, output_magnitude).err(),
+        Some(ObGateError::NoInputs)
+    );
+
+    //The bootstrapping itself maps a phase in (0, 1/2) to +output_value and in (1/2, 1) to -output_value
+    let mut seeder = new_seeder();
+    let mut bootstrapper = Bootstrapper::new(seeder.as_mut());
+    let output_value: u32 = 3 << 26;
+    for (phase, expected) in [
+        (3 << 28, output_value),
+        (13 << 28, output_value.wrapping_neg()),
+    ] {
+        let ct_lwe = match client_key.encrypt_abs(phase) {
+            Ciphertext::Encrypted(ct_lwe) => ct_lwe,
+            Ciphertext::Trivial(_) => panic!("Expected encrypted ciphertext"),
+        };
+        let ct_res =
+            bootstrapper.apply_bootstrapping_pattern_with_output(ct_lwe, server_key, output_value);
+        let error = phase_error(&ct_res, expected, client_key);
+        assert!(error.abs() < 1 << 25, "{phase}: {error}");
+    }
+}
+
+/*An ObliviousEvaluator reuses its scratch buffers from one gate to the next, so it is checked against the free
+functions over a sequence of gates of different kinds and fan-ins, and through a clone with fresh buffers
+*/
+#[test]
+fn evaluator_matches_free_functions() {
+    let (client_key, server_key) = &*KEYS;
+    let tgsw_params = tgsw_params();
+    let mut evaluator = ObliviousEvaluator::new(server_key.clone(), tgsw_params);
+
+    let kind = GateKind::Xor;
+    let (A, ct_d) = new_encrypt_gate(kind, &tgsw_params, client_key);
+    for (m1, m2) in INPUTS {
+        let (ct_1, ct_2) = (client_key.encrypt(m1), client_key.encrypt(m2));
+        let expected = ob_gate_2op(&ct_1, &ct_2, &A, &ct_d, &tgsw_params, server_key).unwrap();
+        let mut ct_res = ct_1;
+        evaluator
+            .ob_gate_2op_assign(&mut ct_res, &ct_2, &A, &ct_d)
+            .unwrap();
+        assert_eq!(client_key.decrypt(&ct_res), client_key.decrypt(&expected));
+        assert_eq!(client_key.decrypt(&ct_res), kind.evaluate(m1, m2));
+    }
+
+    //A 3-input majority after the 2-input gates, with a trivial input, on a clone of the evaluator
+    let (A, d) = threshold_gate_plaintexts(3, 2, false).unwrap();
+    let A = TgswCiphertext::new_encrypt(A, &tgsw_params, client_key);
+    let ct_d = client_key.encrypt_abs(d);
+    let inputs = [
+        encrypt_nop_input(true, 3, client_key).unwrap(),
+        Ciphertext::Trivial(false),
+        encrypt_nop_input(true, 3, client_key).unwrap(),
+    ];
+    let expected = ob_gate_nop(&inputs, &A, &ct_d, &tgsw_params, server_key).unwrap();
+    for evaluator in [&mut evaluator.clone(), &mut evaluator] {
+        let mut ct_res = inputs[0].clone();
+        evaluator
+            .ob_gate_nop_assign(&mut ct_res, &inputs[1..], &A, &ct_d, EIGHTH)
+            .unwrap();
+        assert_eq!(client_key.decrypt(&ct_res), client_key.decrypt(&expected));
+        assert!(client_key.decrypt(&ct_res));
+    }
+}
+
+/*The obfuscated multiplexer on the 8 input combinations, with the select on the middle input, through ob_mux and
+through ObliviousEvaluator::ob_mux_assign (the output is the sum of two bootstrappings, hence the wider bound)
+*/
+#[test]
+fn mux() {
+    let (client_key, server_key) = &*KEYS;
+    let tgsw_params = tgsw_params();
+    let mut evaluator = ObliviousEvaluator::new(server_key.clone(), tgsw_params);
+    let (select, then, else_) = (1, 2, 0);
+    let (A, ct_d) = new_encrypt_mux_params(select, then, else_, &tgsw_params, client_key).unwrap();
+
+    for inputs in 0..8u32 {
+        let m = [inputs & 1 != 0, inputs & 2 != 0, inputs & 4 != 0];
+        let expected = if m[select] { m[then] } else { m[else_] };
+        let expected_phase = if expected {
+            EIGHTH
+        } else {
+            EIGHTH.wrapping_neg()
+        };
+        let cts = m.map(|m_i| client_key.encrypt(m_i));
+
+        let ct_res = ob_mux(&cts, &A, &ct_d, &tgsw_params, server_key).unwrap();
+        let error = phase_error(&ct_res, expected_phase, client_key);
+        assert!(error.abs() < 1 << 26, "ob_mux {m:?}: {error}");
+
+        let [mut ct_0, ct_1, ct_2] = cts;
+        evaluator
+            .ob_mux_assign(&mut ct_0, &ct_1, &ct_2, &A, &ct_d)
+            .unwrap();
+        let error = phase_error(&ct_0, expected_phase, client_key);
+        assert!(error.abs() < 1 << 26, "ob_mux_assign {m:?}: {error}");
+    }
+}
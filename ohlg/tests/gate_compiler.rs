@@ -0,0 +1,117 @@
+#![allow(non_snake_case)]
+/*Self-check of the gate compiler: every 2-input boolean function is either rejected,
+or compiled to (A, d) parameters that compute it on the four input combinations,
+and every 1-input function is computed on both inputs
+*/
+use ohlg::gate_compiler::*;
+use ohlg::tgsw::*;
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::algorithms::lwe_linear_algebra::*;
+use tfhe::core_crypto::entities::lwe_ciphertext::LweCiphertext;
+
+const INPUTS: [(bool, bool); 4] = [(false, false), (false, true), (true, false), (true, true)];
+
+fn truth_table(index: u8) -> TruthTable {
+    [0, 1, 2, 3].map(|i| index >> i & 1 == 1)
+}
+
+#[test]
+fn compile_all_truth_tables() {
+    let mut expressible = 0;
+    for index in 0..16 {
+        let truth_table = truth_table(index);
+        let symmetric = truth_table[1] == truth_table[2];
+
+        match compile_truth_table(&truth_table) {
+            Ok((A, d)) => {
+                assert!(symmetric);
+                expressible += 1;
+                //A(c1+c2)+d on the plaintexts, with the levels at least 1/8 away from 0 and 1/2
+                for (i, (m1, m2)) in INPUTS.into_iter().enumerate() {
+                    let encode = |m: bool| if m { 1u32 << 29 } else { 7 << 29 };
+                    let phase = A
+                        .wrapping_mul(encode(m1).wrapping_add(encode(m2)))
+                        .wrapping_add(d);
+                    assert_eq!(phase < 1 << 31, truth_table[i]);
+                    assert!(phase % (1 << 31) >= 1 << 29 && phase % (1 << 31) <= 3 << 29);
+                }
+            }
+            Err(GateCompileError::NotSymmetric { truth_table: t }) => {
+                assert!(!symmetric);
+                assert_eq!(t, truth_table);
+            }
+        }
+    }
+    assert_eq!(expressible, GateKind::ALL.len());
+}
+
+/*The encrypted gates on the four input combinations, checked before the sign bootstrapping of ob_gate_2op:
+the client key decrypts A(c1+c2)+d directly (true iff the phase is in (0, 1/2)), so no server key is needed
+*/
+#[test]
+fn encrypted_gates() {
+    let tgsw_params =
+        TgswParams::try_new(DEFAULT_PARAMETERS, 64, 2, DecompositionKind::Balanced).unwrap();
+    let client_key = ClientKey::new(&tgsw_params.tfhe_params);
+
+    for kind in GateKind::ALL {
+        let (A, ct_d) =
+            new_encrypt_truth_table_gate(&kind.truth_table(), &tgsw_params, &client_key).unwrap();
+        let ct_d_lwe = match &ct_d {
+            Ciphertext::Encrypted(ct_lwe) => ct_lwe,
+            _ => panic!("Expected encrypted ciphertext"),
+        };
+        for (m1, m2) in INPUTS {
+            let (ct_1, ct_2) = match (client_key.encrypt(m1), client_key.encrypt(m2)) {
+                (Ciphertext::Encrypted(ct_1), Ciphertext::Encrypted(ct_2)) => (ct_1, ct_2),
+                _ => panic!("Expected encrypted ciphertext"),
+            };
+            let mut sum = LweCiphertext::new(0u32, ct_1.lwe_size(), ct_1.ciphertext_modulus());
+            lwe_ciphertext_add(&mut sum, &ct_1, &ct_2);
+
+            let mut ct_res = match A
+                .ext_product(&Ciphertext::Encrypted(sum), &tgsw_params)
+                .unwrap()
+            {
+                Ciphertext::Encrypted(ct_lwe) => ct_lwe,
+                _ => panic!("Expected encrypted ciphertext"),
+            };
+            lwe_ciphertext_add_assign(&mut ct_res, ct_d_lwe);
+
+            assert_eq!(
+                client_key.decrypt(&Ciphertext::Encrypted(ct_res)),
+                kind.evaluate(m1, m2),
+                "{kind:?}({m1}, {m2})"
+            );
+        }
+    }
+}
+
+//Same check for the 1-input gates, before the sign bootstrapping of ob_gate_1op_tgsw
+#[test]
+fn encrypted_unary_gates() {
+    let tgsw_params =
+        TgswParams::try_new(DEFAULT_PARAMETERS, 64, 2, DecompositionKind::Balanced).unwrap();
+    let client_key = ClientKey::new(&tgsw_params.tfhe_params);
+
+    for kind in UnaryGateKind::ALL {
+        let (A, ct_d) = new_encrypt_unary_gate(kind, &tgsw_params, &client_key);
+        let ct_d_lwe = match &ct_d {
+            Ciphertext::Encrypted(ct_lwe) => ct_lwe,
+            _ => panic!("Expected encrypted ciphertext"),
+        };
+        for m in [false, true] {
+            let mut ct_res = match A.ext_product(&client_key.encrypt(m), &tgsw_params).unwrap() {
+                Ciphertext::Encrypted(ct_lwe) => ct_lwe,
+                _ => panic!("Expected encrypted ciphertext"),
+            };
+            lwe_ciphertext_add_assign(&mut ct_res, ct_d_lwe);
+
+            assert_eq!(
+                client_key.decrypt(&Ciphertext::Encrypted(ct_res)),
+                kind.evaluate(m),
+                "{kind:?}({m})"
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+#![allow(non_snake_case)]
+/*The seeded encryption of the gate parameters (see new_encrypt_gate_params_with_seed): the output only depends on
+the seed, not on the number of threads of the rayon pool
+*/
+use ohlg::gate_params::*;
+use ohlg::noise::decrypt_phase;
+use ohlg::tgsw::*;
+use rayon::ThreadPoolBuilder;
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::commons::math::random::Seed;
+use tfhe::safe_serialization::safe_serialize;
+
+const SIZE_LIMIT: u64 = 1 << 30;
+
+#[test]
+fn seeded_encryption_is_thread_independent() {
+    let tgsw_params = TgswParams::new(DEFAULT_PARAMETERS, 64, 2);
+    let client_key = ClientKey::new(&DEFAULT_PARAMETERS);
+    //More gates than threads, so that the gates are split unevenly between the threads
+    let gate_plaintexts: Vec<(u32, u32)> = (0..11).map(|i| (i % 3, i << 29)).collect();
+
+    let encrypt_serialized = |threads: usize, seed: u128| {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let gate_params = pool.install(|| {
+            new_encrypt_gate_params_with_seed(
+                &gate_plaintexts,
+                &tgsw_params,
+                &client_key,
+                Seed(seed),
+            )
+        });
+        let mut serialized = Vec::new();
+        safe_serialize(&gate_params, &mut serialized, SIZE_LIMIT).unwrap();
+        (gate_params, serialized)
+    };
+
+    let (gate_params, single_thread) = encrypt_serialized(1, 42);
+    for threads in [2, 4] {
+        assert!(encrypt_serialized(threads, 42).1 == single_thread);
+    }
+    assert!(encrypt_serialized(4, 43).1 != single_thread);
+
+    //The parameters decrypt to the plaintexts
+    for ((C_gate, cd_gate), &(A, d)) in gate_params
+        .C_gate_params
+        .into_iter()
+        .zip(&gate_params.cd_gate_params)
+        .zip(&gate_plaintexts)
+    {
+        assert_eq!(C_gate.decompress().decrypt(&tgsw_params, &client_key), A);
+        let phase = decrypt_phase(cd_gate, &client_key);
+        assert!((phase.wrapping_sub(d) as i32).abs() < 1 << 20);
+    }
+}
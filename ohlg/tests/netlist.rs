@@ -0,0 +1,339 @@
+#![allow(non_snake_case)]
+/*The netlist of the ODM example, simulated on the plaintexts of its gate parameters, its serialization,
+and the structural checks of Netlist::validate, including the gate parameter slots
+The simulation is the noiseless encrypted evaluation, so no key is needed, except to check it against an execution
+on encrypted inputs
+*/
+use ohlg::gate_compiler::{GateKind, UnaryGateKind};
+use ohlg::gate_params::{mux_gate_param_slots, threshold_gate_plaintexts};
+use ohlg::netlist::*;
+use ohlg::noise::EIGHTH;
+use ohlg::oblivious_evaluator::ObliviousEvaluator;
+use ohlg::oblivious_gates::nop_input_magnitude;
+use ohlg::optimizer::{optimize, OptimizeError};
+use ohlg::tgsw::*;
+use ohlg::topology::{shuffle_gates, TopologyError};
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::commons::math::random::Seed;
+use tfhe::safe_serialization::{safe_deserialize, safe_serialize};
+
+const SIZE_LIMIT: u64 = 1 << 20;
+
+//The ODM circuit of client_odm: does any of the corpus characters match the search character
+fn odm_netlist(chars_num: usize, bits_per_char: usize) -> (Netlist, Vec<(u32, u32)>) {
+    let mut netlist = Netlist::new();
+    let search = netlist.add_input_port("search", bits_per_char);
+    let corpus = netlist.add_input_port("corpus", chars_num * bits_per_char);
+    let mut gate_plaintexts = Vec::new();
+    let mut add_gate = |netlist: &mut Netlist, kind: GateKind, inputs: Vec<WireId>| {
+        gate_plaintexts.push(kind.plaintexts());
+        netlist.add_gate(GateOp::TwoOp, inputs, gate_plaintexts.len() - 1)
+    };
+
+    let mut final_result = None;
+    for corpus_char in corpus.chunks(bits_per_char) {
+        let mut char_result = None;
+        for (&s, &c) in search.iter().zip(corpus_char) {
+            let bit = add_gate(&mut netlist, GateKind::Xnor, vec![s, c]);
+            char_result = Some(match char_result {
+                None => bit,
+                Some(r) => add_gate(&mut netlist, GateKind::And, vec![r, bit]),
+            });
+        }
+        let char_result = char_result.unwrap();
+        final_result = Some(match final_result {
+            None => char_result,
+            Some(r) => add_gate(&mut netlist, GateKind::Or, vec![r, char_result]),
+        });
+    }
+    netlist.add_output_port("match", vec![final_result.unwrap()]);
+    (netlist, gate_plaintexts)
+}
+
+fn bits(byte: u8) -> Vec<bool> {
+    (0..8).map(|i| byte >> i & 1 == 1).collect()
+}
+
+#[test]
+fn odm_simulation() {
+    let (netlist, gate_plaintexts) = odm_netlist(10, 8);
+    assert_eq!(netlist.gates.len(), 159);
+    assert_eq!(netlist.num_params().unwrap(), 159);
+    netlist.validate().unwrap();
+
+    let corpus: Vec<bool> = b"ABCDEFGHIJ".iter().flat_map(|&c| bits(c)).collect();
+    for search in [b'A', b'E', b'J', b'Z', b'a'] {
+        let outputs = netlist
+            .simulate(&[bits(search), corpus.clone()], &gate_plaintexts)
+            .unwrap();
+        assert_eq!(
+            outputs,
+            vec![vec![b"ABCDEFGHIJ".contains(&search)]],
+            "{}",
+            search as char
+        );
+    }
+}
+
+//A circuit with every gate operation on 3 inputs: y = (mux, nand, not mux, majority)
+fn gate_ops_circuit() -> Circuit {
+    let mut netlist = Netlist::new();
+    let x = netlist.add_input_port("x", 3);
+    let mut gate_plaintexts = Vec::new();
+
+    let mux = netlist.add_gate(GateOp::Mux, vec![x[0], x[1], x[2]], 0);
+    gate_plaintexts.extend(mux_gate_param_slots(1, 0, 2).unwrap());
+    let nand = netlist.add_gate(GateOp::TwoOp, vec![x[0], x[1]], gate_plaintexts.len());
+    gate_plaintexts.push(GateKind::Nand.plaintexts());
+    let not = netlist.add_gate(GateOp::OneOp, vec![mux], gate_plaintexts.len());
+    gate_plaintexts.push(UnaryGateKind::Not.plaintexts());
+    let reencode = GateOp::Reencode {
+        output_magnitude: nop_input_magnitude(3).unwrap(),
+    };
+    let reencoded = x
+        .iter()
+        .map(|&wire| netlist.add_gate(reencode, vec![wire], 0))
+        .collect();
+    let threshold = GateOp::Threshold {
+        output_magnitude: EIGHTH,
+    };
+    let majority = netlist.add_gate(threshold, reencoded, gate_plaintexts.len());
+    gate_plaintexts.push(threshold_gate_plaintexts(3, 2, false).unwrap());
+    netlist.add_output_port("y", vec![mux, nand, not, majority]);
+    Circuit {
+        netlist,
+        gate_plaintexts,
+    }
+}
+
+//Every gate operation, on the 8 combinations of 3 inputs
+#[test]
+fn gate_ops_simulation() {
+    let circuit = gate_ops_circuit();
+    assert_eq!(
+        circuit.netlist.num_params().unwrap(),
+        circuit.gate_plaintexts.len()
+    );
+    assert_eq!(circuit.netlist.bootstrap_count(), 8);
+    for i in 0..8 {
+        let m: Vec<bool> = (0..3).map(|b| i >> b & 1 == 1).collect();
+        let select = if m[1] { m[0] } else { m[2] };
+        let expected = vec![
+            select,
+            !(m[0] && m[1]),
+            !select,
+            m.iter().filter(|&&b| b).count() >= 2,
+        ];
+        assert_eq!(
+            circuit.simulate(std::slice::from_ref(&m)).unwrap(),
+            vec![expected],
+            "{m:?}"
+        );
+    }
+}
+
+/*The same circuit evaluated on encrypted inputs with Netlist::execute, against the simulation
+A bootstrapping takes a few seconds in a debug build, so only two input vectors (one per value of the select) are run
+*/
+#[test]
+fn gate_ops_execution() {
+    let circuit = gate_ops_circuit();
+    let client_key = ClientKey::new(&DEFAULT_PARAMETERS);
+    let server_key = ServerKey::new(&client_key);
+    let tgsw_params =
+        TgswParams::try_new(DEFAULT_PARAMETERS, 64, 2, DecompositionKind::Balanced).unwrap();
+    let gate_params = circuit.new_encrypt_gate_params(&tgsw_params, &client_key);
+    let C_gate_params: Vec<TgswCiphertext> = gate_params
+        .C_gate_params
+        .into_iter()
+        .map(SeededTgswCiphertext::decompress)
+        .collect();
+    let mut evaluator = ObliviousEvaluator::new(server_key, tgsw_params);
+
+    for m in [vec![true, true, false], vec![false, false, true]] {
+        let inputs = vec![m.iter().map(|&m_i| client_key.encrypt(m_i)).collect()];
+        let outputs = circuit
+            .netlist
+            .execute(
+                &mut evaluator,
+                &inputs,
+                &C_gate_params,
+                &gate_params.cd_gate_params,
+            )
+            .unwrap();
+        let decrypted: Vec<Vec<bool>> = outputs
+            .iter()
+            .map(|port| port.iter().map(|ct| client_key.decrypt(ct)).collect())
+            .collect();
+        assert_eq!(
+            decrypted,
+            circuit.simulate(std::slice::from_ref(&m)).unwrap(),
+            "{m:?}"
+        );
+    }
+}
+
+#[test]
+fn serialization() {
+    let (netlist, _) = odm_netlist(3, 8);
+    let mut serialized = Vec::new();
+    safe_serialize(&netlist, &mut serialized, SIZE_LIMIT).unwrap();
+    let deserialized: Netlist = safe_deserialize(serialized.as_slice(), SIZE_LIMIT).unwrap();
+    assert_eq!(deserialized, netlist);
+}
+
+#[test]
+fn invalid_netlists() {
+    let mut netlist = Netlist::new();
+    let x = netlist.add_input_port("x", 2);
+    let y = netlist.add_gate(GateOp::TwoOp, vec![x[0], x[1]], 0);
+    netlist.add_output_port("y", vec![y]);
+    netlist.validate().unwrap();
+
+    //A gate reading its own output
+    let mut invalid = netlist.clone();
+    invalid.gates[0].inputs[1] = y;
+    assert_eq!(
+        invalid.validate(),
+        Err(NetlistError::UndrivenWire { wire: y })
+    );
+
+    //A gate driving an input wire
+    let mut invalid = netlist.clone();
+    invalid.gates[0].output = x[1];
+    assert_eq!(
+        invalid.validate(),
+        Err(NetlistError::MultipleDrivers { wire: x[1] })
+    );
+
+    //An output port reading a wire out of range
+    let mut invalid = netlist.clone();
+    invalid.outputs[0].wires.push(3);
+    assert_eq!(
+        invalid.validate(),
+        Err(NetlistError::WireOutOfRange {
+            wire: 3,
+            num_wires: 3
+        })
+    );
+
+    //A 2-input gate with 3 inputs
+    let mut invalid = netlist.clone();
+    invalid.gates[0].inputs.push(x[0]);
+    assert_eq!(
+        invalid.validate(),
+        Err(NetlistError::InvalidArity {
+            gate: 0,
+            op: GateOp::TwoOp,
+            found: 3
+        })
+    );
+
+    //A 2-input threshold gate fed with the boolean encoding is fine, a 3-input one is not
+    let mut invalid = netlist.clone();
+    invalid.gates[0].op = GateOp::Threshold {
+        output_magnitude: EIGHTH,
+    };
+    invalid.validate().unwrap();
+    invalid.gates[0].inputs.push(x[0]);
+    assert_eq!(
+        invalid.validate(),
+        Err(NetlistError::InputEncoding {
+            gate: 0,
+            wire: x[0],
+            expected: nop_input_magnitude(3).unwrap(),
+            found: EIGHTH
+        })
+    );
+
+    //Missing gate parameters or inputs
+    assert_eq!(
+        netlist.simulate(&[vec![true, false]], &[]),
+        Err(NetlistError::MissingGateParams {
+            expected: 1,
+            found: 0
+        })
+    );
+    let and = [GateKind::And.plaintexts()];
+    assert_eq!(
+        netlist.simulate(&[vec![true]], &and),
+        Err(NetlistError::InputWidth {
+            port: "x".to_string(),
+            expected: 2,
+            found: 1
+        })
+    );
+
+    //Parameters putting the phase on the boundary of the sign bootstrapping
+    assert_eq!(
+        netlist.simulate(&[vec![true, false]], &[(1, 0)]),
+        Err(NetlistError::AmbiguousPhase { gate: 0 })
+    );
+}
+
+//Gate parameter slots out of range are reported as errors by every entry point, before any slot is read
+#[test]
+fn invalid_param_slots() {
+    let mut circuit = Circuit::new();
+    let x = circuit.netlist.add_input_port("x", 3);
+    let y = circuit.netlist.add_gate(GateOp::Mux, x.clone(), 0);
+    circuit
+        .gate_plaintexts
+        .extend(mux_gate_param_slots(1, 0, 2).unwrap());
+    circuit.netlist.add_output_port("y", vec![y]);
+    circuit.validate().unwrap();
+    assert_eq!(circuit.netlist.gates[0].param_slots(), Some(0..6));
+
+    //The slots of the multiplexer overflow usize
+    let mut overflowing = circuit.clone();
+    overflowing.netlist.gates[0].param = usize::MAX - 2;
+    assert_eq!(overflowing.netlist.gates[0].param_slots(), None);
+    let error = NetlistError::ParamOutOfRange {
+        gate: 0,
+        param: usize::MAX - 2,
+    };
+    assert_eq!(overflowing.netlist.num_params(), Err(error.clone()));
+    assert_eq!(overflowing.netlist.validate(), Err(error.clone()));
+    assert_eq!(overflowing.validate(), Err(error.clone()));
+    assert_eq!(overflowing.simulate(&[vec![true; 3]]), Err(error.clone()));
+    assert_eq!(
+        optimize(&overflowing, Seed(0)).err(),
+        Some(OptimizeError::Netlist(error.clone()))
+    );
+    assert_eq!(
+        shuffle_gates(&mut overflowing, Seed(0)),
+        Err(TopologyError::Netlist(error))
+    );
+
+    //The slots of the multiplexer fit in usize but not in gate_plaintexts
+    let mut missing = circuit.clone();
+    missing.netlist.gates[0].param = 1;
+    assert_eq!(missing.netlist.num_params(), Ok(7));
+    missing.netlist.validate().unwrap();
+    let error = NetlistError::MissingGateParams {
+        expected: 7,
+        found: 6,
+    };
+    assert_eq!(missing.validate(), Err(error.clone()));
+    assert_eq!(
+        optimize(&missing, Seed(0)).err(),
+        Some(OptimizeError::Netlist(error.clone()))
+    );
+    assert_eq!(
+        shuffle_gates(&mut missing, Seed(0)),
+        Err(TopologyError::Netlist(error))
+    );
+
+    //The param of a gate without parameters is ignored, whatever its value
+    let mut reencoded = circuit.clone();
+    let output_magnitude = nop_input_magnitude(3).unwrap();
+    let z = reencoded
+        .netlist
+        .add_gate(GateOp::Reencode { output_magnitude }, vec![y], usize::MAX);
+    reencoded.netlist.add_output_port("z", vec![z]);
+    assert_eq!(reencoded.netlist.gates[1].param_slots(), Some(0..0));
+    assert_eq!(reencoded.netlist.num_params(), Ok(6));
+    reencoded.validate().unwrap();
+    shuffle_gates(&mut reencoded, Seed(0)).unwrap();
+    assert_eq!(reencoded.gate_plaintexts.len(), 6);
+}
@@ -0,0 +1,125 @@
+#![allow(non_snake_case)]
+/*The client-side inspection helpers of noise.rs and TgswCiphertext::decrypt, under the small LWE key of
+DEFAULT_PARAMETERS and the big key of a parameter set with EncryptionKeyChoice::Big,
+and the external product noise model against measurements
+*/
+use ohlg::noise::{decrypt_eighths, decrypt_phase, ext_product_noise, noise, EIGHTH};
+use ohlg::tgsw::*;
+use tfhe::boolean::prelude::*;
+
+//The parameters of DEFAULT_PARAMETERS_KS_PBS with a GLWE key of dimension 64, so that the big key is cheap to use
+fn big_key_parameters() -> BooleanParameters {
+    BooleanParameters {
+        glwe_dimension: GlweDimension(1),
+        polynomial_size: PolynomialSize(64),
+        ..DEFAULT_PARAMETERS_KS_PBS
+    }
+}
+
+//The fresh noise is ~2^15 with DEFAULT_PARAMETERS and ~4 with the big key, far below this bound
+const NOISE_BOUND: i32 = 1 << 20;
+
+#[test]
+fn tgsw_decrypt() {
+    for tfhe_params in [DEFAULT_PARAMETERS, big_key_parameters()] {
+        let client_key = ClientKey::new(&tfhe_params);
+        for (B, l, decomp_kind) in [
+            (64, 2, DecompositionKind::Unsigned),
+            (16, 3, DecompositionKind::Balanced),
+        ] {
+            let tgsw_params = TgswParams::new_with_decomposition(tfhe_params, B, l, decomp_kind);
+            //Any message in Z/B^lZ is recovered, the larger ones are reduced mod B^l
+            let bl = B.pow(l as u32);
+            for (m, expected) in [(0, 0), (1, 1), (3, 3), (bl - 1, bl - 1), (bl + 5, 5)] {
+                let tgsw_ct = TgswCiphertext::new_encrypt(m, &tgsw_params, &client_key);
+                assert_eq!(tgsw_ct.decrypt(&tgsw_params, &client_key), expected);
+                let seeded = SeededTgswCiphertext::new_encrypt(m, &tgsw_params, &client_key);
+                assert_eq!(
+                    seeded.decompress().decrypt(&tgsw_params, &client_key),
+                    expected
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn phase_and_noise() {
+    for tfhe_params in [DEFAULT_PARAMETERS, big_key_parameters()] {
+        let client_key = ClientKey::new(&tfhe_params);
+
+        //Trivial ciphertexts have the exact encoding and no noise
+        for (m, eighths) in [(true, 1), (false, 7)] {
+            let ct = Ciphertext::Trivial(m);
+            assert_eq!(decrypt_phase(&ct, &client_key), eighths * EIGHTH);
+            assert_eq!(decrypt_eighths(&ct, &client_key), eighths);
+            assert_eq!(noise(&ct, &client_key), 0);
+        }
+
+        //The boolean encryptions are at +-1/8
+        for (m, eighths) in [(true, 1), (false, 7)] {
+            let ct = client_key.encrypt(m);
+            let phase = decrypt_phase(&ct, &client_key);
+            assert!((phase.wrapping_sub(eighths * EIGHTH) as i32).abs() < NOISE_BOUND);
+            assert_eq!(decrypt_eighths(&ct, &client_key), eighths);
+            assert!(noise(&ct, &client_key).abs() < NOISE_BOUND);
+        }
+
+        /*Every eighth with an offset of up to ~1/32 either way: the phase is rounded to the closest eighth (mod 8, so
+        7.75/8 rounds to 0) and the noise is the signed offset
+        */
+        for eighths in 0..8u32 {
+            for offset in [-(1i32 << 27), -(1 << 24), 0, 1 << 24, 1 << 27] {
+                let phase = (eighths * EIGHTH).wrapping_add(offset as u32);
+                let ct = client_key.encrypt_abs(phase);
+                let measured = decrypt_phase(&ct, &client_key);
+                assert!((measured.wrapping_sub(phase) as i32).abs() < NOISE_BOUND);
+                assert_eq!(decrypt_eighths(&ct, &client_key), eighths);
+                assert!((noise(&ct, &client_key) - offset).abs() < NOISE_BOUND);
+            }
+        }
+    }
+}
+
+/*The external product noise of ext_product_noise against the measured one, for TGSW(M) with M > 1:
+- B = 4, l = 5 drops 22 bits, so the rounding part (which grows with M) dominates
+- B = 16, l = 8 is exact (B^l = q), so only the digit part remains
+The input noise is multiplied by M, so the expected output variance is the model plus M^2 times the fresh variance.
+Each sample uses a fresh TGSW ciphertext under a key of dimension 64, as in the decomposition_noise test of tgsw.rs
+*/
+#[test]
+fn ext_product_noise_model() {
+    let tfhe_params = BooleanParameters {
+        lwe_dimension: LweDimension(64),
+        ..DEFAULT_PARAMETERS
+    };
+    let client_key = ClientKey::new(&tfhe_params);
+    let samples = 300;
+    for (B, l, m, rounding_dominates) in [(4, 5, 3, true), (16, 8, 2, false)] {
+        let tgsw_params =
+            TgswParams::new_with_decomposition(tfhe_params, B, l, DecompositionKind::Balanced);
+        let model = ext_product_noise(&tgsw_params, m);
+        if rounding_dominates {
+            assert!(model.rounding_variance > 4.0 * model.digit_variance);
+        } else {
+            assert_eq!(model.rounding_variance, 0.0);
+        }
+
+        let variance = (0..samples)
+            .map(|i| {
+                let tgsw_ct = TgswCiphertext::new_encrypt(m, &tgsw_params, &client_key);
+                let product = tgsw_ct
+                    .ext_product(&client_key.encrypt(i % 2 == 0), &tgsw_params)
+                    .unwrap();
+                (noise(&product, &client_key) as f64 / 2f64.powi(32)).powi(2)
+            })
+            .sum::<f64>()
+            / samples as f64;
+
+        let expected = model.variance() + (m * m) as f64 * model.fresh_variance;
+        assert!(
+            (0.75..1.3).contains(&(variance / expected)),
+            "B = {B}, l = {l}: measured {variance:e}, model {expected:e}"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+#![allow(non_snake_case)]
+/*Trivial inputs and typed errors of the external product used by the obfuscated gates,
+the plaintext branches of the obfuscated multiplexer, and the fan-ins and thresholds of the n-input gates
+The results are checked before the sign bootstrapping, so no server key is needed
+*/
+use ohlg::gate_params::{
+    encrypt_nop_input, mux_gate_param_slots, mux_gate_plaintexts, threshold_gate_plaintexts,
+};
+use ohlg::noise::{decrypt_eighths, EIGHTH};
+use ohlg::oblivious_gates::{nop_input_magnitude, ObGateError};
+use ohlg::tgsw::*;
+use tfhe::boolean::prelude::*;
+
+mod common;
+use common::tgsw_params;
+
+#[test]
+fn trivial_inputs() {
+    let tgsw_params = tgsw_params();
+    let client_key = ClientKey::new(&tgsw_params.tfhe_params);
+
+    //The trivial input is encoded as +-1/8, so A x Trivial(m) has a phase of +-A/8 (in eighths of the torus)
+    for (A, m, expected) in [(1, true, 1), (1, false, 7), (2, true, 2), (0, true, 0)] {
+        let tgsw_ct = TgswCiphertext::new_encrypt(A, &tgsw_params, &client_key);
+        let ct_res = tgsw_ct
+            .ext_product(&Ciphertext::Trivial(m), &tgsw_params)
+            .unwrap();
+        assert_eq!(
+            decrypt_eighths(&ct_res, &client_key),
+            expected,
+            "{A} x Trivial({m})"
+        );
+    }
+}
+
+#[test]
+fn mismatched_ciphertexts() {
+    let tgsw_params = tgsw_params();
+    let client_key = ClientKey::new(&tgsw_params.tfhe_params);
+    let tgsw_ct = TgswCiphertext::new_encrypt(1, &tgsw_params, &client_key);
+    let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+
+    //An input encrypted under the big key of the KS->PBS parameter set
+    let ks_pbs_client_key = ClientKey::new(&DEFAULT_PARAMETERS_KS_PBS);
+    let ks_pbs_lwe_size = TgswParams::new(DEFAULT_PARAMETERS_KS_PBS, 64, 2)
+        .lwe_dimension()
+        .to_lwe_size();
+    assert_eq!(
+        tgsw_ct
+            .ext_product(&ks_pbs_client_key.encrypt(true), &tgsw_params)
+            .unwrap_err(),
+        ObGateError::LweSizeMismatch {
+            expected: lwe_size,
+            found: ks_pbs_lwe_size,
+        }
+    );
+
+    //A TGSW ciphertext with another decomposition level
+    let tgsw_params_l3 =
+        TgswParams::try_new(DEFAULT_PARAMETERS, 16, 3, DecompositionKind::Balanced).unwrap();
+    assert_eq!(
+        tgsw_ct
+            .ext_product(&client_key.encrypt(true), &tgsw_params_l3)
+            .unwrap_err(),
+        ObGateError::TgswMismatch {
+            expected_lwe_size: lwe_size,
+            found_lwe_size: lwe_size,
+            expected_decomp_level: 3,
+            found_decomp_level: 2,
+        }
+    );
+}
+
+//Every assignment of the select, then and else inputs, on the 8 input combinations
+#[test]
+fn mux_plaintexts() {
+    let roles = [
+        (0, 1, 2),
+        (0, 2, 1),
+        (1, 0, 2),
+        (1, 2, 0),
+        (2, 0, 1),
+        (2, 1, 0),
+    ];
+    for (select, then, else_) in roles {
+        let (A, d) = mux_gate_plaintexts(select, then, else_).unwrap();
+        for x in 0..8 {
+            let m = [x & 1 == 1, x >> 1 & 1 == 1, x >> 2 & 1 == 1];
+            let encode = |m: bool| if m { EIGHTH } else { EIGHTH.wrapping_neg() };
+
+            //Each branch is 1/8 or 3/8 away from the boundaries 0 and 1/2 of the sign bootstrapping
+            let branches = [0, 1].map(|j| {
+                let phase = (0..3).fold(d[j], |phase, i| {
+                    phase.wrapping_add(A[j][i].wrapping_mul(encode(m[i])))
+                });
+                assert_eq!(phase % (2 * EIGHTH), EIGHTH);
+                phase < 4 * EIGHTH
+            });
+            let expected = if m[select] { m[then] } else { m[else_] };
+            assert!(!(branches[0] && branches[1]));
+            assert_eq!(
+                branches[0] || branches[1],
+                expected,
+                "{select} ? {then} : {else_}, {m:?}"
+            );
+        }
+    }
+
+    //Out of range or repeated input indices
+    for (select, then, else_) in [(3, 1, 2), (0, 1, 5), (0, 0, 2), (1, 2, 2), (2, 1, 2)] {
+        let error = ObGateError::InvalidMuxInputs {
+            select,
+            then,
+            else_,
+        };
+        assert_eq!(mux_gate_plaintexts(select, then, else_), Err(error));
+        assert_eq!(mux_gate_param_slots(select, then, else_), Err(error));
+    }
+}
+
+//The fan-ins and thresholds that the n-input gates reject, with the encoding of the accepted fan-ins
+#[test]
+fn threshold_errors() {
+    let client_key = ClientKey::new(&DEFAULT_PARAMETERS);
+    for (fan_in, magnitude) in [
+        (1, EIGHTH),
+        (2, EIGHTH),
+        (3, EIGHTH / 2),
+        (5, EIGHTH / 4),
+        (1 << 30, 1),
+    ] {
+        assert_eq!(nop_input_magnitude(fan_in), Ok(magnitude));
+    }
+    for fan_in in [(1 << 30) + 1, usize::MAX] {
+        assert_eq!(
+            nop_input_magnitude(fan_in),
+            Err(ObGateError::FanInTooLarge { fan_in })
+        );
+    }
+    assert_eq!(nop_input_magnitude(0), Err(ObGateError::NoInputs));
+    assert_eq!(
+        encrypt_nop_input(true, 0, &client_key).err(),
+        Some(ObGateError::NoInputs)
+    );
+
+    assert_eq!(
+        threshold_gate_plaintexts(0, 0, false),
+        Err(ObGateError::NoInputs)
+    );
+    assert_eq!(
+        threshold_gate_plaintexts(3, 5, false),
+        Err(ObGateError::ThresholdOutOfRange {
+            threshold: 5,
+            fan_in: 3
+        })
+    );
+    //The constant gates at the ends of the range
+    assert_eq!(threshold_gate_plaintexts(3, 0, false), Ok((0, EIGHTH)));
+    assert_eq!(
+        threshold_gate_plaintexts(3, 4, false),
+        Ok((0, EIGHTH.wrapping_neg()))
+    );
+}
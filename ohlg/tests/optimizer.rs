@@ -0,0 +1,185 @@
+/*The netlist optimizer on small circuits and on the bundled imports: the number of gates removed by each pass,
+and the equivalence of the optimized circuits, checked on every input by the plaintext simulation
+*/
+use ohlg::bristol::parse_bristol_fashion;
+use ohlg::gate_compiler::{GateKind, UnaryGateKind};
+use ohlg::gate_params::mux_gate_param_slots;
+use ohlg::netlist::{Circuit, GateOp};
+use ohlg::optimizer::*;
+use ohlg::yosys::{parse_blif, parse_yosys_json};
+use tfhe::core_crypto::commons::math::random::Seed;
+
+fn optimize_checked(circuit: &Circuit) -> (Circuit, OptimizationReport) {
+    let (optimized, report) = optimize(circuit, Seed(0)).unwrap();
+    optimized.netlist.validate().unwrap();
+    assert_eq!(
+        optimized.netlist.num_params().unwrap(),
+        optimized.gate_plaintexts.len()
+    );
+    assert_eq!(report.gates_after, optimized.netlist.gates.len());
+    assert_eq!(report.bootstraps_after, optimized.netlist.bootstrap_count());
+    (optimized, report)
+}
+
+fn kinds(circuit: &Circuit) -> Vec<(GateOp, (u32, u32))> {
+    circuit
+        .netlist
+        .gates
+        .iter()
+        .map(|gate| (gate.op, circuit.gate_plaintexts[gate.param]))
+        .collect()
+}
+
+#[test]
+fn inverter_folding() {
+    let mut circuit = Circuit::new();
+    let x = circuit.netlist.add_input_port("x", 2);
+    let not_x = circuit.add_gate_1op(UnaryGateKind::Not, x[0]);
+    let not_y = circuit.add_gate_1op(UnaryGateKind::Not, x[1]);
+    //NOT on both inputs: NOT x AND NOT y = x NOR y
+    let nor = circuit.add_gate_2op(GateKind::And, [not_x, not_y]);
+    //NOT on the output: a NAND gate next to the AND gate, which is then dead
+    let and = circuit.add_gate_2op(GateKind::And, [x[0], x[1]]);
+    let nand = circuit.add_gate_1op(UnaryGateKind::Not, and);
+    //NOT on an input of a XOR: XNOR
+    let xnor = circuit.add_gate_2op(GateKind::Xor, [not_x, x[1]]);
+    circuit.netlist.add_output_port("y", vec![nor, nand, xnor]);
+
+    let (optimized, report) = optimize_checked(&circuit);
+    assert_eq!(
+        kinds(&optimized),
+        [GateKind::Nor, GateKind::Xnor, GateKind::Nand]
+            .map(|kind| (GateOp::TwoOp, kind.plaintexts()))
+    );
+    assert_eq!(report.folded_inverters, 3);
+    assert_eq!(report.bootstraps_saved(), 3);
+    assert_eq!(report.checked_inputs, 4);
+
+    //x AND NOT y is not symmetric, the NOT gate is kept
+    let mut circuit = Circuit::new();
+    let x = circuit.netlist.add_input_port("x", 2);
+    let not_y = circuit.add_gate_1op(UnaryGateKind::Not, x[1]);
+    let buf = circuit.add_gate_1op(UnaryGateKind::Buf, not_y);
+    let and_not = circuit.add_gate_2op(GateKind::And, [x[0], buf]);
+    circuit.netlist.add_output_port("y", vec![and_not]);
+    let (optimized, report) = optimize_checked(&circuit);
+    assert_eq!(
+        kinds(&optimized),
+        [
+            (GateOp::OneOp, UnaryGateKind::Not.plaintexts()),
+            (GateOp::TwoOp, GateKind::And.plaintexts())
+        ]
+    );
+    assert_eq!(report.folded_inverters, 1);
+    assert_eq!(report.bootstraps_saved(), 1);
+}
+
+#[test]
+fn constant_propagation() {
+    let mut circuit = Circuit::new();
+    let x = circuit.netlist.add_input_port("x", 2);
+    let one = circuit.add_gate_1op(UnaryGateKind::Const1, x[0]);
+    let zero = circuit.add_gate_1op(UnaryGateKind::Const0, x[0]);
+    //x AND 1 = x, x XOR 1 = NOT x, x OR 1 = 1, 1 AND 0 = 0
+    let and = circuit.add_gate_2op(GateKind::And, [x[0], one]);
+    let xor = circuit.add_gate_2op(GateKind::Xor, [x[1], one]);
+    let or = circuit.add_gate_2op(GateKind::Or, [x[1], one]);
+    let and_constants = circuit.add_gate_2op(GateKind::And, [one, zero]);
+    //(NOT y) XNOR x = y XOR x
+    let nor = circuit.add_gate_2op(GateKind::Xnor, [xor, and]);
+    circuit
+        .netlist
+        .add_output_port("y", vec![and, or, and_constants, nor]);
+
+    let (optimized, report) = optimize_checked(&circuit);
+    //Only the constant outputs need a gate
+    assert_eq!(
+        kinds(&optimized),
+        [
+            (GateOp::TwoOp, GateKind::Xor.plaintexts()),
+            (GateOp::OneOp, UnaryGateKind::Const1.plaintexts()),
+            (GateOp::OneOp, UnaryGateKind::Const0.plaintexts()),
+        ]
+    );
+    assert_eq!(report.bootstraps_saved(), 4);
+    assert_eq!(report.constant_gates, 6);
+}
+
+#[test]
+fn duplicates_and_dead_gates() {
+    let mut circuit = Circuit::new();
+    let x = circuit.netlist.add_input_port("x", 3);
+    let a = circuit.add_gate_2op(GateKind::And, [x[0], x[1]]);
+    let b = circuit.add_gate_2op(GateKind::And, [x[1], x[0]]);
+    let c = circuit.add_gate_2op(GateKind::Or, [a, x[2]]);
+    let d = circuit.add_gate_2op(GateKind::Or, [x[2], b]);
+    let dead = circuit.add_gate_2op(GateKind::Xor, [c, x[0]]);
+    circuit.add_gate_1op(UnaryGateKind::Not, dead);
+    //The multiplexer is kept as is, and merged with its copy
+    circuit
+        .gate_plaintexts
+        .extend(mux_gate_param_slots(0, 1, 2).unwrap());
+    let param = circuit.gate_plaintexts.len() - 6;
+    let mux = circuit
+        .netlist
+        .add_gate(GateOp::Mux, vec![c, x[1], x[2]], param);
+    let mux_copy = circuit
+        .netlist
+        .add_gate(GateOp::Mux, vec![d, x[1], x[2]], param);
+    circuit
+        .netlist
+        .add_output_port("y", vec![c, d, mux, mux_copy]);
+
+    let (optimized, report) = optimize_checked(&circuit);
+    assert_eq!(optimized.netlist.gates.len(), 3);
+    assert_eq!(report.merged_gates, 3);
+    assert_eq!(report.dead_gates, 1);
+    assert_eq!(report.folded_inverters, 1);
+    assert_eq!(report.bootstraps_before, 10);
+    assert_eq!(report.bootstraps_after, 4);
+    assert_eq!(report.bootstraps_saved(), 6);
+}
+
+//The bundled imports: the constants and inverters of the Bristol comparator, the NOT gates of the ANDNOT cells of Yosys
+#[test]
+fn imported_circuits() {
+    for (text, saved) in [
+        (include_str!("bristol/adder4.txt"), 0),
+        (include_str!("bristol/lt4.txt"), 8),
+        (include_str!("bristol/misc.txt"), 1),
+    ] {
+        let circuit = parse_bristol_fashion(text).unwrap();
+        let (_, report) = optimize_checked(&circuit);
+        assert_eq!(report.bootstraps_saved(), saved, "{report:?}");
+    }
+    for (circuit, saved) in [
+        (parse_blif(include_str!("yosys/cmp2.blif")).unwrap(), 4),
+        (
+            parse_yosys_json(include_str!("yosys/cmp2.json"), None).unwrap(),
+            3,
+        ),
+    ] {
+        let (_, report) = optimize_checked(&circuit);
+        assert_eq!(report.bootstraps_saved(), saved, "{report:?}");
+    }
+}
+
+#[test]
+fn equivalence_check() {
+    let mut and = Circuit::new();
+    let x = and.netlist.add_input_port("x", 2);
+    let y = and.add_gate_2op(GateKind::And, [x[0], x[1]]);
+    and.netlist.add_output_port("y", vec![y]);
+    let mut or = and.clone();
+    or.gate_plaintexts[0] = GateKind::Or.plaintexts();
+
+    assert_eq!(check_equivalence(&and, &and, 16, Seed(0)), Ok(4));
+    //Sampled: 3 random inputs out of the 4
+    assert_eq!(check_equivalence(&and, &and, 3, Seed(0)), Ok(3));
+    assert_eq!(
+        check_equivalence(&and, &or, 16, Seed(0)),
+        Err(OptimizeError::NotEquivalent {
+            inputs: vec![vec![true, false]]
+        })
+    );
+}
@@ -0,0 +1,437 @@
+/*The TGSW layer on random and freshly encrypted ciphertexts: the external product kernel against the column-wise
+product of the original implementation, the u64 torus with native and custom moduli, several parameter
+sets in one process, the public-key encryption, the homomorphic operations and the parameter checks
+*/
+#![allow(non_snake_case)]
+use ohlg::noise::{decrypt_eighths, ext_product_noise, fresh_noise_variance, noise};
+use ohlg::tgsw::*;
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::commons::generators::DeterministicSeeder;
+use tfhe::core_crypto::commons::math::random::{DefaultRandomGenerator, RandomGenerator, Seed};
+use tfhe::core_crypto::prelude::*;
+
+mod common;
+use common::column_product::column_product;
+
+fn random_vec(generator: &mut RandomGenerator<DefaultRandomGenerator>, len: usize) -> Vec<u32> {
+    (0..len).map(|_| generator.random_uniform()).collect()
+}
+
+//The blocked kernel gives the same values as the column-wise product, with few or many non-zero digits
+#[test]
+fn ext_product_kernel() {
+    let mut generator = RandomGenerator::<DefaultRandomGenerator>::new(Seed(0));
+    for tgsw_params in [
+        TgswParams::new(DEFAULT_PARAMETERS, 64, 2),
+        TgswParams::new(TFHE_LIB_PARAMETERS, 16, 3),
+    ] {
+        let B = tgsw_params.decomp_base;
+        let l = tgsw_params.decomp_level;
+        let lwe_size = tgsw_params.lwe_dimension().to_lwe_size();
+        let tgsw_ct = TgswCiphertext::from_container(
+            random_vec(&mut generator, l * lwe_size.0 * lwe_size.0),
+            lwe_size,
+            CiphertextModulus::new_native(),
+        );
+
+        //A random ciphertext, and a sparse one whose number of non-zero digits is not a multiple of the block size
+        let mut sparse = vec![0u32; lwe_size.0];
+        for (i, c) in sparse.iter_mut().enumerate().step_by(97) {
+            *c = (i as u32).wrapping_mul(0x9e37_79b9);
+        }
+        for ct_lwe in [random_vec(&mut generator, lwe_size.0), sparse] {
+            let expected = column_product(&tgsw_ct, &ct_lwe, B, l);
+            let ct_lwe = LweCiphertext::from_container(ct_lwe, CiphertextModulus::new_native());
+            let product = tgsw_ct.ext_product_lwe(&ct_lwe, B, l, DecompositionKind::Unsigned);
+            assert_eq!(product.as_ref(), expected.as_slice());
+        }
+    }
+}
+
+//The distance between two values mod q
+fn mod_distance(a: u128, b: u128, q: u128) -> u128 {
+    let d = (a + q - b) % q;
+    d.min(q - d)
+}
+
+/*The u64 torus with the native modulus, a non-native power of two and a prime modulus, with B^l = 2^48 > 2^32:
+decryption, external product, and the seeded ciphertexts (which only support the powers of two)
+*/
+#[test]
+fn u64_torus_and_custom_moduli() {
+    let mut seeder = DeterministicSeeder::<DefaultRandomGenerator>::new(Seed(0));
+    let mut secret_generator = SecretRandomGenerator::<DefaultRandomGenerator>::new(seeder.seed());
+    let mut encryption_generator =
+        EncryptionRandomGenerator::<DefaultRandomGenerator>::new(seeder.seed(), &mut seeder);
+    let lwe_sk: LweSecretKeyOwned<u64> =
+        allocate_and_generate_new_binary_lwe_secret_key(LweDimension(32), &mut secret_generator);
+    let noise = Gaussian::from_dispersion_parameter(StandardDev(2f64.powi(-50)), 0.0);
+    let (B, l) = (256, 6);
+    let m = 3u64;
+
+    for (ciphertext_modulus, q) in [
+        (CiphertextModulus::new_native(), 1u128 << 64),
+        (CiphertextModulus::try_new_power_of_2(62).unwrap(), 1 << 62),
+        (
+            CiphertextModulus::try_new((1 << 61) - 1).unwrap(),
+            (1 << 61) - 1,
+        ),
+    ] {
+        let tgsw_ct = TgswCiphertext::new_encrypt_with_key(
+            m,
+            B,
+            l,
+            &lwe_sk,
+            noise,
+            ciphertext_modulus,
+            &mut encryption_generator,
+        );
+        assert_eq!(tgsw_ct.decrypt_with_key(B, l, &lwe_sk), m);
+
+        //TGSW(3) x LWE(q/8) is an LWE encryption of 3q/8, up to the noise of the product
+        let ct_lwe = allocate_and_encrypt_new_lwe_ciphertext(
+            &lwe_sk,
+            Plaintext((q / 8) as u64),
+            noise,
+            ciphertext_modulus,
+            &mut encryption_generator,
+        );
+        for decomp_kind in [DecompositionKind::Unsigned, DecompositionKind::Balanced] {
+            let product = tgsw_ct.ext_product_lwe(&ct_lwe, B, l, decomp_kind);
+            let phase = decrypt_lwe_ciphertext(&lwe_sk, &product).0 as u128;
+            assert!(mod_distance(phase, 3 * (q / 8), q) < q >> 30, "{phase}");
+        }
+
+        let seeded = SeededTgswCiphertext::new_encrypt_with_key(
+            m,
+            B,
+            l,
+            &lwe_sk,
+            noise,
+            ciphertext_modulus,
+            &mut seeder,
+        );
+        if ciphertext_modulus.is_power_of_two() {
+            let decompressed = seeded.unwrap().decompress();
+            assert_eq!(decompressed.decrypt_with_key(B, l, &lwe_sk), m);
+        } else {
+            assert_eq!(
+                seeded.err(),
+                Some(TgswError::UnsupportedCiphertextModulus { modulus: q })
+            );
+        }
+    }
+}
+
+//Two decompositions used side by side in one process, each encryption and external product with its own gadget
+#[test]
+fn two_parameter_sets() {
+    let client_key = ClientKey::new(&DEFAULT_PARAMETERS);
+    let tgsw_params = [
+        TgswParams::new(DEFAULT_PARAMETERS, 64, 2),
+        TgswParams::new_with_decomposition(DEFAULT_PARAMETERS, 16, 3, DecompositionKind::Balanced),
+    ];
+    for tgsw_params in [tgsw_params[0], tgsw_params[1], tgsw_params[0]] {
+        let tgsw_ct = TgswCiphertext::new_encrypt(3, &tgsw_params, &client_key);
+        let seeded = SeededTgswCiphertext::new_encrypt(3, &tgsw_params, &client_key).decompress();
+        for tgsw_ct in [tgsw_ct, seeded] {
+            assert_eq!(tgsw_ct.decrypt(&tgsw_params, &client_key), 3);
+
+            //TGSW(3) x (+-1/8) = +-3/8
+            for (m, eighths) in [(true, 3), (false, 5)] {
+                let product = tgsw_ct
+                    .ext_product(&client_key.encrypt(m), &tgsw_params)
+                    .unwrap();
+                assert_eq!(decrypt_eighths(&product, &client_key), eighths);
+            }
+        }
+    }
+}
+
+/*Noise analysis of the decompositions (see DecompositionKind): with DEFAULT_PARAMETERS, B = 64 and l = 2, the external
+product TGSW(1) x LWE(+-1/8) adds a digit noise of standard deviation ~8.7e-3 with the unsigned digits and ~4.4e-3 with
+the balanced ones, on top of the rounding noise (~1.4e-3) and the noise of the input itself.
+The measured variance must match the model of noise::ext_product_noise (the relative error of the estimate is
+~sqrt(2/samples), i.e. ~7%), and the balanced digits must divide the digit part by ~4.
+Each sample uses a fresh TGSW ciphertext: the unsigned digits have a mean of (B-1)/2, so for a fixed TGSW ciphertext
+the digit noise has a bias (B-1)/2 * (sum of its row noises), and only varies by Var(d) = B^2/12 around it.
+The model gives the variance over the TGSW encryptions, E[d^2] = B^2/3 (the balanced digits have no such bias).
+The samples use a key of dimension 64 with the noise of DEFAULT_PARAMETERS, so that a TGSW ciphertext is cheap to encrypt.
+*/
+#[test]
+fn decomposition_noise() {
+    for (decomp_kind, documented_digit_std) in [
+        (DecompositionKind::Unsigned, 8.7e-3),
+        (DecompositionKind::Balanced, 4.4e-3),
+    ] {
+        let tgsw_params =
+            TgswParams::new_with_decomposition(DEFAULT_PARAMETERS, 64, 2, decomp_kind);
+        let model = ext_product_noise(&tgsw_params, 1);
+        assert!((model.digit_variance.sqrt() / documented_digit_std - 1.0).abs() < 0.05);
+        assert!((model.rounding_variance.sqrt() / 1.4e-3 - 1.0).abs() < 0.05);
+    }
+
+    let tfhe_params = BooleanParameters {
+        lwe_dimension: LweDimension(64),
+        ..DEFAULT_PARAMETERS
+    };
+    let client_key = ClientKey::new(&tfhe_params);
+    let samples = 400;
+    let mut measured = Vec::new();
+    for decomp_kind in [DecompositionKind::Unsigned, DecompositionKind::Balanced] {
+        let tgsw_params = TgswParams::new_with_decomposition(tfhe_params, 64, 2, decomp_kind);
+        let variance = (0..samples)
+            .map(|i| {
+                let tgsw_ct = TgswCiphertext::new_encrypt(1, &tgsw_params, &client_key);
+                let product = tgsw_ct
+                    .ext_product(&client_key.encrypt(i % 2 == 0), &tgsw_params)
+                    .unwrap();
+                (noise(&product, &client_key) as f64 / 2f64.powi(32)).powi(2)
+            })
+            .sum::<f64>()
+            / samples as f64;
+
+        let model = ext_product_noise(&tgsw_params, 1);
+        let expected = model.variance() + fresh_noise_variance(&tfhe_params);
+        assert!(
+            (0.75..1.3).contains(&(variance / expected)),
+            "{decomp_kind:?}: measured {variance:e}, model {expected:e}"
+        );
+        measured.push(variance);
+    }
+    assert!(measured[1] < measured[0] / 2.5, "{measured:?}");
+}
+
+/*The public-key encryption (see TgswCiphertext::new_encrypt_with_public_key) with a parameter set with
+EncryptionKeyChoice::Big (the one of DEFAULT_PARAMETERS_KS_PBS, with a GLWE key of dimension 64 so that the public
+key is cheap to generate), and the rejected keys
+*/
+#[test]
+fn public_key_encryption() {
+    let tfhe_params = BooleanParameters {
+        glwe_dimension: GlweDimension(1),
+        polynomial_size: PolynomialSize(64),
+        ..DEFAULT_PARAMETERS_KS_PBS
+    };
+    let client_key = ClientKey::new(&tfhe_params);
+    let public_key = PublicKey::new(&client_key);
+    let compressed_public_key = CompressedPublicKey::new(&client_key);
+    let tgsw_params =
+        TgswParams::new_with_decomposition(tfhe_params, 64, 2, DecompositionKind::Balanced);
+    assert_eq!(tgsw_params.lwe_dimension(), LweDimension(64));
+
+    //The boolean encryptions under the big key decrypt with the client key
+    for m in [true, false] {
+        let mu = if m {
+            1u32 << 29
+        } else {
+            (1u32 << 29).wrapping_neg()
+        };
+        for ct in [
+            client_key.encrypt_abs(mu),
+            public_key.encrypt_abs(mu),
+            compressed_public_key.encrypt_abs(mu),
+        ] {
+            assert_eq!(client_key.decrypt(&ct), m);
+        }
+    }
+
+    let tgsw_cts = [
+        TgswCiphertext::new_encrypt_with_public_key(3, &tgsw_params, &public_key).unwrap(),
+        TgswCiphertext::new_encrypt_with_compressed_public_key(
+            3,
+            &tgsw_params,
+            &compressed_public_key,
+        )
+        .unwrap(),
+    ];
+    for tgsw_ct in tgsw_cts {
+        assert_eq!(tgsw_ct.decrypt(&tgsw_params, &client_key), 3);
+        for (m, eighths) in [(true, 3), (false, 5)] {
+            let product = tgsw_ct
+                .ext_product(&public_key.encrypt(m), &tgsw_params)
+                .unwrap();
+            assert_eq!(decrypt_eighths(&product, &client_key), eighths);
+        }
+    }
+
+    //A public key under another key than the one of the parameter set
+    let other_params = TgswParams::new(
+        BooleanParameters {
+            polynomial_size: PolynomialSize(128),
+            ..tfhe_params
+        },
+        64,
+        2,
+    );
+    assert_eq!(
+        TgswCiphertext::new_encrypt_with_public_key(3, &other_params, &public_key).err(),
+        Some(TgswError::KeyDimensionMismatch {
+            expected: LweDimension(128),
+            found: LweDimension(64),
+        })
+    );
+
+    //EncryptionKeyChoice::Small is rejected before any encryption
+    let small_params = TgswParams::new(
+        BooleanParameters {
+            encryption_key_choice: EncryptionKeyChoice::Small,
+            ..tfhe_params
+        },
+        64,
+        2,
+    );
+    assert_eq!(
+        TgswCiphertext::new_encrypt_with_public_key(3, &small_params, &public_key).err(),
+        Some(TgswError::SmallEncryptionKey)
+    );
+}
+
+/*The homomorphic operations on TGSW ciphertexts: the results decrypt to the expected message, and their external
+product with LWE(+-1/8) decrypts to +-M/8. The internal product uses a big key with a small noise (see
+internal_product_with_decomposition) and an exact first decomposition (B1^l1 = q)
+*/
+#[test]
+fn homomorphic_operations() {
+    let tfhe_params = BooleanParameters {
+        glwe_dimension: GlweDimension(1),
+        polynomial_size: PolynomialSize(64),
+        ..DEFAULT_PARAMETERS_KS_PBS
+    };
+    let client_key = ClientKey::new(&tfhe_params);
+    let tgsw_params =
+        TgswParams::new_with_decomposition(tfhe_params, 64, 2, DecompositionKind::Balanced);
+    let fine_params =
+        TgswParams::new_with_decomposition(tfhe_params, 16, 8, DecompositionKind::Balanced);
+    let check = |tgsw_ct: &TgswCiphertext, m: u32| {
+        assert_eq!(tgsw_ct.decrypt(&tgsw_params, &client_key), m);
+        for (input, sign) in [(true, 1), (false, 7)] {
+            let product = tgsw_ct
+                .ext_product(&client_key.encrypt(input), &tgsw_params)
+                .unwrap();
+            assert_eq!(decrypt_eighths(&product, &client_key), (sign * m) % 8);
+        }
+    };
+
+    let tgsw_2 = TgswCiphertext::new_encrypt(2, &tgsw_params, &client_key);
+    let tgsw_3 = TgswCiphertext::new_encrypt(3, &tgsw_params, &client_key);
+    check(&tgsw_2.add(&tgsw_3), 5);
+    check(&tgsw_3.scalar_mul(2), 6);
+    let mut assigned = tgsw_2.to_owned();
+    assigned.add_assign(&tgsw_3);
+    assigned.scalar_mul_assign(3);
+    check(&assigned, 15);
+
+    /*TGSW(3) (B1 = 16, l1 = 8) x TGSW(2) (B2 = 64, l2 = 2) = TGSW(6) under the decomposition of TGSW(2)
+    TGSW(3) itself cannot be decrypted, its finest gadget entry q/B1^l1 = 1 is below the noise (see decrypt_with_key)
+    */
+    let fine_3 = TgswCiphertext::new_encrypt(3, &fine_params, &client_key);
+    let product = fine_3.internal_product(&tgsw_2, &fine_params);
+    check(&product, 6);
+    let product = fine_3.internal_product_with_decomposition(
+        &tgsw_2,
+        fine_params.decomp_base,
+        fine_params.decomp_level,
+        DecompositionKind::Unsigned,
+    );
+    check(&product, 6);
+}
+
+//The approximate decompositions, and every TgswParamsError of try_new, new_approximate and validate
+#[test]
+fn parameter_validation() {
+    use TgswParamsError::*;
+    let kind = DecompositionKind::Balanced;
+
+    //The smallest level l with B^l >= 2^(32 - dropped_bits)
+    let client_key = ClientKey::new(&DEFAULT_PARAMETERS);
+    for (B, dropped_bits, l, exact_dropped_bits) in [
+        (16, 8, 6, 8.0),
+        (64, 20, 2, 20.0),
+        (64, 21, 2, 20.0),
+        (4, 0, 16, 0.0),
+    ] {
+        let tgsw_params =
+            TgswParams::new_approximate(DEFAULT_PARAMETERS, B, dropped_bits, kind).unwrap();
+        assert_eq!((tgsw_params.decomp_base, tgsw_params.decomp_level), (B, l));
+        assert_eq!(tgsw_params.dropped_bits(), exact_dropped_bits);
+
+        let tgsw_ct = TgswCiphertext::new_encrypt(3, &tgsw_params, &client_key);
+        let product = tgsw_ct
+            .ext_product(&client_key.encrypt(true), &tgsw_params)
+            .unwrap();
+        assert_eq!(decrypt_eighths(&product, &client_key), 3);
+    }
+
+    let approximate_errors = [
+        (1, 8, BaseTooSmall { decomp_base: 1 }),
+        //64^5 = 2^30 < 2^32 < 64^6
+        (
+            64,
+            0,
+            DroppedBitsOutOfRange {
+                decomp_base: 64,
+                dropped_bits: 0,
+            },
+        ),
+        (
+            16,
+            32,
+            DroppedBitsOutOfRange {
+                decomp_base: 16,
+                dropped_bits: 32,
+            },
+        ),
+        (3, 8, BaseNotPowerOfTwo { decomp_base: 3 }),
+    ];
+    for (B, dropped_bits, expected) in approximate_errors {
+        assert_eq!(
+            TgswParams::new_approximate(DEFAULT_PARAMETERS, B, dropped_bits, kind).err(),
+            Some(expected)
+        );
+    }
+
+    let empty_key = BooleanParameters {
+        lwe_dimension: LweDimension(0),
+        ..DEFAULT_PARAMETERS
+    };
+    let errors = [
+        (DEFAULT_PARAMETERS, 0, 2, BaseTooSmall { decomp_base: 0 }),
+        (DEFAULT_PARAMETERS, 1, 2, BaseTooSmall { decomp_base: 1 }),
+        (
+            DEFAULT_PARAMETERS,
+            12,
+            2,
+            BaseNotPowerOfTwo { decomp_base: 12 },
+        ),
+        (DEFAULT_PARAMETERS, 64, 0, ZeroLevel),
+        (
+            DEFAULT_PARAMETERS,
+            64,
+            6,
+            PrecisionExceedsModulus {
+                decomp_base: 64,
+                decomp_level: 6,
+            },
+        ),
+        (empty_key, 64, 2, EmptyEncryptionKey),
+    ];
+    for (tfhe_params, B, l, expected) in errors {
+        assert_eq!(
+            TgswParams::try_new(tfhe_params, B, l, kind).err(),
+            Some(expected)
+        );
+        //The unchecked parameters (e.g. deserialized ones) give the same error
+        let unchecked = TgswParams::new_with_decomposition(tfhe_params, B, l, kind);
+        assert_eq!(unchecked.validate().err(), Some(expected));
+        assert!(!expected.to_string().is_empty());
+    }
+
+    //l = 1 drops 26 bits, the rounding error makes the gates fail
+    match TgswParams::try_new(DEFAULT_PARAMETERS, 64, 1, kind) {
+        Err(GateTooNoisy {
+            failure_probability,
+        }) => assert!(failure_probability > MAX_GATE_FAILURE_PROBABILITY),
+        other => panic!("Expected GateTooNoisy, got {other:?}"),
+    }
+}
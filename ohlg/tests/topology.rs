@@ -0,0 +1,264 @@
+/*The topology hiding transformations on the bundled Bristol Fashion circuits: each transformed circuit must be valid
+and compute the same plaintext function as the original one, checked on every input with the plaintext simulation
+*/
+use ohlg::bristol::parse_bristol_fashion;
+use ohlg::gate_compiler::GateKind;
+use ohlg::netlist::{Circuit, GateOp, NetlistError};
+use ohlg::oblivious_gates::nop_input_magnitude;
+use ohlg::topology::*;
+use tfhe::core_crypto::commons::math::random::Seed;
+
+mod common;
+use common::bits;
+
+fn circuits() -> Vec<Circuit> {
+    [
+        include_str!("bristol/adder4.txt"),
+        include_str!("bristol/lt4.txt"),
+        include_str!("bristol/misc.txt"),
+    ]
+    .iter()
+    .map(|text| parse_bristol_fashion(text).unwrap())
+    .collect()
+}
+
+fn assert_same_function(original: &Circuit, transformed: &Circuit) {
+    transformed.netlist.validate().unwrap();
+    assert_eq!(
+        transformed.netlist.num_params().unwrap(),
+        transformed.gate_plaintexts.len()
+    );
+    let widths: Vec<usize> = original
+        .netlist
+        .inputs
+        .iter()
+        .map(|port| port.wires.len())
+        .collect();
+    let num_bits: usize = widths.iter().sum();
+    for x in 0..1 << num_bits {
+        let mut next_bit = 0;
+        let inputs: Vec<Vec<bool>> = widths
+            .iter()
+            .map(|&width| {
+                next_bit += width;
+                bits(x >> (next_bit - width), width)
+            })
+            .collect();
+        assert_eq!(
+            transformed.simulate(&inputs).unwrap(),
+            original.simulate(&inputs).unwrap(),
+            "{inputs:?}"
+        );
+    }
+}
+
+//The 2-input gates of a circuit read two distinct wires, as the constants of the 2-input dummy gates do
+fn assert_distinct_inputs(circuit: &Circuit) {
+    for gate in &circuit.netlist.gates {
+        if gate.op == GateOp::TwoOp {
+            assert_ne!(gate.inputs[0], gate.inputs[1], "{gate:?}");
+        }
+    }
+}
+
+#[test]
+fn dummy_gates() {
+    for (index, original) in circuits().iter().enumerate() {
+        let covering = TopologyClass::covering(original).unwrap();
+        assert!(covering.gate_count >= original.netlist.gates.len());
+        assert!(covering.depth.is_power_of_two() && covering.gate_count.is_power_of_two());
+        let mut circuit = original.clone();
+        pad_topology(&mut circuit, covering, Seed(index as u128)).unwrap();
+        assert_eq!(TopologyClass::of(&circuit).unwrap(), covering);
+        assert_same_function(original, &circuit);
+        assert_distinct_inputs(&circuit);
+    }
+
+    //The adder and the comparator have enough slack to insert gates without increasing their depth
+    for (index, original) in circuits()[..2].iter().enumerate() {
+        let mut circuit = original.clone();
+        insert_dummy_gates(&mut circuit, 10, Seed(index as u128)).unwrap();
+        let class = TopologyClass::of(original).unwrap();
+        assert_eq!(
+            TopologyClass::of(&circuit).unwrap(),
+            TopologyClass {
+                gate_count: class.gate_count + 10,
+                depth: class.depth
+            }
+        );
+        assert_same_function(original, &circuit);
+    }
+}
+
+/*A balanced tree has no slack at its own depth, so covering pads it to a deeper class, where the chain padding the depth
+brings the slack. The chain is made of 2-input dummy gates as long as the gate count allows, not of a run of BUF gates
+*/
+#[test]
+fn balanced_tree() {
+    let mut tree = Circuit::new();
+    let x = tree.netlist.add_input_port("x", 4);
+    let y_0 = tree.add_gate_2op(GateKind::And, [x[0], x[1]]);
+    let y_1 = tree.add_gate_2op(GateKind::And, [x[2], x[3]]);
+    let y = tree.add_gate_2op(GateKind::And, [y_0, y_1]);
+    tree.netlist.add_output_port("y", vec![y]);
+    assert_eq!(
+        insert_dummy_gates(&mut tree.clone(), 1, Seed(0)),
+        Err(TopologyError::NoSlack { missing: 1 })
+    );
+
+    let covering = TopologyClass::covering(&tree).unwrap();
+    assert_eq!(
+        covering,
+        TopologyClass {
+            gate_count: 8,
+            depth: 4
+        }
+    );
+    for seed in 0..8 {
+        let mut circuit = tree.clone();
+        hide_topology(&mut circuit, covering, Seed(seed)).unwrap();
+        assert_eq!(TopologyClass::of(&circuit).unwrap(), covering);
+        assert_same_function(&tree, &circuit);
+        assert_distinct_inputs(&circuit);
+    }
+
+    //Two gates per missing layer: the whole chain is made of 2-input dummy gates
+    let class = TopologyClass {
+        gate_count: 3 + 2 * 6,
+        depth: 8,
+    };
+    for seed in 0..8 {
+        let mut circuit = tree.clone();
+        pad_topology(&mut circuit, class, Seed(seed)).unwrap();
+        assert_eq!(TopologyClass::of(&circuit).unwrap(), class);
+        assert_same_function(&tree, &circuit);
+        assert_distinct_inputs(&circuit);
+        assert!(circuit
+            .netlist
+            .gates
+            .iter()
+            .all(|gate| gate.op == GateOp::TwoOp));
+    }
+}
+
+/*A wire that nothing drives nor reads is valid, but must not be drawn as an input of the constant of a 2-input dummy
+gate, which would read an undriven wire
+*/
+#[test]
+fn undriven_wire() {
+    for (index, original) in circuits().iter().enumerate() {
+        let mut original = original.clone();
+        original.netlist.new_wire();
+        original.validate().unwrap();
+        let covering = TopologyClass::covering(&original).unwrap();
+        for seed in 0..8 {
+            let mut circuit = original.clone();
+            hide_topology(&mut circuit, covering, Seed((index * 8 + seed) as u128)).unwrap();
+            circuit.validate().unwrap();
+            assert_same_function(&original, &circuit);
+        }
+    }
+}
+
+#[test]
+fn shuffles() {
+    for (index, original) in circuits().iter().enumerate() {
+        let mut circuit = original.clone();
+        shuffle_gates(&mut circuit, Seed(index as u128)).unwrap();
+        assert_same_function(original, &circuit);
+        shuffle_wires(&mut circuit, Seed(index as u128)).unwrap();
+        assert_same_function(original, &circuit);
+        assert_eq!(
+            TopologyClass::of(&circuit).unwrap(),
+            TopologyClass::of(original).unwrap()
+        );
+    }
+
+    //The same seed gives the same circuit, another seed another order
+    let original = &circuits()[0];
+    let shuffled = |seed: u128| {
+        let mut circuit = original.clone();
+        hide_topology(
+            &mut circuit,
+            TopologyClass::covering(original).unwrap(),
+            Seed(seed),
+        )
+        .unwrap();
+        circuit
+    };
+    assert_eq!(shuffled(1), shuffled(1));
+    assert_ne!(shuffled(1), shuffled(2));
+}
+
+//The adder and the comparator have the same topology once hidden in a common class
+#[test]
+fn common_class() {
+    let circuits = circuits();
+    let class = TopologyClass {
+        gate_count: 64,
+        depth: 16,
+    };
+    for original in &circuits[..2] {
+        let mut circuit = original.clone();
+        hide_topology(&mut circuit, class, Seed(7)).unwrap();
+        assert_eq!(TopologyClass::of(&circuit).unwrap(), class);
+        assert_eq!(circuit.netlist.bootstrap_count(), 64);
+        assert_same_function(original, &circuit);
+    }
+}
+
+#[test]
+fn invalid_transformations() {
+    let adder = &circuits()[0];
+    let class = TopologyClass::of(adder).unwrap();
+    let too_small = TopologyClass {
+        gate_count: class.gate_count + 1,
+        depth: class.depth + 2,
+    };
+    assert_eq!(
+        pad_topology(&mut adder.clone(), too_small, Seed(0)),
+        Err(TopologyError::TooLarge {
+            class: too_small,
+            gate_count: class.gate_count,
+            depth: class.depth
+        })
+    );
+
+    //An output reading a wire out of range is rejected instead of indexing past the wire depths
+    let mut invalid = adder.clone();
+    invalid.netlist.outputs[0].wires[0] = invalid.netlist.num_wires;
+    let error = TopologyError::Netlist(NetlistError::WireOutOfRange {
+        wire: invalid.netlist.num_wires,
+        num_wires: invalid.netlist.num_wires,
+    });
+    assert_eq!(TopologyClass::of(&invalid), Err(error.clone()));
+    assert_eq!(TopologyClass::covering(&invalid), Err(error));
+
+    //A single gate has no slack
+    let mut circuit = Circuit::new();
+    let x = circuit.netlist.add_input_port("x", 2);
+    let y = circuit.add_gate_2op(GateKind::And, [x[0], x[1]]);
+    circuit.netlist.add_output_port("y", vec![y]);
+    assert_eq!(
+        insert_dummy_gates(&mut circuit.clone(), 1, Seed(0)),
+        Err(TopologyError::NoSlack { missing: 1 })
+    );
+
+    //An output re-encoded for a threshold gate cannot be padded with the boolean gates
+    let output_magnitude = nop_input_magnitude(3).unwrap();
+    let z = circuit
+        .netlist
+        .add_gate(GateOp::Reencode { output_magnitude }, vec![y], 0);
+    circuit.netlist.outputs[0].wires[0] = z;
+    assert_eq!(
+        pad_topology(
+            &mut circuit,
+            TopologyClass {
+                gate_count: 4,
+                depth: 4
+            },
+            Seed(0)
+        ),
+        Err(TopologyError::NoBooleanOutput)
+    );
+}
@@ -0,0 +1,154 @@
+/*Import of the bundled Yosys netlists (tests/yosys, the same 2-bit comparator as write_json and write_blif output),
+checked on every input with the plaintext simulation of the netlist, and the import errors
+*/
+use ohlg::netlist::Circuit;
+use ohlg::yosys::*;
+
+mod common;
+use common::{bits, value};
+
+//The outputs eq, gt, sel and misc of the comparator, for a and b of 2 bits
+fn check_cmp2(circuit: &Circuit) {
+    circuit.netlist.validate().unwrap();
+    let names = |ports: &[ohlg::netlist::Port]| -> Vec<String> {
+        ports.iter().map(|port| port.name.clone()).collect()
+    };
+    assert_eq!(names(&circuit.netlist.inputs), ["a", "b"]);
+    assert_eq!(names(&circuit.netlist.outputs), ["eq", "gt", "sel", "misc"]);
+
+    for a in 0..4 {
+        for b in 0..4 {
+            let (m_a, m_b) = (bits(a, 2), bits(b, 2));
+            let outputs = circuit.simulate(&[m_a.clone(), m_b.clone()]).unwrap();
+            let misc = vec![
+                !(m_a[0] && m_a[1]),
+                !(m_b[0] || m_b[1]),
+                m_a[0] ^ m_b[1],
+                !m_a[1] && m_b[0],
+                true,
+                false,
+            ];
+            assert_eq!(value(&outputs[0]), (a == b) as usize, "{a} == {b}");
+            assert_eq!(value(&outputs[1]), (a > b) as usize, "{a} > {b}");
+            assert_eq!(outputs[2], vec![if m_a[0] { m_b[0] } else { m_b[1] }]);
+            assert_eq!(outputs[3], misc, "{a}, {b}");
+        }
+    }
+}
+
+#[test]
+fn json() {
+    let text = include_str!("yosys/cmp2.json");
+    //The module with the top attribute, or any module by name
+    let circuit = parse_yosys_json(text, None).unwrap();
+    check_cmp2(&circuit);
+    //The $_ANDNOT_ and $_ORNOT_ cells take an extra NOT gate, the constants a gate each
+    assert_eq!(circuit.netlist.gates.len(), 16 + 4 + 2);
+
+    let half_adder = parse_yosys_json(text, Some("half_adder")).unwrap();
+    for (x, y) in [(false, false), (false, true), (true, false), (true, true)] {
+        let outputs = half_adder.simulate(&[vec![x], vec![y]]).unwrap();
+        assert_eq!(outputs, vec![vec![x ^ y, x && y]]);
+    }
+    assert_eq!(
+        parse_yosys_json(text, Some("adder")).unwrap_err(),
+        YosysError::MissingModule {
+            name: Some("adder".to_string())
+        }
+    );
+}
+
+#[test]
+fn blif() {
+    check_cmp2(&parse_blif(include_str!("yosys/cmp2.blif")).unwrap());
+}
+
+#[test]
+fn invalid_netlists() {
+    let json = |cells: &str| {
+        format!(
+            r#"{{"modules": {{"top": {{
+                "ports": {{"a": {{"direction": "input", "bits": [2, 3]}}, "y": {{"direction": "output", "bits": [4]}}}},
+                "cells": {{{cells}}}
+            }}}}}}"#
+        )
+    };
+    assert_eq!(
+        parse_yosys_json(
+            &json(r#""ff": {"type": "$_DFF_P_", "connections": {"C": [2], "D": [3], "Q": [4]}}"#),
+            None
+        )
+        .unwrap_err(),
+        YosysError::UnsupportedCell {
+            cell: "ff".to_string(),
+            cell_type: "$_DFF_P_".to_string()
+        }
+    );
+    assert_eq!(
+        parse_yosys_json(
+            &json(
+                r#""g1": {"type": "$_AND_", "connections": {"A": [2], "B": [5], "Y": [4]}},
+                   "g2": {"type": "$_OR_", "connections": {"A": [3], "B": [4], "Y": [5]}}"#
+            ),
+            None
+        )
+        .unwrap_err(),
+        YosysError::CombinationalLoop {
+            cell: "g1".to_string()
+        }
+    );
+    assert_eq!(
+        parse_yosys_json(
+            &json(r#""g": {"type": "$_AND_", "connections": {"A": [2], "B": [7], "Y": [4]}}"#),
+            None
+        )
+        .unwrap_err(),
+        YosysError::UndrivenNet {
+            net: "7".to_string()
+        }
+    );
+    assert_eq!(
+        parse_yosys_json(
+            &json(r#""g": {"type": "$_AND_", "connections": {"A": [2], "B": ["x"], "Y": [4]}}"#),
+            None
+        )
+        .unwrap_err(),
+        YosysError::UndefinedNet {
+            net: "x".to_string()
+        }
+    );
+    assert_eq!(
+        parse_yosys_json(
+            &json(r#""g": {"type": "$_XOR_", "connections": {"A": [2], "B": [3], "Y": [3]}}"#),
+            None
+        )
+        .unwrap_err(),
+        YosysError::MultipleDrivers {
+            net: "3".to_string()
+        }
+    );
+
+    assert_eq!(
+        parse_blif(".model top\n.inputs a b c\n.outputs y\n.names a b c y\n111 1\n.end\n")
+            .unwrap_err(),
+        YosysError::UnsupportedCell {
+            cell: "y".to_string(),
+            cell_type: ".names with 3 inputs".to_string()
+        }
+    );
+    assert_eq!(
+        parse_blif(".model top\n.inputs a\n.outputs y\n.latch a y re clk 0\n.end\n").unwrap_err(),
+        YosysError::UnsupportedCell {
+            cell: "a y re clk 0".to_string(),
+            cell_type: ".latch".to_string()
+        }
+    );
+    assert_eq!(
+        parse_blif(".model top\n.inputs a b\n.outputs y\n.names a b y\n1- 1\n-0 0\n.end\n")
+            .unwrap_err(),
+        YosysError::Syntax {
+            line: 4,
+            message: "a cover mixes ON-set and OFF-set rows".to_string()
+        }
+    );
+}
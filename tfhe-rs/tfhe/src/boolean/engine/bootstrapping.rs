@@ -31,7 +31,7 @@ pub struct BuffersRef<'a> {
 }
 
 impl Memory {
-    fn as_buffers(&mut self, server_key: &ServerKey) -> BuffersRef<'_> {
+    fn as_buffers(&mut self, server_key: &ServerKey, output_value: u32) -> BuffersRef<'_> {
         let num_elem_in_accumulator = server_key.bootstrapping_key.glwe_size().0
             * server_key.bootstrapping_key.polynomial_size().0;
         let num_elem_in_lwe_after_ks = server_key.key_switching_key.output_lwe_size().0;
@@ -61,7 +61,7 @@ impl Memory {
         );
 
         acc.get_mut_mask().as_mut().fill(0u32);
-        acc.get_mut_body().as_mut().fill(PLAINTEXT_TRUE);
+        acc.get_mut_body().as_mut().fill(output_value);
 
         let (after_ks_elements, after_pbs_elements) =
             other_elements.split_at_mut(num_elem_in_lwe_after_ks);
@@ -454,7 +454,7 @@ impl Bootstrapper {
             lookup_table: accumulator,
             mut buffer_lwe_after_pbs,
             ..
-        } = self.memory.as_buffers(server_key);
+        } = self.memory.as_buffers(server_key, PLAINTEXT_TRUE);
 
         let fourier_bsk = &server_key.bootstrapping_key;
 
@@ -491,12 +491,13 @@ impl Bootstrapper {
         &mut self,
         mut ciphertext: LweCiphertextOwned<u32>,
         server_key: &ServerKey,
+        output_value: u32,
     ) -> Ciphertext {
         let BuffersRef {
             lookup_table,
             mut buffer_lwe_after_pbs,
             ..
-        } = self.memory.as_buffers(server_key);
+        } = self.memory.as_buffers(server_key, output_value);
 
         let fourier_bsk = &server_key.bootstrapping_key;
 
@@ -538,12 +539,13 @@ impl Bootstrapper {
         &mut self,
         mut ciphertext: LweCiphertextOwned<u32>,
         server_key: &ServerKey,
+        output_value: u32,
     ) -> Ciphertext {
         let BuffersRef {
             lookup_table,
             mut buffer_lwe_after_ks,
             ..
-        } = self.memory.as_buffers(server_key);
+        } = self.memory.as_buffers(server_key, output_value);
 
         let fourier_bsk = &server_key.bootstrapping_key;
 
@@ -584,10 +586,22 @@ impl Bootstrapper {
         &mut self,
         ct: LweCiphertextOwned<u32>,
         server_key: &ServerKey,
+    ) -> Ciphertext {
+        self.apply_bootstrapping_pattern_with_output(ct, server_key, PLAINTEXT_TRUE)
+    }
+
+    /// Same as [`Self::apply_bootstrapping_pattern`], but the output encodes `output_value` for a
+    /// phase in (0, 1/2) and `-output_value` for a phase in (1/2, 1) instead of the boolean
+    /// encoding (`PLAINTEXT_TRUE` and `PLAINTEXT_FALSE`).
+    pub fn apply_bootstrapping_pattern_with_output(
+        &mut self,
+        ct: LweCiphertextOwned<u32>,
+        server_key: &ServerKey,
+        output_value: u32,
     ) -> Ciphertext {
         match server_key.pbs_order {
-            PBSOrder::KeyswitchBootstrap => self.keyswitch_bootstrap(ct, server_key),
-            PBSOrder::BootstrapKeyswitch => self.bootstrap_keyswitch(ct, server_key),
+            PBSOrder::KeyswitchBootstrap => self.keyswitch_bootstrap(ct, server_key, output_value),
+            PBSOrder::BootstrapKeyswitch => self.bootstrap_keyswitch(ct, server_key, output_value),
         }
     }
 }
@@ -293,6 +293,25 @@ cks1 has {choice1:?}, cks2 has: {choice2:?}
 
         Ciphertext::Encrypted(output)
     }
+    pub fn encrypt_abs_with_public_key(&mut self, message: u32, pks: &PublicKey) -> Ciphertext {
+        let plain: Plaintext<u32> = Plaintext(message);
+
+        let mut output = LweCiphertext::new(
+            0u32,
+            pks.lwe_public_key.lwe_size(),
+            CiphertextModulus::new_native(),
+        );
+
+        encrypt_lwe_ciphertext_with_public_key(
+            &pks.lwe_public_key,
+            &mut output,
+            plain,
+            &mut self.secret_generator,
+        );
+
+        Ciphertext::Encrypted(output)
+    }
+
     pub fn encrypt_with_compressed_public_key(
         &mut self,
         message: bool,
@@ -320,6 +339,29 @@ cks1 has {choice1:?}, cks2 has: {choice2:?}
         Ciphertext::Encrypted(output)
     }
 
+    pub fn encrypt_abs_with_compressed_public_key(
+        &mut self,
+        message: u32,
+        compressed_pk: &CompressedPublicKey,
+    ) -> Ciphertext {
+        let plain: Plaintext<u32> = Plaintext(message);
+
+        let mut output = LweCiphertext::new(
+            0u32,
+            compressed_pk.compressed_lwe_public_key.lwe_size(),
+            CiphertextModulus::new_native(),
+        );
+
+        encrypt_lwe_ciphertext_with_seeded_public_key(
+            &compressed_pk.compressed_lwe_public_key,
+            &mut output,
+            plain,
+            &mut self.secret_generator,
+        );
+
+        Ciphertext::Encrypted(output)
+    }
+
     pub fn decrypt(&self, ct: &Ciphertext, cks: &ClientKey) -> bool {
         match ct {
             Ciphertext::Trivial(b) => *b,
@@ -131,6 +131,34 @@ impl CompressedPublicKey {
             engine.encrypt_with_compressed_public_key(message, self)
         })
     }
+
+    /// Encrypt a raw u32 plaintext (absolute torus value) using the compressed public key.
+    ///
+    /// The public key masks are regenerated for each encryption, decompress the key first when
+    /// encrypting many values.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tfhe::boolean::prelude::*;
+    ///
+    /// // Generate the client key and the server key:
+    /// let (cks, sks) = gen_keys();
+    ///
+    /// let cpks = CompressedPublicKey::new(&cks);
+    ///
+    /// // Encryption of 1/8, which decrypts to true:
+    /// let ct = cpks.encrypt_abs(1 << 29);
+    ///
+    /// // Decryption:
+    /// let dec = cks.decrypt(&ct);
+    /// assert!(dec);
+    /// ```
+    pub fn encrypt_abs(&self, message: u32) -> Ciphertext {
+        BooleanEngine::with_thread_local_mut(|engine| {
+            engine.encrypt_abs_with_compressed_public_key(message, self)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -44,6 +44,31 @@ impl PublicKey {
         BooleanEngine::with_thread_local_mut(|engine| engine.encrypt_with_public_key(message, self))
     }
 
+    /// Encrypt a raw u32 plaintext (absolute torus value) using the public key.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tfhe::boolean::prelude::*;
+    ///
+    /// // Generate the client key and the server key:
+    /// let (cks, sks) = gen_keys();
+    ///
+    /// let pks = PublicKey::new(&cks);
+    ///
+    /// // Encryption of 1/8, which decrypts to true:
+    /// let ct = pks.encrypt_abs(1 << 29);
+    ///
+    /// // Decryption:
+    /// let dec = cks.decrypt(&ct);
+    /// assert!(dec);
+    /// ```
+    pub fn encrypt_abs(&self, message: u32) -> Ciphertext {
+        BooleanEngine::with_thread_local_mut(|engine| {
+            engine.encrypt_abs_with_public_key(message, self)
+        })
+    }
+
     /// Allocate and generate a client key.
     ///
     /// # Example